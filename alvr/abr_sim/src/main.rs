@@ -0,0 +1,126 @@
+mod simulation;
+mod trace;
+
+use alvr_common::anyhow::{bail, Result};
+use alvr_session::{BitrateModeDefaultVariant, SessionConfig};
+use pico_args::Arguments;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use trace::TraceSample;
+
+const HELP_STR: &str = r#"
+alvr_abr_sim
+Replay the network conditions recorded in a session_log.txt against a bitrate mode.
+
+USAGE:
+    alvr_abr_sim <SESSION_LOG> [ARGS]
+
+FLAGS:
+    --help              Print this text
+
+ARGS:
+    --session <PATH>    session.json to take the bitrate settings from. Defaults are used otherwise
+    --mode <MODE>       Override the bitrate mode: constant, adaptive, nestvr or delay-based
+    --fps <FPS>         Nominal framerate. By default the server framerate of the trace is used
+    --output <PATH>     Per-decision CSV output. Default: abr_sim.csv
+"#;
+
+const DEFAULT_FRAMERATE: f32 = 72.0;
+
+fn run() -> Result<()> {
+    let mut args = Arguments::from_env();
+
+    if args.contains(["-h", "--help"]) {
+        println!("{HELP_STR}");
+        return Ok(());
+    }
+
+    let session_path: Option<PathBuf> = args.opt_value_from_str("--session")?;
+    let mode: Option<String> = args.opt_value_from_str("--mode")?;
+    let framerate: Option<f32> = args.opt_value_from_str("--fps")?;
+    let output_path: PathBuf = args
+        .opt_value_from_str("--output")?
+        .unwrap_or_else(|| PathBuf::from("abr_sim.csv"));
+    let log_path: PathBuf = args.free_from_str()?;
+
+    let remaining = args.finish();
+    if !remaining.is_empty() {
+        bail!("Unrecognized arguments: {remaining:?}");
+    }
+
+    let mut session = SessionConfig::default();
+    if let Some(path) = session_path {
+        session.merge_from_json(&serde_json::from_str(&fs::read_to_string(path)?)?)?;
+    }
+    if let Some(mode) = mode {
+        session.session_settings.video.bitrate.mode.variant = match mode.as_str() {
+            "constant" => BitrateModeDefaultVariant::ConstantMbps,
+            "adaptive" => BitrateModeDefaultVariant::Adaptive,
+            "nestvr" => BitrateModeDefaultVariant::NestVr,
+            "delay-based" => BitrateModeDefaultVariant::DelayBased,
+            _ => bail!("Unrecognized bitrate mode: {mode}"),
+        };
+    }
+    let config = session.to_settings().video.bitrate;
+
+    let trace = trace::load_session_log(&log_path)?;
+
+    let framerate = framerate.unwrap_or_else(|| {
+        trace
+            .iter()
+            .find_map(|entry| match &entry.sample {
+                TraceSample::Network(stats) if stats.server_fps > 0.0 => Some(stats.server_fps),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_FRAMERATE)
+    });
+
+    let (records, summary) = simulation::run(&trace, &config, framerate);
+
+    let mut writer = BufWriter::new(File::create(&output_path)?);
+    writeln!(
+        writer,
+        "time_s,bitrate_bps,framerate,capacity_bps,rtt_ms,shards_lost"
+    )?;
+    for record in &records {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            record.time_s,
+            record.bitrate_bps,
+            record.framerate,
+            record.capacity_bps,
+            record.rtt_ms,
+            record.shards_lost
+        )?;
+    }
+    writer.flush()?;
+
+    let above_capacity_percent = if summary.duration_s > 0.0 {
+        summary.time_above_capacity_s / summary.duration_s * 100.0
+    } else {
+        0.0
+    };
+    println!("Trace duration: {:.2} s", summary.duration_s);
+    println!("Decisions: {}", records.len());
+    println!("Mean bitrate: {:.2} Mbps", summary.mean_bitrate_bps / 1e6);
+    println!(
+        "Time above capacity: {:.2} s ({above_capacity_percent:.1}%)",
+        summary.time_above_capacity_s
+    );
+    println!("Bitrate switches: {}", summary.switches);
+    println!("Decisions written to {}", output_path.display());
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{e}");
+        println!("{HELP_STR}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::trace::{TraceEntry, TraceSample};
+use alvr_bitrate::{BitrateManager, NetworkStatisticsReport};
+use alvr_common::SimulatedClock;
+use alvr_session::{BitrateConfig, BitrateMode};
+use std::{sync::Arc, time::Duration};
+
+const DEFAULT_INITIAL_BITRATE_MBPS: f32 = 30.0;
+
+pub struct DecisionRecord {
+    pub time_s: f64,
+    pub bitrate_bps: u64,
+    pub framerate: f32,
+    pub capacity_bps: f32,
+    pub rtt_ms: f32,
+    pub shards_lost: isize,
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub duration_s: f64,
+    pub mean_bitrate_bps: f64,
+    pub time_above_capacity_s: f64,
+    pub switches: usize,
+}
+
+fn secs(value: f32) -> Duration {
+    // note: max() also maps NaN to zero
+    Duration::from_secs_f32(value.max(0.0))
+}
+
+// Replay the trace open-loop: capacity, RTT and delay gradient are taken from the trace as they
+// were measured during the session, while the throughput seen by the client is modeled as the
+// simulated bitrate capped by the capacity. Each network sample is treated as one frame.
+pub fn run(
+    trace: &[TraceEntry],
+    config: &BitrateConfig,
+    framerate: f32,
+) -> (Vec<DecisionRecord>, Summary) {
+    let initial_bitrate_mbps = match &config.mode {
+        BitrateMode::NestVr {
+            initial_bitrate_mbps,
+            ..
+        }
+        | BitrateMode::DelayBased {
+            initial_bitrate_mbps,
+            ..
+        } => *initial_bitrate_mbps,
+        _ => DEFAULT_INITIAL_BITRATE_MBPS,
+    };
+
+    // The manager timing (update interval, frame intervals) follows the trace timestamps
+    let clock = Arc::new(SimulatedClock::new());
+    let mut manager = BitrateManager::new(
+        Arc::clone(&clock) as _,
+        config.history_size,
+        framerate,
+        initial_bitrate_mbps,
+    );
+
+    let mut records = vec![];
+    let mut summary = Summary::default();
+
+    let mut bitrate_bps = initial_bitrate_mbps as f64 * 1e6;
+    let mut capacity_bps = 0.0;
+    let mut rtt_ms = 0.0;
+    let mut shards_lost = 0;
+    let mut bitrate_time_integral = 0.0;
+    let mut prev_time_s = 0.0;
+    for entry in trace {
+        let interval_s = entry.time_s - prev_time_s;
+        prev_time_s = entry.time_s;
+
+        bitrate_time_integral += bitrate_bps * interval_s;
+        if capacity_bps > 0.0 && bitrate_bps > capacity_bps as f64 {
+            summary.time_above_capacity_s += interval_s;
+        }
+
+        clock.set_elapsed(Duration::from_secs_f64(entry.time_s));
+
+        match &entry.sample {
+            TraceSample::Network(stats) => {
+                // Frames with a single shard have no span and give no capacity estimate
+                if stats.peak_network_throughput_bps > 0.0 {
+                    capacity_bps = stats.peak_network_throughput_bps;
+                }
+                rtt_ms = stats.rtt_ms;
+                shards_lost = stats.shards_lost;
+
+                let instant_throughput_bps = if capacity_bps > 0.0 {
+                    f32::min(bitrate_bps as f32, capacity_bps)
+                } else {
+                    bitrate_bps as f32
+                };
+
+                manager.report_frame_present(&config.adapt_to_framerate);
+                manager.report_network_statistics(NetworkStatisticsReport {
+                    rtt: secs(stats.rtt_ms / 1000.0),
+                    peak_throughput_bps: capacity_bps,
+                    frame_interarrival_s: stats.frame_interarrival_ms / 1000.0,
+                    instant_throughput_bps,
+                    filtered_ow_delay_s: stats.filtered_ow_delay_ms / 1000.0,
+                    shards_lost: stats.shards_lost,
+                    shards_sent: stats.shards_sent,
+                    frames_skipped: stats.frames_skipped,
+                });
+            }
+            TraceSample::Frame(stats) => {
+                let timestamp = Duration::from_secs_f64(entry.time_s);
+                let frame_size_bytes = bitrate_bps / framerate as f64 / 8.0;
+
+                manager.report_frame_encoded(
+                    timestamp,
+                    secs(stats.encoder_s),
+                    frame_size_bytes as usize,
+                );
+                manager.report_frame_latencies(
+                    &config.mode,
+                    timestamp,
+                    secs(stats.network_s),
+                    secs(stats.decoder_s),
+                );
+            }
+        }
+
+        if let Some((params, _)) = manager.get_encoder_params(config) {
+            if !records.is_empty() && params.bitrate_bps as f64 != bitrate_bps {
+                summary.switches += 1;
+            }
+            bitrate_bps = params.bitrate_bps as f64;
+
+            records.push(DecisionRecord {
+                time_s: entry.time_s,
+                bitrate_bps: params.bitrate_bps,
+                framerate: params.framerate,
+                capacity_bps,
+                rtt_ms,
+                shards_lost,
+            });
+        }
+    }
+
+    summary.duration_s = prev_time_s;
+    if summary.duration_s > 0.0 {
+        summary.mean_bitrate_bps = bitrate_time_integral / summary.duration_s;
+    }
+
+    (records, summary)
+}
@@ -0,0 +1,173 @@
+use alvr_common::anyhow::{bail, Result};
+use alvr_events::{Event, EventType, GraphNetworkStatistics, GraphStatistics};
+use std::{fs, path::Path};
+
+const SECONDS_IN_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+pub enum TraceSample {
+    Network(GraphNetworkStatistics),
+    Frame(GraphStatistics),
+}
+
+pub struct TraceEntry {
+    // Seconds since the first entry of the trace. Entries are sorted by time
+    pub time_s: f64,
+    pub sample: TraceSample,
+}
+
+// Event timestamps have the format "%H:%M:%S.%f" (local time of day)
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let mut parts = timestamp.split(':');
+    let hours = parts.next()?.parse::<f64>().ok()?;
+    let minutes = parts.next()?.parse::<f64>().ok()?;
+    let seconds = parts.next()?.parse::<f64>().ok()?;
+
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+// Extract the network and frame statistics events from the content of a session log. Lines that
+// are not events (or events of other types) are ignored.
+fn parse_session_log(content: &str) -> Vec<TraceEntry> {
+    let mut entries = vec![];
+    let mut prev_time_s = 0.0;
+    let mut day_offset_s = 0.0;
+    for line in content.lines() {
+        let Ok(event) = serde_json::from_str::<Event>(line) else {
+            continue;
+        };
+
+        let sample = match event.event_type {
+            EventType::GraphNetworkStatistics(stats) => TraceSample::Network(stats),
+            EventType::GraphStatistics(stats) => TraceSample::Frame(stats),
+            _ => continue,
+        };
+
+        let Some(mut time_s) = parse_timestamp(&event.timestamp) else {
+            continue;
+        };
+
+        // The timestamp does not contain the date. Handle sessions running past midnight. Small
+        // backward jumps can happen because events are logged from different threads
+        if time_s + day_offset_s < prev_time_s - SECONDS_IN_DAY / 2.0 {
+            day_offset_s += SECONDS_IN_DAY;
+        }
+        time_s += day_offset_s;
+        prev_time_s = time_s;
+
+        entries.push(TraceEntry { time_s, sample });
+    }
+
+    entries.sort_by(|a, b| a.time_s.total_cmp(&b.time_s));
+    if let Some(first_time_s) = entries.first().map(|entry| entry.time_s) {
+        for entry in &mut entries {
+            entry.time_s -= first_time_s;
+        }
+    }
+
+    entries
+}
+
+pub fn load_session_log(path: &Path) -> Result<Vec<TraceEntry>> {
+    let entries = parse_session_log(&fs::read_to_string(path)?);
+
+    if !entries
+        .iter()
+        .any(|entry| matches!(entry.sample, TraceSample::Network(_)))
+    {
+        bail!(
+            "No GraphNetworkStatistics events found in {}",
+            path.display()
+        );
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_line(timestamp: &str, event_type: EventType) -> String {
+        serde_json::to_string(&Event {
+            timestamp: timestamp.into(),
+            event_type,
+        })
+        .unwrap()
+    }
+
+    fn network_line(timestamp: &str, frame_index: u32) -> String {
+        event_line(
+            timestamp,
+            EventType::GraphNetworkStatistics(GraphNetworkStatistics {
+                frame_index,
+                ..Default::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("01:02:03.5"), Some(3723.5));
+        assert_eq!(parse_timestamp("00:00:00"), Some(0.0));
+        assert_eq!(parse_timestamp("12:30"), None);
+        assert_eq!(parse_timestamp("aa:00:00"), None);
+    }
+
+    #[test]
+    fn test_parse_session_log_filtering() {
+        let content = [
+            "not an event".into(),
+            network_line("10:00:01.0", 1),
+            event_line("10:00:01.5", EventType::ServerRequestsSelfRestart),
+            event_line(
+                "10:00:02.0",
+                EventType::GraphStatistics(GraphStatistics::default()),
+            ),
+            network_line("bad timestamp", 2),
+        ]
+        .join("\n");
+
+        let entries = parse_session_log(&content);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].time_s, 0.0);
+        assert!(matches!(
+            entries[0].sample,
+            TraceSample::Network(GraphNetworkStatistics { frame_index: 1, .. })
+        ));
+        assert_eq!(entries[1].time_s, 1.0);
+        assert!(matches!(entries[1].sample, TraceSample::Frame(_)));
+    }
+
+    #[test]
+    fn test_parse_session_log_ordering() {
+        // Events logged from different threads can be slightly out of order
+        let content = [
+            network_line("10:00:02.0", 2),
+            network_line("10:00:01.0", 1),
+            network_line("10:00:03.0", 3),
+        ]
+        .join("\n");
+
+        let times = parse_session_log(&content)
+            .iter()
+            .map(|entry| entry.time_s)
+            .collect::<Vec<_>>();
+
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_parse_session_log_past_midnight() {
+        let content = [network_line("23:59:59.0", 1), network_line("00:00:01.0", 2)].join("\n");
+
+        let entries = parse_session_log(&content);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].time_s, 2.0);
+        assert!(matches!(
+            entries[1].sample,
+            TraceSample::Network(GraphNetworkStatistics { frame_index: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use super::{BitrateContext, BitrateController};
+use crate::DynamicEncoderParams;
+use alvr_common::SlidingWindowAverage;
+use alvr_events::NominalBitrateStats;
+use alvr_session::{settings_schema::Switch, BitrateConfig, BitrateMode};
+use std::{collections::VecDeque, time::Duration};
+
+pub struct AdaptiveController {
+    // note: why packet_sizes_bits_history is a queue and not a sliding average? Because some
+    // network samples will be dropped but not any packet size sample
+    packet_sizes_bits_history: VecDeque<(Duration, usize)>,
+    encoder_latency_average: SlidingWindowAverage<Duration>,
+    network_latency_average: SlidingWindowAverage<Duration>,
+    bitrate_average: SlidingWindowAverage<f32>,
+    decoder_latency_overstep_count: usize,
+    dynamic_max_bitrate: f32,
+}
+
+impl AdaptiveController {
+    pub fn new(max_history_size: usize, initial_bitrate: f32) -> Self {
+        Self {
+            packet_sizes_bits_history: VecDeque::new(),
+            encoder_latency_average: SlidingWindowAverage::new(
+                Duration::from_millis(5),
+                max_history_size,
+            ),
+            network_latency_average: SlidingWindowAverage::new(
+                Duration::from_millis(5),
+                max_history_size,
+            ),
+            bitrate_average: SlidingWindowAverage::new(initial_bitrate * 1e6, max_history_size),
+            decoder_latency_overstep_count: 0,
+            dynamic_max_bitrate: f32::MAX,
+        }
+    }
+}
+
+impl BitrateController for AdaptiveController {
+    fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        self.encoder_latency_average.submit_sample(encoder_latency);
+
+        self.packet_sizes_bits_history
+            .push_back((timestamp, size_bytes * 8));
+    }
+
+    fn report_frame_latencies(
+        &mut self,
+        mode: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    ) -> bool {
+        self.network_latency_average.submit_sample(network_latency);
+
+        while let Some(&(timestamp_, size_bits)) = self.packet_sizes_bits_history.front() {
+            if timestamp_ == timestamp {
+                self.bitrate_average
+                    .submit_sample(size_bits as f32 / network_latency.as_secs_f32());
+
+                self.packet_sizes_bits_history.pop_front();
+
+                break;
+            } else {
+                self.packet_sizes_bits_history.pop_front();
+            }
+        }
+
+        if let BitrateMode::Adaptive {
+            decoder_latency_limiter: Switch::Enabled(config),
+            ..
+        } = mode
+        {
+            if decoder_latency > Duration::from_millis(config.max_decoder_latency_ms) {
+                self.decoder_latency_overstep_count += 1;
+
+                if self.decoder_latency_overstep_count == config.latency_overstep_frames {
+                    self.dynamic_max_bitrate =
+                        f32::min(self.bitrate_average.get_average(), self.dynamic_max_bitrate)
+                            * config.latency_overstep_multiplier;
+
+                    self.decoder_latency_overstep_count = 0;
+
+                    return true;
+                }
+            } else {
+                self.decoder_latency_overstep_count = 0;
+            }
+        }
+
+        false
+    }
+
+    fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+        context: &BitrateContext,
+    ) -> (DynamicEncoderParams, NominalBitrateStats) {
+        let BitrateMode::Adaptive {
+            saturation_multiplier,
+            max_bitrate_mbps,
+            min_bitrate_mbps,
+            max_network_latency_ms,
+            encoder_latency_limiter,
+            ..
+        } = &config.mode
+        else {
+            unreachable!()
+        };
+
+        let mut stats = NominalBitrateStats::default();
+
+        let initial_bitrate_average_bps = self.bitrate_average.get_average();
+
+        let mut bitrate_bps = initial_bitrate_average_bps * saturation_multiplier;
+        stats.scaled_calculated_bps = Some(bitrate_bps);
+
+        bitrate_bps = f32::min(bitrate_bps, self.dynamic_max_bitrate);
+        stats.decoder_latency_limiter_bps = Some(self.dynamic_max_bitrate);
+
+        if let Switch::Enabled(max_ms) = max_network_latency_ms {
+            let max = initial_bitrate_average_bps * (*max_ms as f32 / 1000.0)
+                / self.network_latency_average.get_average().as_secs_f32();
+            bitrate_bps = f32::min(bitrate_bps, max);
+
+            stats.network_latency_limiter_bps = Some(max);
+        }
+
+        if let Switch::Enabled(config) = encoder_latency_limiter {
+            let saturation = self.encoder_latency_average.get_average().as_secs_f32()
+                / context.nominal_frame_interval.as_secs_f32();
+            let max = initial_bitrate_average_bps * config.max_saturation_multiplier / saturation;
+            stats.encoder_latency_limiter_bps = Some(max);
+
+            if saturation > config.max_saturation_multiplier {
+                // Note: this assumes linear relationship between bitrate and encoder
+                // latency but this may not be the case
+                bitrate_bps = f32::min(bitrate_bps, max);
+            }
+        }
+
+        if let Switch::Enabled(max) = max_bitrate_mbps {
+            let max = *max as f32 * 1e6;
+            bitrate_bps = f32::min(bitrate_bps, max);
+
+            stats.manual_max_bps = Some(max);
+        }
+        if let Switch::Enabled(min) = min_bitrate_mbps {
+            let min = *min as f32 * 1e6;
+            bitrate_bps = f32::max(bitrate_bps, min);
+
+            stats.manual_min_bps = Some(min);
+        }
+
+        stats.requested_bps = bitrate_bps;
+
+        (context.encoder_params(config, bitrate_bps), stats)
+    }
+}
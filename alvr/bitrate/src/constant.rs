@@ -0,0 +1,33 @@
+use super::{BitrateContext, BitrateController};
+use crate::DynamicEncoderParams;
+use alvr_events::NominalBitrateStats;
+use alvr_session::{BitrateConfig, BitrateMode};
+use std::time::Duration;
+
+pub struct ConstantController;
+
+impl BitrateController for ConstantController {
+    fn update_interval(&self, _mode: &BitrateMode) -> Option<Duration> {
+        None
+    }
+
+    fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+        context: &BitrateContext,
+    ) -> (DynamicEncoderParams, NominalBitrateStats) {
+        let BitrateMode::ConstantMbps(bitrate_mbps) = &config.mode else {
+            unreachable!()
+        };
+
+        let bitrate_bps = *bitrate_mbps as f32 * 1e6;
+
+        (
+            context.encoder_params(config, bitrate_bps),
+            NominalBitrateStats {
+                requested_bps: bitrate_bps,
+                ..Default::default()
+            },
+        )
+    }
+}
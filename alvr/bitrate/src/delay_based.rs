@@ -0,0 +1,253 @@
+use super::{BitrateContext, BitrateController, NetworkStatisticsReport};
+use crate::DynamicEncoderParams;
+use alvr_common::SlidingWindowAverage;
+use alvr_events::{
+    BandwidthUsage, DelayBasedStats, EventType, NominalBitrateStats, RateControlState,
+};
+use alvr_session::{settings_schema::Switch, BitrateConfig, BitrateMode};
+use std::{collections::VecDeque, time::Duration};
+
+// Samples too far above the threshold are not used to adapt it, to avoid following sudden spikes
+// caused by e.g. route changes (GCC, section 5.4)
+const MAX_THRESHOLD_ADAPTATION_DELTA_MS: f32 = 15.0;
+const MAX_ADAPTATION_INTERVAL_MS: f32 = 100.0;
+// The bitrate should not exceed the throughput measured by the client by too much
+const MAX_RECEIVED_BITRATE_RATIO: f32 = 1.5;
+
+struct DelaySample {
+    filtered_ow_delay_ms: f32,
+    interval_ms: f32,
+}
+
+// Implementation of the Google Congestion Control delay-based controller: an overuse detector
+// with adaptive threshold fed by the Kalman-filtered one-way delay gradient, followed by an AIMD
+// rate controller. Network samples are queued and processed when the bitrate is updated, since
+// only then the configuration is available.
+pub struct DelayBasedController {
+    max_history_size: usize,
+    pending_samples: VecDeque<DelaySample>,
+    received_bitrate_average: SlidingWindowAverage<f32>,
+
+    threshold_ms: Option<f32>,
+    overuse_time_ms: f32,
+    prev_filtered_ow_delay_ms: f32,
+    bandwidth_usage: BandwidthUsage,
+
+    rate_control_state: RateControlState,
+}
+
+impl DelayBasedController {
+    pub fn new(max_history_size: usize) -> Self {
+        Self {
+            max_history_size,
+            pending_samples: VecDeque::new(),
+            received_bitrate_average: SlidingWindowAverage::new(0.0, max_history_size),
+
+            threshold_ms: None,
+            overuse_time_ms: 0.0,
+            prev_filtered_ow_delay_ms: 0.0,
+            bandwidth_usage: BandwidthUsage::Normal,
+
+            rate_control_state: RateControlState::Hold,
+        }
+    }
+
+    // Returns the hypothesis for the current sample
+    #[allow(clippy::too_many_arguments)]
+    fn detect(
+        &mut self,
+        sample: &DelaySample,
+        initial_threshold_ms: f32,
+        min_threshold_ms: f32,
+        max_threshold_ms: f32,
+        threshold_gain_up: f32,
+        threshold_gain_down: f32,
+        overuse_time_threshold_ms: f32,
+    ) -> BandwidthUsage {
+        let threshold_ms = *self.threshold_ms.get_or_insert(initial_threshold_ms);
+        let delay_ms = sample.filtered_ow_delay_ms;
+
+        let usage = if delay_ms > threshold_ms {
+            self.overuse_time_ms += sample.interval_ms;
+
+            if self.overuse_time_ms > overuse_time_threshold_ms
+                && delay_ms >= self.prev_filtered_ow_delay_ms
+            {
+                self.overuse_time_ms = 0.0;
+
+                BandwidthUsage::Overuse
+            } else {
+                // Keep the previous hypothesis until overuse is confirmed
+                self.bandwidth_usage
+            }
+        } else if delay_ms < -threshold_ms {
+            self.overuse_time_ms = 0.0;
+
+            BandwidthUsage::Underuse
+        } else {
+            self.overuse_time_ms = 0.0;
+
+            BandwidthUsage::Normal
+        };
+        self.prev_filtered_ow_delay_ms = delay_ms;
+        self.bandwidth_usage = usage;
+
+        // Adapt threshold
+        let delta_ms = delay_ms.abs() - threshold_ms;
+        if delta_ms <= MAX_THRESHOLD_ADAPTATION_DELTA_MS {
+            let gain = if delta_ms < 0.0 {
+                threshold_gain_down
+            } else {
+                threshold_gain_up
+            };
+
+            self.threshold_ms = Some(
+                (threshold_ms
+                    + gain * delta_ms * sample.interval_ms.min(MAX_ADAPTATION_INTERVAL_MS))
+                .clamp(min_threshold_ms, max_threshold_ms),
+            );
+        }
+
+        usage
+    }
+}
+
+impl BitrateController for DelayBasedController {
+    fn update_interval(&self, mode: &BitrateMode) -> Option<Duration> {
+        if let BitrateMode::DelayBased {
+            update_interval_s, ..
+        } = mode
+        {
+            Some(Duration::from_secs_f32(*update_interval_s))
+        } else {
+            None
+        }
+    }
+
+    fn report_network_statistics(&mut self, report: &NetworkStatisticsReport) {
+        self.received_bitrate_average
+            .submit_sample(report.instant_throughput_bps);
+
+        if self.pending_samples.len() >= self.max_history_size {
+            self.pending_samples.pop_front();
+        }
+        self.pending_samples.push_back(DelaySample {
+            filtered_ow_delay_ms: report.filtered_ow_delay_s * 1000.0,
+            interval_ms: report.frame_interarrival_s * 1000.0,
+        });
+    }
+
+    fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+        context: &BitrateContext,
+    ) -> (DynamicEncoderParams, NominalBitrateStats) {
+        let BitrateMode::DelayBased {
+            update_interval_s,
+            max_bitrate_mbps,
+            min_bitrate_mbps,
+            initial_threshold_ms,
+            min_threshold_ms,
+            max_threshold_ms,
+            threshold_gain_up,
+            threshold_gain_down,
+            overuse_time_threshold_ms,
+            increase_factor,
+            decrease_factor,
+            ..
+        } = &config.mode
+        else {
+            unreachable!()
+        };
+
+        let mut stats = NominalBitrateStats::default();
+
+        // Overuse detector. Any overuse since the last update takes precedence over later samples
+        let mut usage = self.bandwidth_usage;
+        let mut overuse_detected = false;
+        while let Some(sample) = self.pending_samples.pop_front() {
+            usage = self.detect(
+                &sample,
+                *initial_threshold_ms,
+                *min_threshold_ms,
+                *max_threshold_ms,
+                *threshold_gain_up,
+                *threshold_gain_down,
+                *overuse_time_threshold_ms,
+            );
+            overuse_detected |= usage == BandwidthUsage::Overuse;
+        }
+        if overuse_detected {
+            usage = BandwidthUsage::Overuse;
+        }
+
+        // Rate controller state machine
+        let rate_control_state = match (usage, self.rate_control_state) {
+            (BandwidthUsage::Overuse, _) => RateControlState::Decrease,
+            (BandwidthUsage::Underuse, _) => RateControlState::Hold,
+            (BandwidthUsage::Normal, RateControlState::Hold) => RateControlState::Increase,
+            (BandwidthUsage::Normal, RateControlState::Increase) => RateControlState::Increase,
+            (BandwidthUsage::Normal, RateControlState::Decrease) => RateControlState::Hold,
+        };
+
+        let received_bitrate_bps = self.received_bitrate_average.get_average();
+        let mut bitrate_bps = context.last_target_bitrate_bps;
+
+        match rate_control_state {
+            RateControlState::Increase => {
+                bitrate_bps *= increase_factor.powf(*update_interval_s);
+
+                if received_bitrate_bps > 0.0 {
+                    bitrate_bps = f32::min(
+                        bitrate_bps,
+                        MAX_RECEIVED_BITRATE_RATIO * received_bitrate_bps,
+                    );
+                }
+            }
+            RateControlState::Decrease => {
+                if received_bitrate_bps > 0.0 {
+                    bitrate_bps = f32::min(bitrate_bps, *decrease_factor * received_bitrate_bps);
+                } else {
+                    bitrate_bps *= *decrease_factor;
+                }
+            }
+            RateControlState::Hold => (),
+        }
+
+        if let Switch::Enabled(max) = max_bitrate_mbps {
+            let max = *max * 1e6;
+            bitrate_bps = f32::min(bitrate_bps, max);
+
+            stats.manual_max_bps = Some(max);
+        }
+        if let Switch::Enabled(min) = min_bitrate_mbps {
+            let min = *min * 1e6;
+            bitrate_bps = f32::max(bitrate_bps, min);
+
+            stats.manual_min_bps = Some(min);
+        }
+
+        alvr_events::send_event(EventType::DelayBasedStats(DelayBasedStats {
+            filtered_ow_delay_ms: self.prev_filtered_ow_delay_ms,
+            threshold_ms: self.threshold_ms.unwrap_or(*initial_threshold_ms),
+            overuse_time_ms: self.overuse_time_ms,
+
+            bandwidth_usage: usage,
+            rate_control_state,
+
+            received_bitrate_bps,
+            requested_bitrate_bps: bitrate_bps,
+        }));
+
+        // After a decrease, wait for the queues to drain before probing again
+        self.rate_control_state = if rate_control_state == RateControlState::Decrease {
+            RateControlState::Hold
+        } else {
+            rate_control_state
+        };
+
+        stats.requested_bps = bitrate_bps;
+
+        (context.encoder_params(config, bitrate_bps), stats)
+    }
+}
@@ -0,0 +1,414 @@
+mod adaptive;
+mod constant;
+mod delay_based;
+mod nestvr;
+
+use alvr_common::{SharedClock, SlidingWindowAverage};
+use alvr_events::NominalBitrateStats;
+use alvr_session::{
+    settings_schema::Switch, BitrateAdaptiveFramerateConfig, BitrateConfig, BitrateMode,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicEncoderParams {
+    pub bitrate_bps: u64,
+    pub framerate: f32,
+}
+
+// Identifies a bitrate controller implementation. There is one id per BitrateMode variant.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BitrateModeId {
+    ConstantMbps,
+    Adaptive,
+    NestVr,
+    DelayBased,
+}
+
+impl From<&BitrateMode> for BitrateModeId {
+    fn from(mode: &BitrateMode) -> Self {
+        match mode {
+            BitrateMode::ConstantMbps(_) => Self::ConstantMbps,
+            BitrateMode::Adaptive { .. } => Self::Adaptive,
+            BitrateMode::NestVr { .. } => Self::NestVr,
+            BitrateMode::DelayBased { .. } => Self::DelayBased,
+        }
+    }
+}
+
+// Network statistics derived from the client feedback for a single received frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkStatisticsReport {
+    pub rtt: Duration,
+    pub peak_throughput_bps: f32,
+    pub frame_interarrival_s: f32,
+    pub instant_throughput_bps: f32,
+    pub filtered_ow_delay_s: f32,
+    pub shards_lost: isize,
+    pub shards_sent: usize,
+    pub frames_skipped: u32,
+}
+
+// Frame timing and bitrate state shared by all controllers, owned by BitrateManager
+pub struct BitrateContext {
+    pub nominal_frame_interval: Duration,
+    pub frame_interval_average: Duration,
+    pub last_target_bitrate_bps: f32,
+    // Fraction of network statistics packets lost, only non-zero when the feedback is sent on an
+    // unreliable transport. The reports of lost feedback are missing from the controllers history
+    pub feedback_loss_rate: f32,
+}
+
+impl BitrateContext {
+    pub fn encoder_params(&self, config: &BitrateConfig, bitrate_bps: f32) -> DynamicEncoderParams {
+        let frame_interval = if config.adapt_to_framerate.enabled() {
+            self.frame_interval_average
+        } else {
+            self.nominal_frame_interval
+        };
+
+        DynamicEncoderParams {
+            bitrate_bps: bitrate_bps as u64,
+            framerate: 1.0 / frame_interval.as_secs_f32().min(1.0),
+        }
+    }
+}
+
+// An adaptive bitrate algorithm. Every registered controller receives all reports, even when
+// not selected, so that switching mode at runtime does not start from empty history.
+pub trait BitrateController: Send {
+    // Interval between periodic updates. None means that the bitrate is recomputed only when the
+    // config changes or an update is explicitly requested
+    fn update_interval(&self, _mode: &BitrateMode) -> Option<Duration> {
+        Some(UPDATE_INTERVAL)
+    }
+
+    fn report_frame_present(&mut self, _interval: Duration) {}
+
+    fn report_frame_encoded(
+        &mut self,
+        _timestamp: Duration,
+        _encoder_latency: Duration,
+        _size_bytes: usize,
+    ) {
+    }
+
+    // Returns true if an immediate parameters update is needed
+    fn report_frame_latencies(
+        &mut self,
+        _mode: &BitrateMode,
+        _timestamp: Duration,
+        _network_latency: Duration,
+        _decoder_latency: Duration,
+    ) -> bool {
+        false
+    }
+
+    fn report_network_statistics(&mut self, _report: &NetworkStatisticsReport) {}
+
+    // Capacity estimated by the client from the dispersion of a probe train
+    fn report_probe_capacity(&mut self, _capacity_bps: f32) {}
+
+    // Called only when config.mode corresponds to this controller. The implementation must fill
+    // NominalBitrateStats::requested_bps
+    fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+        context: &BitrateContext,
+    ) -> (DynamicEncoderParams, NominalBitrateStats);
+}
+
+// Register new bitrate controllers here
+fn registry(
+    max_history_size: usize,
+    initial_framerate: f32,
+    initial_bitrate: f32,
+) -> HashMap<BitrateModeId, Box<dyn BitrateController>> {
+    let mut controllers = HashMap::<BitrateModeId, Box<dyn BitrateController>>::new();
+
+    controllers.insert(
+        BitrateModeId::ConstantMbps,
+        Box::new(constant::ConstantController),
+    );
+    controllers.insert(
+        BitrateModeId::Adaptive,
+        Box::new(adaptive::AdaptiveController::new(
+            max_history_size,
+            initial_bitrate,
+        )),
+    );
+    controllers.insert(
+        BitrateModeId::NestVr,
+        Box::new(nestvr::NestVrController::new(
+            max_history_size,
+            initial_framerate,
+        )),
+    );
+    controllers.insert(
+        BitrateModeId::DelayBased,
+        Box::new(delay_based::DelayBasedController::new(max_history_size)),
+    );
+
+    controllers
+}
+
+pub struct BitrateManager {
+    clock: SharedClock,
+    nominal_frame_interval: Duration,
+    frame_interval_average: SlidingWindowAverage<Duration>,
+    last_frame_instant: Instant,
+    last_update_instant: Instant,
+    previous_config: Option<BitrateConfig>,
+    update_needed: bool,
+
+    last_target_bitrate_bps: f32,
+
+    max_history_size: usize,
+    feedback_loss_rate_average: SlidingWindowAverage<f32>,
+
+    controllers: HashMap<BitrateModeId, Box<dyn BitrateController>>,
+}
+impl BitrateManager {
+    pub fn new(
+        clock: SharedClock,
+        max_history_size: usize,
+        initial_framerate: f32,
+        initial_bitrate: f32,
+    ) -> Self {
+        let now = clock.now();
+
+        Self {
+            clock,
+            nominal_frame_interval: Duration::from_secs_f32(1. / initial_framerate),
+            frame_interval_average: SlidingWindowAverage::new(
+                Duration::from_millis(16),
+                max_history_size,
+            ),
+            last_frame_instant: now,
+            last_update_instant: now,
+            previous_config: None,
+            update_needed: true,
+
+            last_target_bitrate_bps: initial_bitrate * 1e6,
+
+            max_history_size,
+            feedback_loss_rate_average: SlidingWindowAverage::new(0.0, max_history_size),
+
+            controllers: registry(max_history_size, initial_framerate, initial_bitrate),
+        }
+    }
+
+    // Note: This is used to calculate the framerate/frame interval. The frame present is the most
+    // accurate event for this use.
+    pub fn report_frame_present(&mut self, config: &Switch<BitrateAdaptiveFramerateConfig>) {
+        let now = self.clock.now();
+
+        let interval = now - self.last_frame_instant;
+        self.last_frame_instant = now;
+
+        self.frame_interval_average.submit_sample(interval);
+
+        for controller in self.controllers.values_mut() {
+            controller.report_frame_present(interval);
+        }
+
+        if let Some(config) = config.as_option() {
+            let interval_ratio =
+                interval.as_secs_f32() / self.frame_interval_average.get_average().as_secs_f32();
+
+            if interval_ratio > config.framerate_reset_threshold_multiplier
+                || interval_ratio < 1.0 / config.framerate_reset_threshold_multiplier
+            {
+                // Clear most of the samples, keep some for stability
+                self.frame_interval_average.retain(5);
+                self.update_needed = true;
+            }
+        }
+    }
+
+    pub fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        for controller in self.controllers.values_mut() {
+            controller.report_frame_encoded(timestamp, encoder_latency, size_bytes);
+        }
+    }
+
+    pub fn report_network_statistics(&mut self, report: NetworkStatisticsReport) {
+        for controller in self.controllers.values_mut() {
+            controller.report_network_statistics(&report);
+        }
+    }
+
+    // Called for every received network statistics packet, with the number of packets lost since
+    // the previous one. The count comes from the packet index gap, which can be huge for a late or
+    // forged packet, but more losses than the window size don't change the average.
+    pub fn report_feedback_received(&mut self, lost_packets: u32) {
+        for _ in 0..usize::min(lost_packets as usize, self.max_history_size) {
+            self.feedback_loss_rate_average.submit_sample(1.0);
+        }
+        self.feedback_loss_rate_average.submit_sample(0.0);
+    }
+
+    pub fn report_probe_capacity(&mut self, capacity_bps: f32) {
+        for controller in self.controllers.values_mut() {
+            controller.report_probe_capacity(capacity_bps);
+        }
+    }
+
+    pub fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    ) {
+        if network_latency.is_zero() {
+            return;
+        }
+
+        for controller in self.controllers.values_mut() {
+            self.update_needed |= controller.report_frame_latencies(
+                config,
+                timestamp,
+                network_latency,
+                decoder_latency,
+            );
+        }
+    }
+
+    // Last bitrate requested by the active controller
+    pub fn target_bitrate_bps(&self) -> f32 {
+        self.last_target_bitrate_bps
+    }
+
+    // Returns None if the encoder parameters should not be updated
+    pub fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+    ) -> Option<(DynamicEncoderParams, NominalBitrateStats)> {
+        let now = self.clock.now();
+
+        let controller = self
+            .controllers
+            .get_mut(&BitrateModeId::from(&config.mode))?;
+
+        if self
+            .previous_config
+            .as_ref()
+            .map(|prev| config != prev)
+            .unwrap_or(true)
+        {
+            self.previous_config = Some(config.clone());
+        } else if !self.update_needed
+            && controller
+                .update_interval(&config.mode)
+                .map(|interval| now < self.last_update_instant + interval)
+                .unwrap_or(true)
+        {
+            return None;
+        }
+
+        self.last_update_instant = now;
+        self.update_needed = false;
+
+        let context = BitrateContext {
+            nominal_frame_interval: self.nominal_frame_interval,
+            frame_interval_average: self.frame_interval_average.get_average(),
+            last_target_bitrate_bps: self.last_target_bitrate_bps,
+            feedback_loss_rate: self.feedback_loss_rate_average.get_average(),
+        };
+
+        let (params, stats) = controller.get_encoder_params(config, &context);
+
+        self.last_target_bitrate_bps = stats.requested_bps;
+
+        Some((params, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::SimulatedClock;
+    use alvr_session::SessionConfig;
+    use std::sync::Arc;
+
+    fn default_config() -> BitrateConfig {
+        SessionConfig::default().to_settings().video.bitrate
+    }
+
+    #[test]
+    fn test_update_interval_simulated_time() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut manager = BitrateManager::new(Arc::clone(&clock) as _, 256, 90.0, 30.0);
+
+        let config = default_config();
+        let BitrateMode::NestVr {
+            update_interval_nestvr_s,
+            ..
+        } = &config.mode
+        else {
+            panic!("NeSt-VR is expected to be the default mode");
+        };
+        let update_interval = Duration::from_secs_f32(*update_interval_nestvr_s);
+
+        // The first call always updates the parameters
+        assert!(manager.get_encoder_params(&config).is_some());
+        assert!(manager.get_encoder_params(&config).is_none());
+
+        clock.advance(update_interval / 2);
+        assert!(manager.get_encoder_params(&config).is_none());
+
+        clock.advance(update_interval / 2);
+        assert!(manager.get_encoder_params(&config).is_some());
+        assert!(manager.get_encoder_params(&config).is_none());
+    }
+
+    #[test]
+    fn test_frame_interval_simulated_time() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut manager = BitrateManager::new(Arc::clone(&clock) as _, 256, 90.0, 30.0);
+
+        let mut config = default_config();
+        config.mode = BitrateMode::ConstantMbps(30);
+        config.adapt_to_framerate = Switch::Enabled(BitrateAdaptiveFramerateConfig {
+            framerate_reset_threshold_multiplier: 2.0,
+        });
+
+        for _ in 0..300 {
+            clock.advance(Duration::from_secs_f64(1.0 / 72.0));
+            manager.report_frame_present(&config.adapt_to_framerate);
+        }
+
+        let (params, stats) = manager.get_encoder_params(&config).unwrap();
+        assert!((params.framerate - 72.0).abs() < 0.1);
+        assert_eq!(params.bitrate_bps, 30_000_000);
+        assert_eq!(stats.requested_bps, 30e6);
+    }
+
+    #[test]
+    fn test_feedback_loss_gap_bounded() {
+        let clock = Arc::new(SimulatedClock::new());
+        let mut manager = BitrateManager::new(clock, 16, 90.0, 30.0);
+
+        manager.report_feedback_received(1);
+        assert_eq!(manager.feedback_loss_rate_average.get_average(), 1.0 / 3.0);
+
+        // A gap of 2^31 packets fills the window instead of looping over every packet
+        manager.report_feedback_received(1 << 31);
+        assert_eq!(manager.feedback_loss_rate_average.history_buffer_len(), 16);
+        assert_eq!(
+            manager.feedback_loss_rate_average.get_average(),
+            15.0 / 16.0
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use super::{BitrateContext, BitrateController, NetworkStatisticsReport};
+use crate::DynamicEncoderParams;
+use alvr_common::SlidingWindowAverage;
+use alvr_events::{EventType, HeuristicStats, NestVrDecision, NominalBitrateStats};
+use alvr_session::{settings_schema::Switch, BitrateConfig, BitrateMode};
+use rand::{distributions::Uniform, thread_rng, Rng};
+use std::time::Duration;
+
+// Probe trains are sent rarely, average only the latest estimates
+const PROBE_CAPACITY_HISTORY_SIZE: usize = 5;
+
+fn floor_to_nearest_mult_from_initial(value: f32, step: f32, initial: f32) -> f32 {
+    initial + ((value - initial) / step).floor() * step
+}
+
+fn minmax_bitrate(
+    bitrate_bps: f32,
+    max_bitrate_mbps: &Switch<f32>,
+    min_bitrate_mbps: &Switch<f32>,
+) -> f32 {
+    let mut bitrate = bitrate_bps;
+    if let Switch::Enabled(max) = max_bitrate_mbps {
+        let max = *max as f32 * 1e6;
+        bitrate = f32::min(bitrate, max);
+    }
+    if let Switch::Enabled(min) = min_bitrate_mbps {
+        let min = *min as f32 * 1e6;
+        bitrate = f32::max(bitrate, min);
+    }
+    bitrate
+}
+
+pub struct NestVrController {
+    rtt_average: SlidingWindowAverage<Duration>,
+    peak_throughput_average: SlidingWindowAverage<f32>,
+    frame_interarrival_average: SlidingWindowAverage<f32>,
+    packet_loss_rate_average: SlidingWindowAverage<f32>,
+    frame_skip_rate_average: SlidingWindowAverage<f32>,
+    probe_capacity_average: Option<SlidingWindowAverage<f32>>,
+}
+
+impl NestVrController {
+    pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            rtt_average: SlidingWindowAverage::new(Duration::from_millis(5), max_history_size),
+            peak_throughput_average: SlidingWindowAverage::new(300E6, max_history_size),
+            frame_interarrival_average: SlidingWindowAverage::new(
+                1. / initial_framerate,
+                max_history_size,
+            ),
+            packet_loss_rate_average: SlidingWindowAverage::new(0.0, max_history_size),
+            frame_skip_rate_average: SlidingWindowAverage::new(0.0, max_history_size),
+            probe_capacity_average: None,
+        }
+    }
+}
+
+impl BitrateController for NestVrController {
+    fn update_interval(&self, mode: &BitrateMode) -> Option<Duration> {
+        if let BitrateMode::NestVr {
+            update_interval_nestvr_s,
+            ..
+        } = mode
+        {
+            Some(Duration::from_secs_f32(*update_interval_nestvr_s))
+        } else {
+            None
+        }
+    }
+
+    fn report_network_statistics(&mut self, report: &NetworkStatisticsReport) {
+        self.rtt_average.submit_sample(report.rtt);
+
+        self.peak_throughput_average
+            .submit_sample(report.peak_throughput_bps);
+
+        self.frame_interarrival_average
+            .submit_sample(report.frame_interarrival_s);
+
+        // shards_lost can be negative in case of reordering
+        let packet_loss_rate = if report.shards_sent > 0 {
+            (report.shards_lost.max(0) as f32 / report.shards_sent as f32).min(1.0)
+        } else {
+            0.0
+        };
+        self.packet_loss_rate_average
+            .submit_sample(packet_loss_rate);
+
+        // Fraction of frames lost since the previous received frame (included)
+        let frame_skip_rate = report.frames_skipped as f32 / (report.frames_skipped + 1) as f32;
+        self.frame_skip_rate_average.submit_sample(frame_skip_rate);
+    }
+
+    fn report_probe_capacity(&mut self, capacity_bps: f32) {
+        if let Some(average) = &mut self.probe_capacity_average {
+            average.submit_sample(capacity_bps);
+        } else {
+            self.probe_capacity_average = Some(SlidingWindowAverage::new(
+                capacity_bps,
+                PROBE_CAPACITY_HISTORY_SIZE,
+            ));
+        }
+    }
+
+    fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+        context: &BitrateContext,
+    ) -> (DynamicEncoderParams, NominalBitrateStats) {
+        let BitrateMode::NestVr {
+            max_bitrate_mbps,
+            min_bitrate_mbps,
+            initial_bitrate_mbps,
+            step_size_mbps,
+            capacity_scaling_factor,
+            rtt_explor_prob,
+            nfr_thresh,
+            rtt_thresh_scaling_factor,
+            loss_rate_thresh,
+            use_probe_capacity,
+            ..
+        } = &config.mode
+        else {
+            unreachable!()
+        };
+
+        let mut stats = NominalBitrateStats::default();
+
+        // Sample from uniform distribution
+        let mut rng = thread_rng();
+        let uniform_dist = Uniform::new(0.0, 1.0);
+        let random_prob = rng.sample(uniform_dist);
+
+        let mut bitrate_bps: f32 = context.last_target_bitrate_bps;
+
+        let frame_interval_s = context.frame_interval_average.as_secs_f32();
+        let rtt_avg_heur_s = self.rtt_average.get_average().as_secs_f32();
+
+        let server_fps = if frame_interval_s != 0.0 {
+            1.0 / frame_interval_s
+        } else {
+            0.0
+        };
+        let heur_fps = if self.frame_interarrival_average.get_average() != 0.0 {
+            1.0 / self.frame_interarrival_average.get_average()
+        } else {
+            0.0
+        };
+
+        let packet_loss_rate_avg = self.packet_loss_rate_average.get_average();
+        let frame_skip_rate_avg = self.frame_skip_rate_average.get_average();
+        // Lost feedback is not reflected in the other averages, it is an independent loss signal
+        let feedback_loss_rate_avg = context.feedback_loss_rate;
+
+        // Fall back to the peak throughput until the first probe train is received
+        let estimated_capacity_bps = match &self.probe_capacity_average {
+            Some(average) if *use_probe_capacity => average.get_average(),
+            _ => self.peak_throughput_average.get_average(),
+        };
+        let steps_bps = *step_size_mbps * 1E6;
+
+        let threshold_fps = *nfr_thresh * server_fps;
+        let threshold_rtt = frame_interval_s * *rtt_thresh_scaling_factor;
+        let threshold_u = *rtt_explor_prob;
+        let threshold_loss = loss_rate_thresh.as_option().copied();
+
+        let loss_exceeded = threshold_loss
+            .map(|threshold| {
+                packet_loss_rate_avg
+                    .max(frame_skip_rate_avg)
+                    .max(feedback_loss_rate_avg)
+                    > threshold
+            })
+            .unwrap_or(false);
+
+        let decision = if heur_fps >= threshold_fps {
+            if loss_exceeded {
+                NestVrDecision::DecreaseLoss
+            } else if rtt_avg_heur_s > threshold_rtt {
+                if random_prob >= threshold_u {
+                    NestVrDecision::DecreaseRtt
+                } else {
+                    NestVrDecision::Hold
+                }
+            } else {
+                if random_prob <= threshold_u {
+                    NestVrDecision::Increase
+                } else {
+                    NestVrDecision::Hold
+                }
+            }
+        } else {
+            NestVrDecision::DecreaseNfr
+        };
+
+        match decision {
+            NestVrDecision::Increase => bitrate_bps += steps_bps, // increase bitrate by 1 step
+            NestVrDecision::Hold => (),
+            NestVrDecision::DecreaseRtt
+            | NestVrDecision::DecreaseLoss
+            | NestVrDecision::DecreaseNfr => bitrate_bps -= steps_bps, // decrease bitrate by 1 step
+        }
+
+        // Ensure bitrate is within allowed range
+        bitrate_bps = minmax_bitrate(bitrate_bps, max_bitrate_mbps, min_bitrate_mbps);
+
+        // Ensure bitrate is below the estimated network capacity
+        let capacity_upper_limit = *capacity_scaling_factor * estimated_capacity_bps;
+        bitrate_bps = floor_to_nearest_mult_from_initial(
+            f32::min(bitrate_bps, capacity_upper_limit),
+            steps_bps,
+            initial_bitrate_mbps * 1E6,
+        );
+
+        let heur_stats = HeuristicStats {
+            frame_interval_s: frame_interval_s,
+            server_fps: server_fps, // fps_tx
+            steps_bps: steps_bps,
+
+            network_heur_fps: heur_fps, // fps_rx
+            rtt_avg_heur_s: rtt_avg_heur_s,
+            random_prob: random_prob,
+            packet_loss_rate_avg,
+            frame_skip_rate_avg,
+            feedback_loss_rate_avg,
+
+            threshold_fps: threshold_fps,
+            threshold_rtt_s: threshold_rtt,
+            threshold_u: threshold_u,
+            threshold_loss,
+
+            requested_bitrate_bps: bitrate_bps,
+        };
+        alvr_events::send_event(EventType::HeuristicStats(heur_stats));
+
+        if let Switch::Enabled(max) = max_bitrate_mbps {
+            let maxi = *max as f32 * 1e6;
+            stats.manual_max_bps = Some(maxi);
+        }
+        if let Switch::Enabled(min) = min_bitrate_mbps {
+            let mini = *min as f32 * 1e6;
+            stats.manual_min_bps = Some(mini);
+        }
+
+        stats.requested_bps = bitrate_bps;
+        stats.nestvr_decision = Some(decision);
+
+        (context.encoder_params(config, bitrate_bps), stats)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+const MAX_SAMPLES: usize = 64;
+// Exchanges with a round trip time above the minimum plus this tolerance are delayed by queuing,
+// their offset is not reliable
+const RTT_TOLERANCE_S: f64 = 0.002;
+// Minimum time span of the samples used to estimate the drift
+const MIN_DRIFT_SPAN_S: f64 = 5.0;
+
+struct ClockSyncSample {
+    client_time_s: f64,
+    offset_s: f64,
+    rtt_s: f64,
+}
+
+// Estimates the offset and drift of the server clock relative to the client clock, from NTP-style
+// exchanges. Times are in seconds, relative to the reference time of each peer.
+// server_time = client_time + offset(client_time)
+#[derive(Default)]
+pub struct ClockSync {
+    samples: VecDeque<ClockSyncSample>,
+    // offset at the mean time of the selected samples
+    offset_s: Option<f64>,
+    mean_client_time_s: f64,
+    drift: f64,
+}
+
+impl ClockSync {
+    // client_send_s: t0, server_receive_s: t1, server_send_s: t2, client_receive_s: t3
+    pub fn report_exchange(
+        &mut self,
+        client_send_s: f64,
+        server_receive_s: f64,
+        server_send_s: f64,
+        client_receive_s: f64,
+    ) {
+        let rtt_s = (client_receive_s - client_send_s) - (server_send_s - server_receive_s);
+        if rtt_s < 0.0 {
+            return;
+        }
+
+        self.samples.push_back(ClockSyncSample {
+            client_time_s: (client_send_s + client_receive_s) / 2.0,
+            offset_s: ((server_receive_s - client_send_s) + (server_send_s - client_receive_s))
+                / 2.0,
+            rtt_s,
+        });
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        let min_rtt_s = self
+            .samples
+            .iter()
+            .map(|sample| sample.rtt_s)
+            .fold(f64::MAX, f64::min);
+        let selected = self
+            .samples
+            .iter()
+            .filter(|sample| sample.rtt_s <= min_rtt_s + RTT_TOLERANCE_S)
+            .collect::<Vec<_>>();
+
+        let count = selected.len() as f64;
+        let mean_time_s = selected.iter().map(|s| s.client_time_s).sum::<f64>() / count;
+        let mean_offset_s = selected.iter().map(|s| s.offset_s).sum::<f64>() / count;
+
+        let span_s = selected.last().unwrap().client_time_s - selected[0].client_time_s;
+        self.drift = if span_s >= MIN_DRIFT_SPAN_S {
+            // Least squares fit of the offset over time
+            let covariance = selected
+                .iter()
+                .map(|s| (s.client_time_s - mean_time_s) * (s.offset_s - mean_offset_s))
+                .sum::<f64>();
+            let variance = selected
+                .iter()
+                .map(|s| (s.client_time_s - mean_time_s).powi(2))
+                .sum::<f64>();
+
+            covariance / variance
+        } else {
+            0.0
+        };
+
+        self.offset_s = Some(mean_offset_s);
+        self.mean_client_time_s = mean_time_s;
+    }
+
+    pub fn offset_at(&self, client_time_s: f64) -> Option<f64> {
+        self.offset_s
+            .map(|offset_s| offset_s + self.drift * (client_time_s - self.mean_client_time_s))
+    }
+
+    pub fn to_server_time(&self, client_time_s: f64) -> Option<f64> {
+        self.offset_at(client_time_s)
+            .map(|offset_s| client_time_s + offset_s)
+    }
+
+    pub fn drift_ppm(&self) -> f32 {
+        (self.drift * 1e6) as f32
+    }
+}
@@ -1,13 +1,14 @@
 #![allow(clippy::if_same_then_else)]
 
 use crate::{
+    clock_sync::ClockSync,
     decoder::{self, DECODER_INIT_CONFIG},
     logging_backend::{LogMirrorData, LOG_CHANNEL_SENDER},
     platform,
     sockets::AnnouncerSocket,
     statistics::StatisticsManager,
     storage::Config,
-    ClientCoreEvent, EVENT_QUEUE, LIFECYCLE_STATE, STATISTICS_MANAGER,
+    ClientCoreEvent, StatisticsRecord, EVENT_QUEUE, LIFECYCLE_STATE, STATISTICS_MANAGER,
 };
 use alvr_audio::AudioDevice;
 use alvr_common::{
@@ -15,19 +16,24 @@ use alvr_common::{
     glam::UVec2,
     info,
     once_cell::sync::Lazy,
-    parking_lot::{Condvar, RwLock},
+    parking_lot::{Condvar, Mutex, RwLock},
     wait_rwlock, warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState,
     OptLazy, ToCon, ALVR_VERSION,
 };
 use alvr_packets::{
-    ClientConnectionResult, ClientControlPacket, ClientStatistics, Haptics,
-    NetworkStatisticsPacket, ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader,
-    VideoStreamingCapabilities, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
+    AuthenticationChallenge, BatchedNetworkStatistics, ClientConnectionResult, ClientControlPacket,
+    ClientStatistics, ClockSyncRequestPacket, Haptics, NetworkStatisticsPacket, ProbeHeader,
+    ProbeReportPacket, ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader,
+    VideoStreamingCapabilities, AUDIO, FEEDBACK, HAPTICS, PROBE, STATISTICS, TRACKING, VIDEO,
+};
+use alvr_session::{
+    settings_schema::Switch, NetworkFeedbackTransport, NetworkStatisticsBatchingConfig,
+    SessionConfig, SocketProtocol,
 };
-use alvr_session::{settings_schema::Switch, SessionConfig};
 use alvr_sockets::{
-    ControlSocketSender, PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder,
-    KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
+    ControlSocketSender, KeyExchange, NetworkImpairment, PeerType, ProtoControlSocket,
+    QuicCertificate, ShardHeaderVersion, StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL,
+    KEEPALIVE_TIMEOUT,
 };
 use serde_json as json;
 use std::{
@@ -64,8 +70,7 @@ const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const CONNECTION_RETRY_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
-
-const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(1);
 
 pub static CONNECTION_STATE: Lazy<Arc<RwLock<ConnectionState>>> =
     Lazy::new(|| Arc::new(RwLock::new(ConnectionState::Disconnected)));
@@ -78,10 +83,12 @@ pub static STATISTICS_SENDER: OptLazy<StreamSender<ClientStatistics>> =
     alvr_common::lazy_mut_none();
 
 fn set_hud_message(message: &str) {
+    let config = Config::load();
     let message = format!(
-        "ALVR v{}\nhostname: {}\nIP: {}\n\n{message}",
+        "ALVR v{}\nhostname: {}\nfingerprint: {}\nIP: {}\n\n{message}",
         *ALVR_VERSION,
-        Config::load().hostname,
+        config.hostname,
+        alvr_common::key_fingerprint(&config.identity().public_key()),
         platform::local_ip(),
     );
 
@@ -94,6 +101,50 @@ fn is_streaming() -> bool {
     *CONNECTION_STATE.read() == ConnectionState::Streaming
 }
 
+// Network statistics are sent on the control socket unless a feedback stream is provided
+fn send_network_feedback(
+    feedback_sender: &mut Option<StreamSender<ClientControlPacket>>,
+    packet: &ClientControlPacket,
+) {
+    if let Some(sender) = feedback_sender {
+        sender.send_header(packet).ok();
+    } else if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+        sender.send(packet).ok();
+    }
+}
+
+// Send the batched network statistics once the oldest record has been held for the batching
+// interval or the batch is full. Each record is paired with the instant its frame was received
+fn send_network_statistics_batch_if_due(
+    batch: &mut Vec<(Instant, NetworkStatisticsPacket)>,
+    config: &NetworkStatisticsBatchingConfig,
+    feedback_sender: &mut Option<StreamSender<ClientControlPacket>>,
+) {
+    let now = Instant::now();
+    let Some((first_receive_instant, _)) = batch.first() else {
+        return;
+    };
+    if batch.len() < config.max_frames
+        && now.saturating_duration_since(*first_receive_instant)
+            < Duration::from_millis(config.interval_ms)
+    {
+        return;
+    }
+
+    let records = batch
+        .drain(..)
+        .map(|(receive_instant, statistics)| BatchedNetworkStatistics {
+            statistics,
+            hold_duration: now.saturating_duration_since(receive_instant),
+        })
+        .collect();
+
+    send_network_feedback(
+        feedback_sender,
+        &ClientControlPacket::NetworkStatisticsBatch(records),
+    );
+}
+
 pub fn connection_lifecycle_loop(
     recommended_view_resolution: UVec2,
     supported_refresh_rates: Vec<f32>,
@@ -124,9 +175,12 @@ fn connection_pipeline(
     recommended_view_resolution: UVec2,
     supported_refresh_rates: Vec<f32>,
 ) -> ConResult {
+    let config = Config::load();
+    let identity = config.identity();
+
     let (mut proto_control_socket, server_ip) = {
-        let config = Config::load();
-        let announcer_socket = AnnouncerSocket::new(&config.hostname).to_con()?;
+        let announcer_socket =
+            AnnouncerSocket::new(&config.hostname, &identity.public_key()).to_con()?;
         let listener_socket =
             alvr_sockets::get_server_listener(HANDSHAKE_ACTION_TIMEOUT).to_con()?;
 
@@ -166,6 +220,10 @@ fn connection_pipeline(
         .input_sample_rate()
         .unwrap();
 
+    let key_exchange = KeyExchange::new();
+    // Generated before knowing the stream protocol, its hash must be signed during the handshake
+    let quic_certificate = QuicCertificate::generate().to_con()?;
+
     proto_control_socket
         .send(&ClientConnectionResult::ConnectionAccepted {
             client_protocol_id: alvr_common::protocol_id(),
@@ -176,8 +234,22 @@ fn connection_pipeline(
                 supported_refresh_rates,
                 microphone_sample_rate,
             }),
+            stream_public_key: key_exchange.public_key(),
+            identity_public_key: identity.public_key(),
+            quic_certificate_hash: quic_certificate.hash(),
         })
         .to_con()?;
+
+    let challenge =
+        proto_control_socket.recv::<AuthenticationChallenge>(HANDSHAKE_ACTION_TIMEOUT)?;
+    proto_control_socket
+        .send(&identity.sign_challenge(
+            &challenge,
+            &key_exchange.public_key(),
+            &quic_certificate.hash(),
+        ))
+        .to_con()?;
+
     let config_packet =
         proto_control_socket.recv::<StreamConfigPacket>(HANDSHAKE_ACTION_TIMEOUT)?;
 
@@ -204,6 +276,19 @@ fn connection_pipeline(
         .get("game_audio_sample_rate")
         .and_then(|v| v.as_u64())
         .unwrap_or(44100) as u32;
+    // Servers that don't negotiate the shard header use the first version
+    let shard_header_version = negotiated_config
+        .get("shard_header_version")
+        .and_then(|v| v.as_u64())
+        .map(|number| ShardHeaderVersion::from_number(number).to_con())
+        .transpose()?
+        .unwrap_or(ShardHeaderVersion::V1);
+    // The server sends its key only if stream encryption is enabled
+    let stream_encryption_keys = negotiated_config
+        .get("stream_public_key")
+        .and_then(|v| json::from_value::<[u8; 32]>(v.clone()).ok())
+        .map(|public_key| key_exchange.derive_keys(public_key, false).to_con())
+        .transpose()?;
 
     let streaming_start_event = ClientCoreEvent::StreamingStarted {
         view_resolution,
@@ -249,6 +334,7 @@ fn connection_pipeline(
 
     let stream_socket_builder = StreamSocketBuilder::listen_for_server(
         Duration::from_secs(1),
+        quic_certificate,
         settings.connection.stream_port,
         settings.connection.stream_protocol,
         settings.connection.dscp,
@@ -268,8 +354,15 @@ fn connection_pipeline(
         settings.connection.stream_port,
         settings.connection.packet_size as _,
         HANDSHAKE_ACTION_TIMEOUT,
+        shard_header_version,
+        stream_encryption_keys,
+        alvr_common::system_clock(),
     )?;
 
+    stream_socket.enable_network_impairment(NetworkImpairment::new(
+        settings.debug.network_impairment.clone().into_option(),
+    ));
+
     info!("Connected to server");
     {
         let config = &mut *DECODER_INIT_CONFIG.lock();
@@ -279,13 +372,31 @@ fn connection_pipeline(
         config.options = settings.video.mediacodec_extra_options;
     }
 
+    // Applies per stream
+    let receive_buffer_budget = settings.connection.stream_receive_buffer_mb as usize * 1024 * 1024;
     let mut video_receiver =
-        stream_socket.subscribe_to_stream::<VideoPacketHeader>(VIDEO, MAX_UNREAD_PACKETS);
-    let mut game_audio_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
+        stream_socket.subscribe_to_stream::<VideoPacketHeader>(VIDEO, receive_buffer_budget);
+    let mut game_audio_receiver = stream_socket.subscribe_to_stream(AUDIO, receive_buffer_budget);
     let tracking_sender = stream_socket.request_stream(TRACKING);
     let mut haptics_receiver =
-        stream_socket.subscribe_to_stream::<Haptics>(HAPTICS, MAX_UNREAD_PACKETS);
+        stream_socket.subscribe_to_stream::<Haptics>(HAPTICS, receive_buffer_budget);
     let statistics_sender = stream_socket.request_stream(STATISTICS);
+    let mut feedback_sender = (settings.connection.network_feedback_transport
+        == NetworkFeedbackTransport::StreamSocket)
+        .then(|| stream_socket.request_stream(FEEDBACK));
+
+    if let (SocketProtocol::Udp | SocketProtocol::Quic, Switch::Enabled(config)) = (
+        &settings.connection.stream_protocol,
+        &settings.connection.video_nack,
+    ) {
+        stream_socket
+            .enable_nack(
+                VIDEO,
+                Duration::from_millis(config.deadline_ms),
+                Duration::from_millis(config.retry_interval_ms),
+            )
+            .to_con()?;
+    }
 
     let mut last_instant_IDR_client = Instant::now();
     let interval_IDR_seconds_f32 =
@@ -293,85 +404,170 @@ fn connection_pipeline(
 
     let mut frames_dropped: u32 = 0; // number of frames dropped
 
-    let video_receive_thread = thread::spawn(move || {
-        let mut stream_corrupted = false;
-        while is_streaming() {
-            let data = match video_receiver.recv(STREAMING_RECV_TIMEOUT) {
-                Ok(data) => data,
-                Err(ConnectionError::TryAgain(_)) => continue,
-                Err(ConnectionError::Other(_)) => return,
-            };
-
-            // send frame and network statistics for every reconstructed video frame
-            if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                sender
-                    .send(&ClientControlPacket::NetworkStatistics(
-                        NetworkStatisticsPacket {
-                            // Frame specific metrics
-                            frame_index: data.get_frame_index() as i32, // index of the current frame
-                            frame_span: data.get_frame_span(), // duration of the current frame
-
-                            bytes_in_frame: data.get_bytes_in_frame(), // bytes received for the current frame, including both prefixes and network headers
-                            bytes_in_frame_app: data.get_bytes_in_frame_app(), // bytes received for the current frame, excluding both prefixes and network headers
+    let reference_time = stream_socket.reference_time();
+    let clock_sync = Arc::new(Mutex::new(ClockSync::default()));
 
-                            // Interval specific metrics
-                            frame_interarrival: data.get_frame_interarrival(), // time interval between consecutive frames
+    let statistics_batching = settings
+        .connection
+        .network_statistics_batching
+        .clone()
+        .into_option();
 
-                            interarrival_jitter: data.get_interarrival_jitter(), // measure of the variability in the time between the reception of consecutive video shards
-                            ow_delay: data.get_ow_delay(), // one-way delay of the received video shards
-                            filtered_ow_delay: data.get_filtered_ow_delay(), // kalman filtered one-way delay of the received video shards, as GCC does
-
-                            frames_skipped: data.get_frames_skipped(), // number of frames skipped
-
-                            rx_bytes: data.get_rx_bytes(), // bytes received in the interval between the consecutive frames, including any prefixes and network headers
-
-                            rx_shard_counter: data.get_rx_shard_counter(), // non-duplicated video shards received during the interval between consecutive frames
-                            duplicated_shard_counter: data.get_duplicated_shard_counter(), // duplicated video shards received during the interval between consecutive frames
+    let video_receive_thread = thread::spawn({
+        let clock_sync = Arc::clone(&clock_sync);
+        move || {
+            let mut stream_corrupted = false;
+            let mut statistics_batch = vec![];
+            while is_streaming() {
+                let data = match video_receiver.recv(STREAMING_RECV_TIMEOUT) {
+                    Ok(data) => data,
+                    Err(ConnectionError::TryAgain(_)) => {
+                        if let Some(config) = &statistics_batching {
+                            send_network_statistics_batch_if_due(
+                                &mut statistics_batch,
+                                config,
+                                &mut feedback_sender,
+                            );
+                        }
+                        continue;
+                    }
+                    Err(ConnectionError::Other(_)) => return,
+                };
+
+                let receive_instant = Instant::now();
+                let client_time_s = receive_instant
+                    .saturating_duration_since(reference_time)
+                    .as_secs_f64();
+                // The times are converted to integer microseconds in f64, f32 loses precision
+                // in long sessions
+                let (downlink_ow_delay_us, tx_server_time_us, clock_drift_ppm) = {
+                    let clock_sync = clock_sync.lock();
+                    (
+                        clock_sync.offset_at(client_time_s).map(|offset_s| {
+                            ((data.get_raw_ow_delay() + offset_s) * 1e6).round() as i64
+                        }),
+                        clock_sync
+                            .to_server_time(client_time_s)
+                            .map(|time_s| (time_s * 1e6).round() as u64),
+                        clock_sync.drift_ppm(),
+                    )
+                };
+
+                // send frame and network statistics for every reconstructed video frame
+                let network_statistics = NetworkStatisticsPacket {
+                    // Frame specific metrics
+                    frame_index: data.get_frame_index() as i32, // index of the current frame
+                    frame_span: data.get_frame_span(),          // duration of the current frame
+
+                    bytes_in_frame: data.get_bytes_in_frame(), // bytes received for the current frame, including both prefixes and network headers
+                    bytes_in_frame_app: data.get_bytes_in_frame_app(), // bytes received for the current frame, excluding both prefixes and network headers
+
+                    // Interval specific metrics
+                    frame_interarrival: data.get_frame_interarrival(), // time interval between consecutive frames
+
+                    interarrival_jitter: data.get_interarrival_jitter(), // measure of the variability in the time between the reception of consecutive video shards
+                    ow_delay: data.get_ow_delay(), // one-way delay of the received video shards
+                    filtered_ow_delay: data.get_filtered_ow_delay(), // kalman filtered one-way delay of the received video shards, as GCC does
+
+                    frames_skipped: data.get_frames_skipped(), // number of frames skipped
+                    fec_recovered_frames: data.get_fec_recovered_frames(), // number of frames reconstructed using FEC parity shards
+
+                    nacks_sent: data.get_nacks_sent(), // retransmission requests sent during the interval between consecutive frames
+                    late_recovered_frames: data.get_late_recovered_frames(), // frames completed by a retransmitted shard
+                    wasted_retransmissions: data.get_wasted_retransmissions(), // retransmitted shards received after their frame was completed or abandoned
+
+                    evicted_frames: data.get_evicted_packets(), // incomplete frames dropped to stay within the receive buffer budget
+                    discarded_shards: data.get_discarded_shards(), // shards dropped because they didn't fit in the receive buffer budget
+
+                    rx_bytes: data.get_rx_bytes(), // bytes received in the interval between the consecutive frames, including any prefixes and network headers
+
+                    rx_shard_counter: data.get_rx_shard_counter(), // non-duplicated video shards received during the interval between consecutive frames
+                    duplicated_shard_counter: data.get_duplicated_shard_counter(), // duplicated video shards received during the interval between consecutive frames
+                    authentication_failures: data.get_authentication_failures(), // shards that failed authentication during the interval between consecutive frames
+
+                    shard_loss_reports: data.get_shard_loss_reports().to_vec(), // received shards of the video frames completed or abandoned during the interval between consecutive frames
+
+                    downlink_ow_delay_us, // absolute one-way delay of the first received shard
+                    tx_server_time_us,    // send time of this packet in the server time base
+                    clock_drift_ppm,
+                };
+
+                crate::record_statistics(StatisticsRecord::Network(network_statistics.clone()));
+
+                if let Some(config) = &statistics_batching {
+                    statistics_batch.push((receive_instant, network_statistics));
+                    send_network_statistics_batch_if_due(
+                        &mut statistics_batch,
+                        config,
+                        &mut feedback_sender,
+                    );
+                } else {
+                    send_network_feedback(
+                        &mut feedback_sender,
+                        &ClientControlPacket::NetworkStatistics(network_statistics),
+                    );
+                }
 
-                            highest_rx_frame_index: data.get_highest_rx_frame_index(), // index of the highest video frame received during the interval between consecutive frames
-                            highest_rx_shard_index: data.get_highest_rx_shard_index(), // index of the highest video shard received during the interval between consecutive frames
-                        },
-                    ))
-                    .ok();
-            }
+                let Ok((header, nal)) = data.get() else {
+                    return;
+                };
+                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    stats.report_video_packet_received(header.timestamp);
+                }
 
-            let Ok((header, nal)) = data.get() else {
-                return;
-            };
-            if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                stats.report_video_packet_received(header.timestamp);
-            }
+                // periodically request an IDR frame using the settings' client_idr_refresh_interval_ms
 
-            // periodically request an IDR frame using the settings' client_idr_refresh_interval_ms
+                if settings.connection.idr_periodic_bool {
+                    if Instant::now()
+                        .saturating_duration_since(last_instant_IDR_client)
+                        .as_secs_f32()
+                        >= interval_IDR_seconds_f32
+                    {
+                        if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                            sender.send(&ClientControlPacket::RequestIdr).ok();
+                        }
+                        last_instant_IDR_client = Instant::now();
+                    }
+                }
 
-            if settings.connection.idr_periodic_bool {
-                if Instant::now()
-                    .saturating_duration_since(last_instant_IDR_client)
-                    .as_secs_f32()
-                    >= interval_IDR_seconds_f32
-                {
+                if header.is_idr {
+                    stream_corrupted = false;
+                } else if data.had_packet_loss() {
+                    stream_corrupted = true;
                     if let Some(sender) = &mut *CONTROL_SENDER.lock() {
                         sender.send(&ClientControlPacket::RequestIdr).ok();
                     }
-                    last_instant_IDR_client = Instant::now();
-                }
-            }
-
-            if header.is_idr {
-                stream_corrupted = false;
-            } else if data.had_packet_loss() {
-                stream_corrupted = true;
-                if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                    sender.send(&ClientControlPacket::RequestIdr).ok();
+                    warn!(
+                        "Network skipped {} video packets",
+                        data.get_frames_skipped()
+                    );
                 }
-                warn!(
-                    "Network skipped {} video packets",
-                    data.get_frames_skipped()
-                );
-            }
-            if !stream_corrupted || !settings.connection.avoid_video_glitching {
-                if !decoder::push_nal(header.timestamp, nal) {
-                    stream_corrupted = true;
+                if !stream_corrupted || !settings.connection.avoid_video_glitching {
+                    if !decoder::push_nal(header.timestamp, nal) {
+                        stream_corrupted = true;
+                        if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                            sender.send(&ClientControlPacket::RequestIdr).ok();
+                        }
+                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                            stats.report_video_packet_dropped(data.get_frame_index());
+                        }
+                        warn!(
+                            "Dropped video packet {}. Reason: Decoder saturation",
+                            data.get_frame_index()
+                        );
+                        frames_dropped += 1;
+                    } else {
+                        // frame is decoded correctly
+                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                            stats.report_video_packet_data(
+                                header.timestamp,
+                                data.get_frame_index(),
+                                frames_dropped,
+                            );
+                        }
+                        frames_dropped = 0;
+                    }
+                } else {
                     if let Some(sender) = &mut *CONTROL_SENDER.lock() {
                         sender.send(&ClientControlPacket::RequestIdr).ok();
                     }
@@ -379,33 +575,11 @@ fn connection_pipeline(
                         stats.report_video_packet_dropped(data.get_frame_index());
                     }
                     warn!(
-                        "Dropped video packet {}. Reason: Decoder saturation",
+                        "Dropped video packet {}. Reason: Waiting for IDR frame",
                         data.get_frame_index()
                     );
                     frames_dropped += 1;
-                } else {
-                    // frame is decoded correctly
-                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                        stats.report_video_packet_data(
-                            header.timestamp,
-                            data.get_frame_index(),
-                            frames_dropped,
-                        );
-                    }
-                    frames_dropped = 0;
-                }
-            } else {
-                if let Some(sender) = &mut *CONTROL_SENDER.lock() {
-                    sender.send(&ClientControlPacket::RequestIdr).ok();
-                }
-                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                    stats.report_video_packet_dropped(data.get_frame_index());
                 }
-                warn!(
-                    "Dropped video packet {}. Reason: Waiting for IDR frame",
-                    data.get_frame_index()
-                );
-                frames_dropped += 1;
             }
         }
     });
@@ -476,12 +650,43 @@ fn connection_pipeline(
         }
     });
 
+    let probe_receive_thread = if settings.connection.capacity_probing.enabled() {
+        let mut probe_receiver =
+            stream_socket.subscribe_to_stream::<ProbeHeader>(PROBE, receive_buffer_budget);
+        thread::spawn(move || {
+            while is_streaming() {
+                let data = match probe_receiver.recv(STREAMING_RECV_TIMEOUT) {
+                    Ok(data) => data,
+                    Err(ConnectionError::TryAgain(_)) => continue,
+                    Err(ConnectionError::Other(_)) => return,
+                };
+
+                // A train with lost shards is never reconstructed, so the estimate is not biased
+                // by the loss
+                let capacity_bps = data.get_dispersion_rate_bps();
+                if capacity_bps > 0.0 {
+                    if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                        sender
+                            .send(&ClientControlPacket::ProbeReport(ProbeReportPacket {
+                                train_index: data.get_frame_index(),
+                                capacity_bps,
+                            }))
+                            .ok();
+                    }
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     let (log_channel_sender, log_channel_receiver) = mpsc::channel();
 
     let control_send_thread = thread::spawn({
         let disconnect_notif = Arc::clone(&disconnect_notif);
         move || {
             let mut keepalive_deadline = Instant::now();
+            let mut clock_sync_deadline = Instant::now();
 
             #[cfg(target_os = "android")]
             let mut battery_deadline = Instant::now();
@@ -507,6 +712,21 @@ fn connection_pipeline(
                     }
                 }
 
+                if Instant::now() > clock_sync_deadline {
+                    if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                        sender
+                            .send(&ClientControlPacket::ClockSyncRequest(
+                                ClockSyncRequestPacket {
+                                    client_send_time: Instant::now()
+                                        .saturating_duration_since(reference_time),
+                                },
+                            ))
+                            .ok();
+
+                        clock_sync_deadline = Instant::now() + CLOCK_SYNC_INTERVAL;
+                    }
+                }
+
                 #[cfg(target_os = "android")]
                 if Instant::now() > battery_deadline {
                     let (gauge_value, is_plugged) = platform::get_battery_status();
@@ -544,6 +764,16 @@ fn connection_pipeline(
                         set_hud_message(SERVER_RESTART_MESSAGE);
                         disconnect_notif.notify_one();
                     }
+                    Ok(ServerControlPacket::ClockSyncResponse(response)) => {
+                        let client_receive_time =
+                            Instant::now().saturating_duration_since(reference_time);
+                        clock_sync.lock().report_exchange(
+                            response.client_send_time.as_secs_f64(),
+                            response.server_receive_time.as_secs_f64(),
+                            response.server_send_time.as_secs_f64(),
+                            client_receive_time.as_secs_f64(),
+                        );
+                    }
                     Ok(_) => (),
                     Err(ConnectionError::TryAgain(_)) => {
                         if Instant::now() > disconnection_deadline {
@@ -571,8 +801,15 @@ fn connection_pipeline(
         move || {
             while is_streaming() {
                 match stream_socket.recv() {
-                    Ok(()) => (),
-                    Err(ConnectionError::TryAgain(_)) => continue,
+                    // Poll also on timeout: during a loss burst no shard arrives, and the NACK
+                    // retries must still be sent
+                    Ok(()) | Err(ConnectionError::TryAgain(_)) => {
+                        for nack in stream_socket.poll_nacks() {
+                            if let Some(sender) = &mut *CONTROL_SENDER.lock() {
+                                sender.send(&ClientControlPacket::Nack(nack)).ok();
+                            }
+                        }
+                    }
                     Err(e) => {
                         info!("Client disconnected. Cause: {e}");
                         set_hud_message(SERVER_DISCONNECTED_MESSAGE);
@@ -623,6 +860,7 @@ fn connection_pipeline(
     game_audio_thread.join().ok();
     microphone_thread.join().ok();
     haptics_receive_thread.join().ok();
+    probe_receive_thread.join().ok();
     control_send_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
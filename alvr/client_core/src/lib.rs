@@ -6,6 +6,7 @@
 )]
 
 mod c_api;
+mod clock_sync;
 mod connection;
 mod decoder;
 mod logging_backend;
@@ -31,7 +32,10 @@ use alvr_common::{
     parking_lot::{Mutex, RwLock},
     ConnectionState, Fov, LifecycleState, OptLazy,
 };
-use alvr_packets::{BatteryPacket, ButtonEntry, ClientControlPacket, Tracking, ViewsConfig};
+use alvr_packets::{
+    BatteryPacket, ButtonEntry, ClientControlPacket, ClientStatistics, NetworkStatisticsPacket,
+    Tracking, ViewsConfig,
+};
 use alvr_session::{CodecType, Settings};
 use connection::{
     CONNECTION_STATE, CONTROL_SENDER, DISCONNECTED_NOTIF, STATISTICS_SENDER, TRACKING_SENDER,
@@ -41,6 +45,7 @@ use serde::{Deserialize, Serialize};
 use statistics::StatisticsManager;
 use std::{
     collections::VecDeque,
+    sync::mpsc,
     thread::{self, JoinHandle},
     time::Duration,
 };
@@ -55,6 +60,15 @@ static EVENT_QUEUE: Lazy<Mutex<VecDeque<ClientCoreEvent>>> =
 
 static CONNECTION_THREAD: OptLazy<JoinHandle<()>> = alvr_common::lazy_mut_none();
 
+static STATISTICS_RECORDER: OptLazy<mpsc::Sender<StatisticsRecord>> = alvr_common::lazy_mut_none();
+
+// Copy of a statistics packet sent to the server
+#[derive(Serialize, Clone)]
+pub enum StatisticsRecord {
+    Network(NetworkStatisticsPacket),
+    Client(ClientStatistics),
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ClientCoreEvent {
     UpdateHudMessage(String),
@@ -222,6 +236,8 @@ pub fn report_submit(target_timestamp: Duration, vsync_queue: Duration) {
 
         if let Some(sender) = &mut *STATISTICS_SENDER.lock() {
             if let Some(stats) = stats.summary(target_timestamp) {
+                record_statistics(StatisticsRecord::Client(stats.clone()));
+
                 sender.send_header(&stats).ok();
             } else {
                 error!("Statistics summary not ready!");
@@ -230,6 +246,18 @@ pub fn report_submit(target_timestamp: Duration, vsync_queue: Duration) {
     }
 }
 
+/// Receive a copy of every statistics packet sent to the server, for automated testing. Pass None
+/// to stop recording.
+pub fn set_statistics_recorder(sender: Option<mpsc::Sender<StatisticsRecord>>) {
+    *STATISTICS_RECORDER.lock() = sender;
+}
+
+fn record_statistics(record: StatisticsRecord) {
+    if let Some(sender) = &*STATISTICS_RECORDER.lock() {
+        sender.send(record).ok();
+    }
+}
+
 /// Call only with external decoder
 pub fn request_idr() {
     if let Some(sender) = &mut *CONTROL_SENDER.lock() {
@@ -1,21 +1,22 @@
 use alvr_common::{anyhow::Result, ALVR_NAME};
-use alvr_sockets::{CONTROL_PORT, LOCAL_IP};
+use alvr_sockets::{CONTROL_PORT, HANDSHAKE_PACKET_SIZE_BYTES, LOCAL_IP};
 use std::net::{Ipv4Addr, UdpSocket};
 
 pub struct AnnouncerSocket {
     socket: UdpSocket,
-    packet: [u8; 56],
+    packet: [u8; HANDSHAKE_PACKET_SIZE_BYTES],
 }
 
 impl AnnouncerSocket {
-    pub fn new(hostname: &str) -> Result<Self> {
+    pub fn new(hostname: &str, identity_public_key: &[u8; 32]) -> Result<Self> {
         let socket = UdpSocket::bind((LOCAL_IP, CONTROL_PORT))?;
         socket.set_broadcast(true)?;
 
-        let mut packet = [0; 56];
+        let mut packet = [0; HANDSHAKE_PACKET_SIZE_BYTES];
         packet[0..ALVR_NAME.len()].copy_from_slice(ALVR_NAME.as_bytes());
         packet[16..24].copy_from_slice(&alvr_common::protocol_id().to_le_bytes());
         packet[24..24 + hostname.len()].copy_from_slice(hostname.as_bytes());
+        packet[56..88].copy_from_slice(identity_public_key);
 
         Ok(Self { socket, packet })
     }
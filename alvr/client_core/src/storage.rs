@@ -1,4 +1,5 @@
 use alvr_common::{error, info};
+use alvr_sockets::ClientIdentity;
 use app_dirs2::{AppDataType, AppInfo};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -20,6 +21,9 @@ fn config_path() -> PathBuf {
 pub struct Config {
     pub protocol_id: u64,
     pub hostname: String,
+    // Ed25519 secret key, the server pins the corresponding public key
+    #[serde(default = "ClientIdentity::generate_secret_key")]
+    pub identity_secret_key: [u8; 32],
 }
 
 impl Default for Config {
@@ -35,6 +39,7 @@ impl Default for Config {
                 rng.gen_range(0..10),
                 rng.gen_range(0..10),
             ),
+            identity_secret_key: ClientIdentity::generate_secret_key(),
         }
     }
 }
@@ -44,7 +49,13 @@ impl Config {
         if let Ok(config_string) = fs::read_to_string(config_path()) {
             // Failure happens if the Config signature changed between versions.
             // todo: recover data from mismatched Config signature. low priority
-            if let Ok(config) = serde_json::from_str(&config_string) {
+            if let Ok(config) = serde_json::from_str::<Config>(&config_string) {
+                // Configs from older versions don't have an identity key, the generated one must
+                // be kept
+                if !config_string.contains("identity_secret_key") {
+                    config.store();
+                }
+
                 return config;
             } else {
                 info!("Error parsing ALVR config. Using default");
@@ -65,4 +76,8 @@ impl Config {
             error!("Error writing ALVR config: {e}")
         }
     }
+
+    pub fn identity(&self) -> ClientIdentity {
+        ClientIdentity::from_secret_key(&self.identity_secret_key)
+    }
 }
@@ -1,6 +1,10 @@
+mod scenario;
+
 use alvr_client_core::ClientCoreEvent;
 use alvr_common::{
+    anyhow::{bail, Result},
     glam::{Quat, UVec2, Vec3},
+    info,
     parking_lot::RwLock,
     DeviceMotion, Pose, RelaxedAtomic, HEAD_ID,
 };
@@ -10,8 +14,13 @@ use eframe::{
     egui::{CentralPanel, Context, RichText, Slider, ViewportBuilder},
     Frame, NativeOptions,
 };
+use pico_args::Arguments;
+use scenario::Scenario;
 use std::{
     f32::consts::{FRAC_PI_2, PI},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
     sync::{
         mpsc::{self, TryRecvError},
         Arc,
@@ -20,6 +29,28 @@ use std::{
     time::{Duration, Instant},
 };
 
+const HELP_STR: &str = r#"
+alvr_client_mock
+Mock client. Opens a window with manual controls, unless --headless is used.
+
+USAGE:
+    alvr_client_mock [FLAGS] [ARGS]
+
+FLAGS:
+    --help                  Print this text
+    --headless              Run a scripted session without window
+
+ARGS (headless only):
+    --scenario <PATH>       JSON scenario with head poses and emulated timings. Defaults otherwise
+    --duration-s <SECS>     Override the run duration of the scenario
+    --decode-ms <MS>        Override the emulated decode time
+    --compositor-ms <MS>    Override the emulated compositor time
+    --vsync-ms <MS>         Override the emulated vsync queue time
+    --output <PATH>         Statistics output, one JSON record per line. Default: client_stats.jsonl
+"#;
+
+const SCENARIO_STEP: Duration = Duration::from_millis(10);
+
 #[derive(Clone, PartialEq)]
 struct WindowInput {
     height: f32,
@@ -294,9 +325,109 @@ fn client_thread(
     alvr_client_core::destroy();
 }
 
+fn run_headless(mut args: Arguments) -> Result<()> {
+    let scenario_path: Option<PathBuf> = args.opt_value_from_str("--scenario")?;
+    let duration_s: Option<f32> = args.opt_value_from_str("--duration-s")?;
+    let decode_ms: Option<u64> = args.opt_value_from_str("--decode-ms")?;
+    let compositor_ms: Option<u64> = args.opt_value_from_str("--compositor-ms")?;
+    let vsync_ms: Option<u64> = args.opt_value_from_str("--vsync-ms")?;
+    let output_path: PathBuf = args
+        .opt_value_from_str("--output")?
+        .unwrap_or_else(|| PathBuf::from("client_stats.jsonl"));
+
+    let remaining = args.finish();
+    if !remaining.is_empty() {
+        bail!("Unrecognized arguments: {remaining:?}");
+    }
+
+    let mut scenario = if let Some(path) = scenario_path {
+        serde_json::from_str(&fs::read_to_string(path)?)?
+    } else {
+        Scenario::default()
+    };
+    scenario.duration_s = duration_s.unwrap_or(scenario.duration_s);
+    scenario.emulated_decode_ms = decode_ms.unwrap_or(scenario.emulated_decode_ms);
+    scenario.emulated_compositor_ms = compositor_ms.unwrap_or(scenario.emulated_compositor_ms);
+    scenario.emulated_vsync_ms = vsync_ms.unwrap_or(scenario.emulated_vsync_ms);
+
+    let mut output_file = BufWriter::new(File::create(&output_path)?);
+    let (record_sender, record_receiver) = mpsc::channel();
+    alvr_client_core::set_statistics_recorder(Some(record_sender));
+
+    let writer_thread = thread::spawn(move || -> Result<usize> {
+        let mut records_count = 0;
+        for record in record_receiver {
+            writeln!(output_file, "{}", serde_json::to_string(&record)?)?;
+            records_count += 1;
+        }
+        output_file.flush()?;
+
+        Ok(records_count)
+    });
+
+    let (input_sender, input_receiver) = mpsc::channel::<WindowInput>();
+    let (output_sender, output_receiver) = mpsc::channel::<WindowOutput>();
+
+    let client_thread = thread::spawn(|| {
+        client_thread(output_sender, input_receiver);
+    });
+
+    let start_instant = Instant::now();
+    let duration = Duration::from_secs_f32(scenario.duration_s);
+    let mut last_input = None;
+    let mut hud_message = String::new();
+    while start_instant.elapsed() < duration {
+        let input = scenario.input_at(start_instant.elapsed().as_secs_f32());
+        if last_input.as_ref() != Some(&input) {
+            input_sender.send(input.clone()).ok();
+            last_input = Some(input);
+        }
+
+        while let Ok(output) = output_receiver.try_recv() {
+            if output.hud_message != hud_message {
+                info!("{}", output.hud_message);
+                hud_message = output.hud_message;
+            }
+        }
+
+        thread::sleep(SCENARIO_STEP);
+    }
+
+    // Dropping the input sender stops the client
+    drop(input_sender);
+    client_thread.join().unwrap();
+
+    alvr_client_core::set_statistics_recorder(None);
+    let records_count = writer_thread.join().unwrap()?;
+
+    println!(
+        "{records_count} statistics records written to {}",
+        output_path.display()
+    );
+
+    Ok(())
+}
+
 fn main() {
     env_logger::init();
 
+    let mut args = Arguments::from_env();
+
+    if args.contains(["-h", "--help"]) {
+        println!("{HELP_STR}");
+        return;
+    }
+
+    if args.contains("--headless") {
+        if let Err(e) = run_headless(args) {
+            eprintln!("{e}");
+            println!("{HELP_STR}");
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let (input_sender, input_receiver) = mpsc::channel::<WindowInput>();
     let (output_sender, output_receiver) = mpsc::channel::<WindowOutput>();
 
@@ -0,0 +1,95 @@
+use crate::WindowInput;
+use serde::Deserialize;
+
+// Head pose keyframe. Keyframes are linearly interpolated
+#[derive(Deserialize, Clone, Copy)]
+pub struct ScriptedPose {
+    pub time_s: f32,
+    pub height: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub duration_s: f32,
+    pub emulated_decode_ms: u64,
+    pub emulated_compositor_ms: u64,
+    pub emulated_vsync_ms: u64,
+    pub use_random_position: bool,
+    pub random_position_offset_magnitude: f32,
+    pub random_position_interval_ms: u64,
+    // The sequence is looped. If empty, the head stays still
+    pub poses: Vec<ScriptedPose>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let input = WindowInput::default();
+
+        Self {
+            duration_s: 60.0,
+            emulated_decode_ms: input.emulated_decode_ms,
+            emulated_compositor_ms: input.emulated_compositor_ms,
+            emulated_vsync_ms: input.emulated_vsync_ms,
+            use_random_position: input.use_random_position,
+            random_position_offset_magnitude: input.random_position_offset_magnitude,
+            random_position_interval_ms: input.random_position_interval_ms,
+            poses: vec![],
+        }
+    }
+}
+
+impl Scenario {
+    pub fn input_at(&self, time_s: f32) -> WindowInput {
+        let mut input = WindowInput {
+            use_random_position: self.use_random_position,
+            random_position_offset_magnitude: self.random_position_offset_magnitude,
+            random_position_interval_ms: self.random_position_interval_ms,
+            emulated_decode_ms: self.emulated_decode_ms,
+            emulated_compositor_ms: self.emulated_compositor_ms,
+            emulated_vsync_ms: self.emulated_vsync_ms,
+            ..Default::default()
+        };
+
+        let (Some(first), Some(last)) = (self.poses.first(), self.poses.last()) else {
+            return input;
+        };
+
+        let time_s = if last.time_s > 0.0 {
+            time_s % last.time_s
+        } else {
+            0.0
+        };
+
+        let next_index = self
+            .poses
+            .iter()
+            .position(|pose| pose.time_s > time_s)
+            .unwrap_or(self.poses.len());
+
+        let pose = if next_index == 0 {
+            *first
+        } else if next_index == self.poses.len() {
+            *last
+        } else {
+            let from = self.poses[next_index - 1];
+            let to = self.poses[next_index];
+            let t = (time_s - from.time_s) / (to.time_s - from.time_s);
+
+            ScriptedPose {
+                time_s,
+                height: from.height + (to.height - from.height) * t,
+                yaw: from.yaw + (to.yaw - from.yaw) * t,
+                pitch: from.pitch + (to.pitch - from.pitch) * t,
+            }
+        };
+
+        input.height = pose.height;
+        input.yaw = pose.yaw;
+        input.pitch = pose.pitch;
+
+        input
+    }
+}
@@ -0,0 +1,66 @@
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Source of time for components whose behavior depends on time measurements. This allows driving
+// them in simulated time from tests and offline tools.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+// Clock that advances only when requested. The origin is taken when the clock is created.
+pub struct SimulatedClock {
+    origin: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl SimulatedClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock()
+    }
+
+    pub fn advance(&self, interval: Duration) {
+        *self.elapsed.lock() += interval;
+    }
+
+    // The clock is monotonic: setting a time earlier than the current one has no effect
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        let mut elapsed_lock = self.elapsed.lock();
+        *elapsed_lock = Duration::max(*elapsed_lock, elapsed);
+    }
+}
+
+impl Default for SimulatedClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        self.origin + *self.elapsed.lock()
+    }
+}
@@ -1,4 +1,5 @@
 mod average;
+mod clock;
 mod connection_result;
 mod inputs;
 mod logging;
@@ -20,6 +21,7 @@ pub use semver;
 pub use settings_schema;
 
 pub use average::*;
+pub use clock::*;
 pub use connection_result::*;
 pub use inputs::*;
 pub use log::{debug, error, info, warn};
@@ -31,6 +33,16 @@ pub use weighted::*;
 
 pub const ALVR_NAME: &str = "ALVR";
 
+// Short form of a public key, displayed both on the dashboard and on the client to be compared by
+// the user
+pub fn key_fingerprint(public_key: &[u8; 32]) -> String {
+    public_key[..8]
+        .chunks(2)
+        .map(|pair| format!("{:02X}{:02X}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
 pub type OptLazy<T> = Lazy<Mutex<Option<T>>>;
 
 pub const fn lazy_mut_none<T>() -> OptLazy<T> {
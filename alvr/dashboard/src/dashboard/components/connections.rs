@@ -146,6 +146,13 @@ impl ConnectionsTab {
     }
 }
 
+fn fingerprint(data: &ClientConnectionConfig) -> String {
+    data.public_key
+        .as_ref()
+        .map(alvr_common::key_fingerprint)
+        .unwrap_or_else(|| "Unknown".into())
+}
+
 fn new_clients_section(
     ui: &mut Ui,
     clients: &[(String, ClientConnectionConfig)],
@@ -159,7 +166,7 @@ fn new_clients_section(
                 ui.add_space(5.0);
                 ui.heading("New clients");
             });
-            for (hostname, _) in clients {
+            for (hostname, data) in clients {
                 Frame::group(ui.style())
                     .fill(theme::DARKER_BG)
                     .inner_margin(egui::vec2(15.0, 12.0))
@@ -170,6 +177,9 @@ fn new_clients_section(
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
                                     ui.label(hostname);
+                                    // To be compared with the fingerprint shown on the headset
+                                    // before trusting the client
+                                    ui.weak(format!("fingerprint: {}", fingerprint(data)));
                                 });
                                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                     if ui.button("Trust").clicked() {
@@ -243,10 +253,11 @@ fn trusted_clients_section(
                                     ui.end_row();
 
                                     ui.label(format!(
-                                        "{hostname}: {}",
+                                        "{hostname}: {}, fingerprint: {}",
                                         data.current_ip
                                             .map(|ip| ip.to_string())
                                             .unwrap_or_else(|| "Unknown IP".into()),
+                                        fingerprint(data),
                                     ));
                                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                         if ui.button("Remove").clicked() {
@@ -356,6 +356,18 @@ impl StatisticsTab {
                     Color32::RED,
                 );
                 maybe_label(ui, "OW Delay", Some(stats.ow_delay_ms), Color32::BLUE);
+                maybe_label(
+                    ui,
+                    "Downlink OWD (absolute)",
+                    stats.downlink_owd_ms,
+                    Color32::GRAY,
+                );
+                maybe_label(
+                    ui,
+                    "Uplink OWD (feedback)",
+                    stats.uplink_owd_ms,
+                    Color32::GRAY,
+                );
 
                 // maybe_label(
                 //     ui,
@@ -371,12 +383,13 @@ impl StatisticsTab {
         self.draw_network_graph(
             ui,
             available_width,
-            "Frames Skipped, Shards Lost and Shards Duplicated Graph",
+            "Frames Skipped, Shards Lost, Shards Duplicated and FEC Recovered Frames Graph",
             -20.0..=20.0 as f32,
             |painter, to_screen_trans| {
                 let mut frameskipped = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut shardloss = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut dup_shards = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut fec_recovered = Vec::with_capacity(GRAPH_HISTORY_SIZE);
 
                 for i in 0..GRAPH_HISTORY_SIZE {
                     let pointer_graphstatistics = &self.history_network[i];
@@ -389,11 +402,15 @@ impl StatisticsTab {
 
                     let val_dups = pointer_graphstatistics.shards_duplicated;
                     dup_shards.push(to_screen_trans * pos2(i as f32, val_dups as f32));
+
+                    let val_fec = pointer_graphstatistics.fec_recovered_frames;
+                    fec_recovered.push(to_screen_trans * pos2(i as f32, val_fec as f32));
                 }
 
                 draw_lines(painter, frameskipped, Color32::LIGHT_BLUE);
                 draw_lines(painter, shardloss, Color32::LIGHT_RED);
                 draw_lines(painter, dup_shards, Color32::DARK_GREEN);
+                draw_lines(painter, fec_recovered, Color32::GOLD);
             },
             |ui, stats| {
                 fn maybe_label(
@@ -425,6 +442,26 @@ impl StatisticsTab {
                     Some(graphstats.shards_duplicated as f32),
                     Color32::DARK_GREEN,
                 );
+                maybe_label(
+                    ui,
+                    "FEC Recovered Frames",
+                    Some(graphstats.fec_recovered_frames as f32),
+                    Color32::GOLD,
+                );
+                ui.label(&format!(
+                    "Shard Loss: {:.2}% Max Reordering: {} Loss Bursts (1/2/3-4/5-8/9+): {:?}",
+                    graphstats.shard_loss_rate * 100.0,
+                    graphstats.max_reordering_depth,
+                    graphstats.loss_burst_histogram
+                ));
+                ui.label(&format!(
+                    "Evicted Frames: {} Discarded Shards: {}",
+                    graphstats.evicted_frames, graphstats.discarded_shards
+                ));
+                ui.label(&format!(
+                    "Authentication Failures (DL/UL): {}/{}",
+                    graphstats.authentication_failures, graphstats.uplink_authentication_failures
+                ));
             },
         )
     }
@@ -513,6 +550,7 @@ impl StatisticsTab {
                 let mut network_throughput_bps: Vec<Pos2> = Vec::with_capacity(GRAPH_HISTORY_SIZE);
 
                 let mut requested = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut probe_capacity = Vec::with_capacity(GRAPH_HISTORY_SIZE);
 
                 for i in 0..GRAPH_HISTORY_SIZE {
                     let pointer_graphstatistics = &self.history_network[i];
@@ -522,9 +560,13 @@ impl StatisticsTab {
                     network_throughput_bps.push(to_screen_trans * pos2(i as f32, value_nw / 1e6));
 
                     requested.push(to_screen_trans * pos2(i as f32, nom_br.requested_bps / 1e6));
+
+                    let value_probe = pointer_graphstatistics.probe_capacity_bps;
+                    probe_capacity.push(to_screen_trans * pos2(i as f32, value_probe / 1e6));
                 }
                 draw_lines(painter, network_throughput_bps, Color32::BLUE);
                 draw_lines(painter, requested, theme::OK_GREEN);
+                draw_lines(painter, probe_capacity, Color32::GOLD);
             },
             |ui, stats| {
                 fn maybe_label(
@@ -552,6 +594,12 @@ impl StatisticsTab {
                     Some(n.requested_bps),
                     theme::OK_GREEN,
                 );
+                maybe_label(
+                    ui,
+                    "Probe Capacity Estimate",
+                    (graphstats.probe_capacity_bps > 0.0).then_some(graphstats.probe_capacity_bps),
+                    Color32::GOLD,
+                );
             },
         )
     }
@@ -1,6 +1,6 @@
 use alvr_common::{debug, error, info, parking_lot::Mutex, warn, RelaxedAtomic};
 use alvr_events::{Event, EventType};
-use alvr_packets::ServerRequest;
+use alvr_packets::{ServerRequest, WEB_API_TOKEN_HEADER};
 use alvr_server_io::ServerDataManager;
 use eframe::egui;
 use std::{
@@ -85,6 +85,7 @@ impl DataSources {
 
         let server_data_manager = get_local_data_source();
         let port = server_data_manager.settings().connection.web_server_port;
+        let api_token = server_data_manager.session().web_api_token.clone();
         let data_source = Arc::new(Mutex::new(DataSource::Local(Box::new(server_data_manager))));
 
         let requests_thread = thread::spawn({
@@ -108,7 +109,10 @@ impl DataSources {
                                 ServerRequest::GetSession => {
                                     report_session_local(&context, &events_sender, data_manager);
                                 }
-                                ServerRequest::UpdateSession(session) => {
+                                ServerRequest::UpdateSession(mut session) => {
+                                    // The token cannot be changed by the dashboard
+                                    session.web_api_token =
+                                        data_manager.session().web_api_token.clone();
                                     *data_manager.session_mut() = *session;
 
                                     report_session_local(&context, &events_sender, data_manager);
@@ -190,7 +194,11 @@ impl DataSources {
                                 }
                             }
                         } else {
-                            request_agent.get(&uri).send_json(&request).ok();
+                            request_agent
+                                .get(&uri)
+                                .set(WEB_API_TOKEN_HEADER, &api_token)
+                                .send_json(&request)
+                                .ok();
                         }
                     }
 
@@ -1,5 +1,5 @@
 use alvr_events::Event;
-use alvr_packets::ServerRequest;
+use alvr_packets::{ServerRequest, WEB_API_TOKEN_HEADER};
 use eframe::{egui, web_sys};
 use ewebsock::{WsEvent, WsMessage, WsReceiver};
 use gloo_net::http::Request;
@@ -7,20 +7,33 @@ use gloo_net::http::Request;
 pub struct DataSources {
     context: egui::Context,
     ws_receiver: Option<WsReceiver>,
+    api_token: String,
 }
 
 impl DataSources {
     pub fn new(context: egui::Context) -> Self {
+        // The web API token is passed in the URL fragment, which is not sent to the server:
+        // http://<streamer IP>:8082/#token=<token>
+        let hash = web_sys::window()
+            .unwrap()
+            .location()
+            .hash()
+            .unwrap_or_default();
+        let api_token = hash.strip_prefix("#token=").unwrap_or_default().to_owned();
+
         Self {
             context,
             ws_receiver: None,
+            api_token,
         }
     }
 
     pub fn request(&self, request: ServerRequest) {
         let context = self.context.clone();
+        let api_token = self.api_token.clone();
         wasm_bindgen_futures::spawn_local(async move {
             Request::post("/api/dashboard-request")
+                .header(WEB_API_TOKEN_HEADER, &api_token)
                 .body(serde_json::to_string(&request).unwrap())
                 .send()
                 .await
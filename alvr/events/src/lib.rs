@@ -48,6 +48,19 @@ pub struct NominalBitrateStats {
     pub manual_max_bps: Option<f32>,
     pub manual_min_bps: Option<f32>,
     pub requested_bps: f32,
+    // Set by NeSt-VR at each adjustment period
+    #[serde(default)]
+    pub nestvr_decision: Option<NestVrDecision>,
+}
+
+// Step chosen by the NeSt-VR heuristic, before the capacity limit is applied
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NestVrDecision {
+    Increase,
+    Hold,
+    DecreaseRtt,
+    DecreaseLoss,
+    DecreaseNfr,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct GraphStatistics {
@@ -88,16 +101,71 @@ pub struct GraphNetworkStatistics {
 
     pub rtt_ms: f32,
 
+    // Absolute one-way delays, available once the client clock is synchronized with the server
+    // clock. The uplink delay is measured on the network statistics feedback itself
+    #[serde(default)]
+    pub downlink_owd_ms: Option<f32>,
+    #[serde(default)]
+    pub uplink_owd_ms: Option<f32>,
+    #[serde(default)]
+    pub clock_drift_ppm: f32,
+
     pub frame_interarrival_ms: f32,
     pub frame_jitter_ms: f32,
 
     pub frames_skipped: u32,
+    #[serde(default)]
+    pub fec_recovered_frames: u32,
+
+    #[serde(default)]
+    pub nacks_sent: u32,
+    #[serde(default)]
+    pub retransmitted_shards: usize,
+    #[serde(default)]
+    pub late_recovered_frames: u32,
+    // Retransmitted shards received after their frame was completed or abandoned
+    #[serde(default)]
+    pub wasted_retransmissions: u32,
+    // Network statistics packets lost before this one, when sent on the stream socket
+    #[serde(default)]
+    pub lost_feedback_packets: u32,
+    // Dropped by the client to stay within the video receive buffer budget
+    #[serde(default)]
+    pub evicted_frames: u32,
+    #[serde(default)]
+    pub discarded_shards: u32,
+    // Shards that failed authentication, received by the client and by the server
+    #[serde(default)]
+    pub authentication_failures: u32,
+    #[serde(default)]
+    pub uplink_authentication_failures: u32,
+
+    #[serde(default)]
+    pub pacing_rate_bps: f32,
+    // The frame was not stretched by the network beyond the pacer send duration: the peak
+    // throughput is a lower bound of the capacity
+    #[serde(default)]
+    pub pacing_limited: bool,
 
     pub shards_lost: isize,
+    // Not present in logs recorded before this field was added
+    #[serde(default)]
+    pub shards_sent: usize,
     pub shards_duplicated: u32,
+    // Derived from the per-frame received shard reports of the client. Loss bursts are counted
+    // by length, in buckets of 1, 2, 3-4, 5-8 and 9+ shards
+    #[serde(default)]
+    pub shard_loss_rate: f32,
+    #[serde(default)]
+    pub loss_burst_histogram: [u32; 5],
+    #[serde(default)]
+    pub max_reordering_depth: u32,
 
     pub instant_network_throughput_bps: f32,
     pub peak_network_throughput_bps: f32,
+    // Latest capacity estimate from probe trains, 0 if capacity probing is disabled
+    #[serde(default)]
+    pub probe_capacity_bps: f32,
 
     pub nominal_bitrate: NominalBitrateStats,
 
@@ -113,14 +181,48 @@ pub struct HeuristicStats {
     pub network_heur_fps: f32,
     pub rtt_avg_heur_s: f32,
     pub random_prob: f32,
+    pub packet_loss_rate_avg: f32,
+    pub frame_skip_rate_avg: f32,
+    #[serde(default)]
+    pub feedback_loss_rate_avg: f32,
 
     pub threshold_fps: f32,
     pub threshold_rtt_s: f32,
     pub threshold_u: f32,
+    pub threshold_loss: Option<f32>,
 
     pub requested_bitrate_bps: f32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, Default, PartialEq, Eq)]
+pub enum BandwidthUsage {
+    #[default]
+    Normal,
+    Overuse,
+    Underuse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, Default, PartialEq, Eq)]
+pub enum RateControlState {
+    #[default]
+    Hold,
+    Increase,
+    Decrease,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, Default)]
+pub struct DelayBasedStats {
+    pub filtered_ow_delay_ms: f32,
+    pub threshold_ms: f32,
+    pub overuse_time_ms: f32,
+
+    pub bandwidth_usage: BandwidthUsage,
+    pub rate_control_state: RateControlState,
+
+    pub received_bitrate_bps: f32,
+    pub requested_bitrate_bps: f32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TrackingEvent {
     pub head_motion: Option<DeviceMotion>,
@@ -155,6 +257,7 @@ pub enum EventType {
     GraphStatistics(GraphStatistics),
     GraphNetworkStatistics(GraphNetworkStatistics),
     HeuristicStats(HeuristicStats),
+    DelayBasedStats(DelayBasedStats),
     Tracking(Box<TrackingEvent>),
     Buttons(Vec<ButtonEvent>),
     Haptics(HapticsEvent),
@@ -169,6 +272,11 @@ pub struct Event {
     pub event_type: EventType,
 }
 
-pub fn send_event(event_type: EventType) {
+// Events are written to the session log and to stdout, the web API token must never be part of them
+pub fn send_event(mut event_type: EventType) {
+    if let EventType::Session(session) = &mut event_type {
+        session.web_api_token.clear();
+    }
+
     info!("{}", serde_json::to_string(&event_type).unwrap());
 }
@@ -16,6 +16,9 @@ pub const HAPTICS: u16 = 1;
 pub const AUDIO: u16 = 2;
 pub const VIDEO: u16 = 3;
 pub const STATISTICS: u16 = 4;
+pub const PROBE: u16 = 5;
+// Network statistics, when sent on the stream socket. The header is a ClientControlPacket
+pub const FEEDBACK: u16 = 6;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VideoStreamingCapabilities {
@@ -31,10 +34,28 @@ pub enum ClientConnectionResult {
         display_name: String,
         server_ip: IpAddr,
         streaming_capabilities: Option<VideoStreamingCapabilities>,
+        // Ephemeral X25519 key, used if the server enables stream encryption
+        stream_public_key: [u8; 32],
+        // Persistent Ed25519 key, pinned by the server when the client is trusted
+        identity_public_key: [u8; 32],
+        // SHA-256 of the self-signed certificate used if the stream protocol is QUIC
+        quic_certificate_hash: [u8; 32],
     },
     ClientStandby,
 }
 
+// Sent by the server after ConnectionAccepted. The client proves the ownership of its identity key
+// by signing the challenge together with its stream public key and QUIC certificate hash
+#[derive(Serialize, Deserialize)]
+pub struct AuthenticationChallenge {
+    pub nonce: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthenticationResponse {
+    pub signature: Vec<u8>, // Ed25519 signature, 64 bytes
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct StreamConfigPacket {
     pub session: String, // JSON session that allows for extrapolation
@@ -57,6 +78,8 @@ pub enum ServerControlPacket {
     ServerPredictionAverage(Duration), // todo: remove
     Reserved(String),
     ReservedBuffer(Vec<u8>),
+
+    ClockSyncResponse(ClockSyncResponsePacket),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -88,14 +111,84 @@ pub struct NetworkStatisticsPacket {
     pub filtered_ow_delay: f32,
 
     pub frames_skipped: u32,
+    pub fec_recovered_frames: u32,
+
+    pub nacks_sent: u32,
+    pub late_recovered_frames: u32,
+    // Retransmitted shards received after their frame was completed or abandoned
+    pub wasted_retransmissions: u32,
+
+    // Dropped by the client to stay within the video receive buffer budget
+    pub evicted_frames: u32,
+    pub discarded_shards: u32,
 
     pub rx_bytes: u32,
 
     pub rx_shard_counter: u32,
     pub duplicated_shard_counter: u32,
+    // Shards dropped because they failed authentication, with stream encryption
+    pub authentication_failures: u32,
+
+    // Received shards of the video frames completed or abandoned since the previous statistics
+    pub shard_loss_reports: Vec<ShardLossReport>,
+
+    // Available once the client clock is synchronized with the server clock. Microseconds, it can
+    // be negative if the clock synchronization is inaccurate
+    pub downlink_ow_delay_us: Option<i64>,
+    // Send time of this packet in the server time base (microseconds since the server reference
+    // time), used to measure the uplink one-way delay
+    pub tx_server_time_us: Option<u64>,
+    pub clock_drift_ppm: f32,
+}
+
+// Network statistics of a frame held by the client until its batch is sent. The hold duration is
+// subtracted from the VF-RTT and the uplink one-way delay
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchedNetworkStatistics {
+    pub statistics: NetworkStatisticsPacket,
+    pub hold_duration: Duration,
+}
+
+// Received shards of a video frame, as a run-length encoding of alternating received and lost
+// runs, starting with a received run (possibly of length 0). Shards are indexed in send order,
+// data shards first. For completed frames, the runs stop at the last received shard since the
+// remaining parity shards were not needed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShardLossReport {
+    pub frame_index: u32,
+    pub runs: Vec<u32>,
+    // Maximum distance between the highest shard index received so far and a shard received
+    // after it
+    pub reordering_depth: u32,
+}
 
-    pub highest_rx_frame_index: i32,
-    pub highest_rx_shard_index: i32,
+// NTP-style clock synchronization. Times are relative to the stream socket reference time of each
+// peer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClockSyncRequestPacket {
+    pub client_send_time: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClockSyncResponsePacket {
+    pub client_send_time: Duration,
+    pub server_receive_time: Duration,
+    pub server_send_time: Duration,
+}
+
+// Request for retransmission of the missing shards of a packet
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NackPacket {
+    pub stream_id: u16,
+    pub packet_index: u32,
+    pub shard_indices: Vec<u32>,
+}
+
+// Capacity estimated by the client from the dispersion of a probe train
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbeReportPacket {
+    pub train_index: u32,
+    pub capacity_bps: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
@@ -126,6 +219,10 @@ pub enum ClientControlPacket {
     ReservedBuffer(Vec<u8>),
 
     NetworkStatistics(NetworkStatisticsPacket),
+    Nack(NackPacket),
+    ProbeReport(ProbeReportPacket),
+    ClockSyncRequest(ClockSyncRequestPacket),
+    NetworkStatisticsBatch(Vec<BatchedNetworkStatistics>),
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -136,6 +233,10 @@ pub struct FaceData {
     pub htc_lip_expression: Option<Vec<f32>>, // issue: Serialize does not support [f32; 37]
 }
 
+// Probe trains carry only padding
+#[derive(Serialize, Deserialize)]
+pub struct ProbeHeader;
+
 #[derive(Serialize, Deserialize)]
 pub struct VideoPacketHeader {
     pub timestamp: Duration,
@@ -215,6 +316,7 @@ pub enum ClientListAction {
     RemoveEntry,
     UpdateCurrentIp(Option<IpAddr>),
     SetConnectionState(ConnectionState),
+    SetPublicKey([u8; 32]),
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -245,6 +347,9 @@ pub enum FirewallRulesAction {
     Remove,
 }
 
+// Header carrying the web API token
+pub const WEB_API_TOKEN_HEADER: &str = "X-ALVR-API-Token";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ServerRequest {
     Log(LogEntry),
@@ -267,3 +372,14 @@ pub enum ServerRequest {
     RestartSteamvr,
     ShutdownSteamvr,
 }
+
+impl ServerRequest {
+    // Requests that don't change the server state are allowed without the web API token. Log is
+    // not one of them, it writes arbitrary lines to the session log and to the event stream
+    pub fn is_mutating(&self) -> bool {
+        !matches!(
+            self,
+            Self::GetSession | Self::GetAudioDevices | Self::GetDriverList
+        )
+    }
+}
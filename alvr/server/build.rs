@@ -17,6 +17,11 @@ fn get_ffmpeg_path() -> PathBuf {
 }
 
 fn main() {
+    // The headless server does not use the C++ driver
+    if env::var("CARGO_FEATURE_HEADLESS").is_ok() {
+        return;
+    }
+
     let platform_name = env::var("CARGO_CFG_TARGET_OS").unwrap();
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let cpp_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("cpp");
@@ -0,0 +1,3 @@
+fn main() {
+    alvr_server::run_headless();
+}
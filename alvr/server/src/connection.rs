@@ -1,5 +1,4 @@
 use crate::{
-    bitrate::BitrateManager,
     face_tracking::FaceTrackingSink,
     hand_gestures::{trigger_hand_gesture_actions, HandGestureManager, HAND_GESTURE_BUTTON_SET},
     haptics,
@@ -11,6 +10,7 @@ use crate::{
     SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
 };
 use alvr_audio::AudioDevice;
+use alvr_bitrate::BitrateManager;
 use alvr_common::{
     con_bail, debug, error,
     glam::{UVec2, Vec2},
@@ -18,22 +18,26 @@ use alvr_common::{
     once_cell::sync::Lazy,
     parking_lot::{Condvar, Mutex},
     settings_schema::Switch,
-    warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState, OptLazy, ToCon,
-    BUTTON_INFO, CONTROLLER_PROFILE_INFO, DEVICE_ID_TO_PATH, HEAD_ID, LEFT_HAND_ID,
-    QUEST_CONTROLLER_PROFILE_PATH, RIGHT_HAND_ID,
+    warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState, OptLazy,
+    SharedClock, ToCon, BUTTON_INFO, CONTROLLER_PROFILE_INFO, DEVICE_ID_TO_PATH, HEAD_ID,
+    LEFT_HAND_ID, QUEST_CONTROLLER_PROFILE_PATH, RIGHT_HAND_ID,
 };
 use alvr_events::{ButtonEvent, EventType, HapticsEvent, TrackingEvent};
 use alvr_packets::{
-    ClientConnectionResult, ClientControlPacket, ClientListAction, ClientStatistics, Haptics,
-    ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader, AUDIO, HAPTICS,
-    STATISTICS, TRACKING, VIDEO,
+    AuthenticationResponse, ClientConnectionResult, ClientControlPacket, ClientListAction,
+    ClientStatistics, ClockSyncResponsePacket, Haptics, NetworkStatisticsPacket, ProbeHeader,
+    ServerControlPacket, StreamConfigPacket, Tracking, VideoPacketHeader, AUDIO, FEEDBACK, HAPTICS,
+    PROBE, STATISTICS, TRACKING, VIDEO,
+};
+use alvr_server_io::ServerDataManager;
+use alvr_session::{
+    BitrateMode, ControllersEmulationMode, FrameSize, NetworkFeedbackTransport, OpenvrConfig,
+    SessionConfig, SocketProtocol,
 };
-use alvr_session::{BitrateMode, ControllersEmulationMode, FrameSize, OpenvrConfig, SessionConfig};
 use alvr_sockets::{
-    PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL,
-    KEEPALIVE_TIMEOUT,
+    FrameTracker, KeyExchange, NetworkImpairment, PeerType, ProtoControlSocket, ShardHeaderVersion,
+    StreamSender, StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
 };
-use std::sync::RwLock;
 use std::{
     collections::{HashMap, HashSet},
     io::Write,
@@ -53,20 +57,50 @@ const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
 
-const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
-
 static VIDEO_CHANNEL_SENDER: OptLazy<SyncSender<VideoPacket>> = alvr_common::lazy_mut_none();
 static HAPTICS_SENDER: OptLazy<StreamSender<Haptics>> = alvr_common::lazy_mut_none();
 static CONNECTION_THREADS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(vec![]));
 pub static CLIENTS_TO_BE_REMOVED: Lazy<Mutex<HashSet<String>>> =
     Lazy::new(|| Mutex::new(HashSet::new()));
 
-type InstantMap = Arc<RwLock<HashMap<u32, Instant>>>;
-
 fn align32(value: f32) -> u32 {
     ((value / 32.).floor() * 32.) as u32
 }
 
+// hold_duration: time the client delayed the statistics to send them in a batch
+fn report_network_statistics(
+    clock: &SharedClock,
+    reference_time: Instant,
+    video_frame_tracker: &FrameTracker,
+    network_stats: NetworkStatisticsPacket,
+    hold_duration: Duration,
+) {
+    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+        let now = clock.now();
+
+        let rtt = video_frame_tracker
+            .get(network_stats.frame_index as u32)
+            .map(|info| {
+                now.saturating_duration_since(info.send_start)
+                    .saturating_sub(hold_duration)
+            })
+            .unwrap_or(Duration::ZERO);
+
+        // The feedback carries its send time in the server time base
+        let uplink_ow_delay_us = network_stats.tx_server_time_us.map(|tx_time_us| {
+            let rx_time = now
+                .saturating_duration_since(reference_time)
+                .saturating_sub(hold_duration);
+
+            rx_time.as_micros() as i64 - tx_time_us as i64
+        });
+
+        let report = stats.report_network_statistics(network_stats, rtt, uplink_ow_delay_us);
+
+        BITRATE_MANAGER.lock().report_network_statistics(report);
+    }
+}
+
 fn is_streaming(client_hostname: &str) -> bool {
     SERVER_DATA_MANAGER
         .read()
@@ -258,7 +292,7 @@ pub fn handshake_loop() {
                 continue;
             }
 
-            for (client_hostname, client_ip) in clients {
+            for (client_hostname, (client_ip, identity_public_key)) in clients {
                 let trusted = {
                     let mut data_manager = SERVER_DATA_MANAGER.write();
 
@@ -270,6 +304,20 @@ pub fn handshake_loop() {
                         },
                     );
 
+                    // The announced key is shown on the dashboard and it's pinned when the client
+                    // is trusted. After that it can only be changed by removing the client
+                    if !data_manager
+                        .client_list()
+                        .get(&client_hostname)
+                        .map(|c| c.trusted)
+                        .unwrap_or(false)
+                    {
+                        data_manager.update_client_list(
+                            client_hostname.clone(),
+                            ClientListAction::SetPublicKey(identity_public_key),
+                        );
+                    }
+
                     if config.auto_trust_clients {
                         data_manager
                             .update_client_list(client_hostname.clone(), ClientListAction::Trust);
@@ -343,6 +391,61 @@ fn try_connect(mut client_ips: HashMap<IpAddr, String>) -> ConResult {
     Ok(())
 }
 
+// Challenge-response with the identity key of the client. Trusted clients without a pinned key
+// (added manually or before the keys were introduced) are pinned on first use
+fn authenticate_client(
+    proto_socket: &mut ProtoControlSocket,
+    server_data_manager: &mut ServerDataManager,
+    client_hostname: &str,
+    identity_public_key: &[u8; 32],
+    stream_public_key: &[u8; 32],
+    quic_certificate_hash: &[u8; 32],
+) -> ConResult {
+    let pinned_public_key = server_data_manager
+        .client_list()
+        .get(client_hostname)
+        .and_then(|c| c.public_key);
+
+    if let Some(pinned_public_key) = pinned_public_key {
+        if pinned_public_key != *identity_public_key {
+            con_bail!(
+                "Identity key mismatch for {client_hostname}! Expected fingerprint: {}, found: {}. \
+                If the client was reinstalled, remove it and trust it again",
+                alvr_common::key_fingerprint(&pinned_public_key),
+                alvr_common::key_fingerprint(identity_public_key),
+            );
+        }
+    }
+
+    let challenge = alvr_sockets::new_authentication_challenge();
+    proto_socket.send(&challenge).to_con()?;
+    let response = proto_socket.recv::<AuthenticationResponse>(HANDSHAKE_ACTION_TIMEOUT)?;
+
+    if let Err(e) = alvr_sockets::verify_authentication_response(
+        &challenge,
+        &response,
+        identity_public_key,
+        stream_public_key,
+        quic_certificate_hash,
+    ) {
+        con_bail!("Client {client_hostname} failed authentication: {e}");
+    }
+
+    if pinned_public_key.is_none() {
+        info!(
+            "Pinned identity key of {client_hostname}, fingerprint: {}",
+            alvr_common::key_fingerprint(identity_public_key)
+        );
+
+        server_data_manager.update_client_list(
+            client_hostname.to_owned(),
+            ClientListAction::SetPublicKey(*identity_public_key),
+        );
+    }
+
+    Ok(())
+}
+
 fn connection_pipeline(
     mut proto_socket: ProtoControlSocket,
     client_hostname: String,
@@ -377,6 +480,9 @@ fn connection_pipeline(
         client_protocol_id,
         display_name,
         streaming_capabilities,
+        stream_public_key,
+        identity_public_key,
+        quic_certificate_hash,
         ..
     } = connection_result
     {
@@ -395,17 +501,27 @@ fn connection_pipeline(
             return Ok(());
         }
 
-        streaming_capabilities
+        authenticate_client(
+            &mut proto_socket,
+            &mut server_data_lock,
+            &client_hostname,
+            &identity_public_key,
+            &stream_public_key,
+            &quic_certificate_hash,
+        )?;
+
+        streaming_capabilities.map(|caps| (caps, stream_public_key, quic_certificate_hash))
     } else {
         debug!("Found client in standby. Retrying");
         return Ok(());
     };
 
-    let streaming_caps = if let Some(streaming_caps) = maybe_streaming_caps {
-        streaming_caps
-    } else {
-        con_bail!("Only streaming clients are supported for now");
-    };
+    let (streaming_caps, client_stream_public_key, client_quic_certificate_hash) =
+        if let Some(caps) = maybe_streaming_caps {
+            caps
+        } else {
+            con_bail!("Only streaming clients are supported for now");
+        };
 
     let settings = server_data_lock.settings().clone();
 
@@ -484,6 +600,16 @@ fn connection_pipeline(
             0
         };
 
+    let stream_key_exchange = settings.connection.stream_encryption.then(KeyExchange::new);
+    let stream_public_key = stream_key_exchange.as_ref().map(KeyExchange::public_key);
+    let stream_encryption_keys = stream_key_exchange
+        .map(|key_exchange| {
+            key_exchange
+                .derive_keys(client_stream_public_key, true)
+                .to_con()
+        })
+        .transpose()?;
+
     let client_config = StreamConfigPacket {
         session: {
             let session = server_data_lock.session().clone();
@@ -493,6 +619,8 @@ fn connection_pipeline(
             "view_resolution": stream_view_resolution,
             "refresh_rate_hint": fps,
             "game_audio_sample_rate": game_audio_sample_rate,
+            "shard_header_version": ShardHeaderVersion::LATEST.number(),
+            "stream_public_key": stream_public_key,
         })
         .to_string(),
     };
@@ -524,7 +652,11 @@ fn connection_pipeline(
     if !matches!(signal, ClientControlPacket::StreamReady) {
         con_bail!("Got unexpected packet waiting for stream ack");
     }
+    let clock = alvr_common::system_clock();
+
     *STATISTICS_MANAGER.lock() = Some(StatisticsManager::new(
+        Arc::clone(&clock),
+        client_hostname.clone(),
         settings.connection.statistics_history_size,
         Duration::from_secs_f32(1.0 / fps),
         if let Switch::Enabled(config) = &settings.headset.controllers {
@@ -540,44 +672,86 @@ fn connection_pipeline(
     if let BitrateMode::NestVr {
         initial_bitrate_mbps,
         ..
+    }
+    | BitrateMode::DelayBased {
+        initial_bitrate_mbps,
+        ..
     } = &config_mode
     {
         initial_bitrate = *initial_bitrate_mbps;
     }
 
-    *BITRATE_MANAGER.lock() =
-        BitrateManager::new(settings.video.bitrate.history_size, fps, initial_bitrate);
+    *BITRATE_MANAGER.lock() = BitrateManager::new(
+        Arc::clone(&clock),
+        settings.video.bitrate.history_size,
+        fps,
+        initial_bitrate,
+    );
 
     let mut stream_socket = StreamSocketBuilder::connect_to_client(
         HANDSHAKE_ACTION_TIMEOUT,
         client_ip,
+        client_quic_certificate_hash,
         settings.connection.stream_port,
         settings.connection.stream_protocol,
         settings.connection.dscp,
         settings.connection.server_send_buffer_bytes,
         settings.connection.server_recv_buffer_bytes,
         settings.connection.packet_size as _,
+        ShardHeaderVersion::LATEST,
+        stream_encryption_keys,
+        Arc::clone(&clock),
     )?;
 
+    // Time base of the clock synchronization with the client
+    let reference_time = stream_socket.reference_time();
+
+    let network_impairment =
+        NetworkImpairment::new(settings.debug.network_impairment.clone().into_option());
+    stream_socket.enable_network_impairment(network_impairment.clone());
+
     let mut video_sender = stream_socket.request_stream(VIDEO);
+    // FEC is useless on TCP since shards are never lost
+    let video_fec_config = if matches!(
+        settings.connection.stream_protocol,
+        SocketProtocol::Udp | SocketProtocol::Quic
+    ) {
+        settings.connection.video_fec.clone().into_option()
+    } else {
+        None
+    };
+    if let Some(config) = &video_fec_config {
+        video_sender.set_fec_overhead_ratio(config.overhead_ratio);
+    }
+    let video_retransmitter =
+        if let (SocketProtocol::Udp | SocketProtocol::Quic, Switch::Enabled(config)) = (
+            &settings.connection.stream_protocol,
+            &settings.connection.video_nack,
+        ) {
+            Some(video_sender.enable_retransmission(config.max_cached_frames))
+        } else {
+            None
+        };
+    // Applies per stream
+    let receive_buffer_budget = settings.connection.stream_receive_buffer_mb as usize * 1024 * 1024;
     let game_audio_sender = stream_socket.request_stream(AUDIO);
-    let mut microphone_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
+    let mut microphone_receiver = stream_socket.subscribe_to_stream(AUDIO, receive_buffer_budget);
     let mut tracking_receiver =
-        stream_socket.subscribe_to_stream::<Tracking>(TRACKING, MAX_UNREAD_PACKETS);
+        stream_socket.subscribe_to_stream::<Tracking>(TRACKING, receive_buffer_budget);
     let haptics_sender = stream_socket.request_stream(HAPTICS);
     let mut statics_receiver =
-        stream_socket.subscribe_to_stream::<ClientStatistics>(STATISTICS, MAX_UNREAD_PACKETS);
+        stream_socket.subscribe_to_stream::<ClientStatistics>(STATISTICS, receive_buffer_budget);
 
     let (video_channel_sender, video_channel_receiver) =
         std::sync::mpsc::sync_channel(settings.connection.max_queued_server_video_frames);
     *VIDEO_CHANNEL_SENDER.lock() = Some(video_channel_sender);
     *HAPTICS_SENDER.lock() = Some(haptics_sender);
 
-    let map: InstantMap = Arc::new(RwLock::new(HashMap::new()));
+    let video_frame_tracker = video_sender.frame_tracker();
 
+    let video_pacing_config = settings.connection.video_pacing.clone();
     let video_send_thread = thread::spawn({
         let client_hostname = client_hostname.clone();
-        let map_clone: Arc<RwLock<HashMap<u32, Instant>>> = Arc::clone(&map);
         move || {
             while is_streaming(&client_hostname) {
                 let VideoPacket { header, payload } =
@@ -587,29 +761,40 @@ fn connection_pipeline(
                         Err(RecvTimeoutError::Disconnected) => return,
                     };
 
+                if let Switch::Enabled(config) = &video_pacing_config {
+                    let rate_bps = BITRATE_MANAGER.lock().target_bitrate_bps() as f64
+                        * config.bitrate_multiplier as f64;
+                    video_sender.set_pacing_rate(Some(rate_bps), config.burst_shards);
+                }
+
                 let mut buffer = video_sender.get_buffer(&header).unwrap();
                 // todo: make encoder write to socket buffers directly to avoid copy
                 buffer
                     .get_range_mut(0, payload.len())
                     .copy_from_slice(&payload);
+                buffer.set_idr(header.is_idr);
                 video_sender.send(buffer).ok();
 
-                let cloned_socket_map = video_sender.get_frame_tracker_map();
-
-                match map_clone.write() {
-                    Ok(mut guard) => {
-                        *guard = cloned_socket_map;
-                    }
-                    Err(_) => {
-                        warn!("Failed to acquire write lock in RTT hashmap");
-                    }
-                }
-
                 let frame_index = video_sender.get_last_packet_id();
                 let shards_count = video_sender.get_shards_count();
 
                 if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                    stats.report_frame_sent(header.timestamp, frame_index, shards_count);
+                    stats.report_frame_sent(
+                        header.timestamp,
+                        frame_index,
+                        shards_count,
+                        video_sender.get_last_send_duration(),
+                        video_sender.get_pacing_rate().unwrap_or(0.0) as f32,
+                    );
+
+                    if let Some(config) = &video_fec_config {
+                        if let Switch::Enabled(adaptive) = &config.adaptive {
+                            let ratio = (stats.shard_loss_rate() * adaptive.loss_rate_multiplier)
+                                .max(config.overhead_ratio)
+                                .min(adaptive.max_overhead_ratio);
+                            video_sender.set_fec_overhead_ratio(ratio);
+                        }
+                    }
                 }
             }
         }
@@ -715,6 +900,31 @@ fn connection_pipeline(
         thread::spawn(|| ())
     };
 
+    let capacity_probing_config = settings.connection.capacity_probing.clone();
+    let probe_thread = if let Switch::Enabled(config) = capacity_probing_config {
+        let mut probe_sender = stream_socket.request_stream(PROBE);
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            let interval = Duration::from_millis(config.interval_ms);
+            let mut deadline = Instant::now() + interval;
+            while is_streaming(&client_hostname) {
+                let now = Instant::now();
+                if now < deadline {
+                    // Wake up regularly to check if the stream is still running
+                    thread::sleep(Duration::min(deadline - now, STREAMING_RECV_TIMEOUT));
+                    continue;
+                }
+                deadline = now + interval;
+
+                if let Err(e) = probe_sender.send_padding_train(&ProbeHeader, config.train_length) {
+                    debug!("Failed to send probe train: {e}");
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     let tracking_manager = Arc::new(Mutex::new(TrackingManager::new()));
     let hand_gesture_manager = Arc::new(Mutex::new(HandGestureManager::new()));
 
@@ -943,6 +1153,63 @@ fn connection_pipeline(
         }
     });
 
+    let feedback_thread = if settings.connection.network_feedback_transport
+        == NetworkFeedbackTransport::StreamSocket
+    {
+        let mut feedback_receiver = stream_socket
+            .subscribe_to_stream::<ClientControlPacket>(FEEDBACK, receive_buffer_budget);
+        let video_frame_tracker = Arc::clone(&video_frame_tracker);
+        let clock = Arc::clone(&clock);
+        let client_hostname = client_hostname.clone();
+        thread::spawn(move || {
+            while is_streaming(&client_hostname) {
+                let data = match feedback_receiver.recv(STREAMING_RECV_TIMEOUT) {
+                    Ok(data) => data,
+                    Err(ConnectionError::TryAgain(_)) => continue,
+                    Err(ConnectionError::Other(_)) => return,
+                };
+
+                // Lost feedback must not be mistaken for lost video frames
+                let lost_packets = data.get_frames_skipped();
+                if lost_packets > 0 {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_lost_feedback(lost_packets);
+                    }
+                }
+                BITRATE_MANAGER
+                    .lock()
+                    .report_feedback_received(lost_packets);
+
+                match data.get_header() {
+                    Ok(ClientControlPacket::NetworkStatistics(network_stats)) => {
+                        report_network_statistics(
+                            &clock,
+                            reference_time,
+                            &video_frame_tracker,
+                            network_stats,
+                            Duration::ZERO,
+                        );
+                    }
+                    Ok(ClientControlPacket::NetworkStatisticsBatch(batch)) => {
+                        for record in batch {
+                            report_network_statistics(
+                                &clock,
+                                reference_time,
+                                &video_frame_tracker,
+                                record.statistics,
+                                record.hold_duration,
+                            );
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(e) => debug!("Failed to deserialize network feedback: {e}"),
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     let control_sender = Arc::new(Mutex::new(control_sender));
 
     let keepalive_thread = thread::spawn({
@@ -965,7 +1232,8 @@ fn connection_pipeline(
     });
 
     let control_receive_thread = thread::spawn({
-        let map_clone: Arc<RwLock<HashMap<u32, Instant>>> = Arc::clone(&map);
+        let video_frame_tracker = Arc::clone(&video_frame_tracker);
+        let clock = Arc::clone(&clock);
         let mut controller_button_mapping_manager = server_data_lock
             .settings()
             .headset
@@ -991,7 +1259,7 @@ fn connection_pipeline(
         move || {
             unsafe { crate::InitOpenvrClient() };
             let mut disconnection_deadline = Instant::now() + KEEPALIVE_TIMEOUT;
-            // Accessing the original HashMap from the main thread
+
             while is_streaming(&client_hostname) {
                 let packet = match control_receiver.recv(STREAMING_RECV_TIMEOUT) {
                     Ok(packet) => packet,
@@ -1034,33 +1302,65 @@ fn connection_pipeline(
                     }
 
                     ClientControlPacket::NetworkStatistics(network_stats) => {
-                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-                            let now = Instant::now();
-
-                            let map_rtt_lock = map_clone.read().unwrap();
-
-                            let mut hashmap = map_rtt_lock.clone();
-
-                            let frame_id = network_stats.frame_index as u32;
-
-                            let rtt: Duration;
-                            if let Some(send_instant) = hashmap.remove(&frame_id) {
-                                rtt = now.saturating_duration_since(send_instant);
-                            } else {
-                                rtt = Duration::ZERO;
-                            }
-
-                            let (peak_network_throughput_bps, frame_interarrival_s) =
-                                stats.report_network_statistics(network_stats, rtt);
-
-                            BITRATE_MANAGER.lock().report_network_statistics(
-                                rtt,
-                                peak_network_throughput_bps,
-                                frame_interarrival_s,
+                        report_network_statistics(
+                            &clock,
+                            reference_time,
+                            &video_frame_tracker,
+                            network_stats,
+                            Duration::ZERO,
+                        );
+                    }
+                    ClientControlPacket::NetworkStatisticsBatch(batch) => {
+                        for record in batch {
+                            report_network_statistics(
+                                &clock,
+                                reference_time,
+                                &video_frame_tracker,
+                                record.statistics,
+                                record.hold_duration,
                             );
                         }
                     }
 
+                    ClientControlPacket::Nack(nack) => {
+                        if let Some(retransmitter) = video_retransmitter
+                            .as_ref()
+                            .filter(|_| nack.stream_id == VIDEO)
+                        {
+                            match retransmitter.retransmit(nack.packet_index, &nack.shard_indices) {
+                                Ok(count) => {
+                                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                                        stats.report_retransmitted_shards(count);
+                                    }
+                                }
+                                Err(e) => debug!("Video shards retransmission failed: {e}"),
+                            }
+                        }
+                    }
+                    ClientControlPacket::ClockSyncRequest(request) => {
+                        let server_receive_time =
+                            clock.now().saturating_duration_since(reference_time);
+                        control_sender
+                            .lock()
+                            .send(&ServerControlPacket::ClockSyncResponse(
+                                ClockSyncResponsePacket {
+                                    client_send_time: request.client_send_time,
+                                    server_receive_time,
+                                    server_send_time: clock
+                                        .now()
+                                        .saturating_duration_since(reference_time),
+                                },
+                            ))
+                            .ok();
+                    }
+                    ClientControlPacket::ProbeReport(report) => {
+                        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                            stats.report_probe_capacity(report.capacity_bps);
+                        }
+                        BITRATE_MANAGER
+                            .lock()
+                            .report_probe_capacity(report.capacity_bps);
+                    }
                     ClientControlPacket::VideoErrorReport => {
                         unsafe { crate::VideoErrorReportReceive() };
                     }
@@ -1161,7 +1461,16 @@ fn connection_pipeline(
         let client_hostname = client_hostname.clone();
         move || {
             while is_streaming(&client_hostname) {
-                match stream_socket.recv() {
+                let res = stream_socket.recv();
+
+                let authentication_failures = stream_socket.take_authentication_failures();
+                if authentication_failures > 0 {
+                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                        stats.report_authentication_failures(authentication_failures);
+                    }
+                }
+
+                match res {
                     Ok(()) => (),
                     Err(ConnectionError::TryAgain(_)) => continue,
                     Err(e) => {
@@ -1188,6 +1497,16 @@ fn connection_pipeline(
                 .unwrap_or(false)
                 && *LIFECYCLE_STATE.read() == LifecycleState::Resumed
             {
+                network_impairment.set_config(
+                    SERVER_DATA_MANAGER
+                        .read()
+                        .settings()
+                        .debug
+                        .network_impairment
+                        .clone()
+                        .into_option(),
+                );
+
                 thread::sleep(STREAMING_RECV_TIMEOUT);
             }
 
@@ -1257,8 +1576,10 @@ fn connection_pipeline(
     video_send_thread.join().ok();
     game_audio_thread.join().ok();
     microphone_thread.join().ok();
+    probe_thread.join().ok();
     tracking_receive_thread.join().ok();
     statistics_thread.join().ok();
+    feedback_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
     keepalive_thread.join().ok();
@@ -1,4 +1,3 @@
-mod bitrate;
 mod c_api;
 mod connection;
 mod face_tracking;
@@ -6,12 +5,14 @@ mod hand_gestures;
 mod haptics;
 mod input_mapping;
 mod logging_backend;
+mod metrics;
 mod openvr_props;
 mod sockets;
 mod statistics;
 mod tracking;
 mod web_server;
 
+#[cfg(not(feature = "headless"))]
 #[allow(
     non_camel_case_types,
     non_upper_case_globals,
@@ -22,8 +23,20 @@ mod web_server;
 mod bindings {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
+#[cfg(feature = "headless")]
+#[allow(
+    non_camel_case_types,
+    non_upper_case_globals,
+    dead_code,
+    non_snake_case,
+    clippy::missing_safety_doc
+)]
+mod synthetic_driver;
 use bindings::*;
+#[cfg(feature = "headless")]
+use synthetic_driver as bindings;
 
+use alvr_bitrate::BitrateManager;
 use alvr_common::{
     error,
     glam::Quat,
@@ -37,7 +50,6 @@ use alvr_filesystem::{self as afs, Layout};
 use alvr_packets::{ClientListAction, DecoderInitializationConfig, VideoPacketHeader};
 use alvr_server_io::ServerDataManager;
 use alvr_session::{CodecType, Settings};
-use bitrate::BitrateManager;
 use statistics::StatisticsManager;
 use std::{
     collections::HashMap,
@@ -57,18 +69,29 @@ pub static LIFECYCLE_STATE: RwLock<LifecycleState> = RwLock::new(LifecycleState:
 pub static IS_RESTARTING: RelaxedAtomic = RelaxedAtomic::new(false);
 static CONNECTION_THREAD: RwLock<Option<JoinHandle<()>>> = RwLock::new(None);
 
+#[cfg(not(feature = "headless"))]
 static FILESYSTEM_LAYOUT: Lazy<Layout> = Lazy::new(|| {
     afs::filesystem_layout_from_openvr_driver_root_dir(
         &alvr_server_io::get_driver_dir_from_registered().unwrap(),
     )
 });
+// The headless server is not registered as a driver, it is placed next to the dashboard
+#[cfg(feature = "headless")]
+static FILESYSTEM_LAYOUT: Lazy<Layout> =
+    Lazy::new(|| afs::filesystem_layout_from_dashboard_exe(&env::current_exe().unwrap()));
 static SERVER_DATA_MANAGER: Lazy<RwLock<ServerDataManager>> =
     Lazy::new(|| RwLock::new(ServerDataManager::new(&FILESYSTEM_LAYOUT.session())));
 static WEBSERVER_RUNTIME: OptLazy<Runtime> = Lazy::new(|| Mutex::new(Runtime::new().ok()));
 
 static STATISTICS_MANAGER: OptLazy<StatisticsManager> = alvr_common::lazy_mut_none();
-static BITRATE_MANAGER: Lazy<Mutex<BitrateManager>> =
-    Lazy::new(|| Mutex::new(BitrateManager::new(256, 60.0, 30.0)));
+static BITRATE_MANAGER: Lazy<Mutex<BitrateManager>> = Lazy::new(|| {
+    Mutex::new(BitrateManager::new(
+        alvr_common::system_clock(),
+        256,
+        60.0,
+        30.0,
+    ))
+});
 
 pub struct VideoPacket {
     pub header: VideoPacketHeader,
@@ -396,20 +419,30 @@ pub unsafe extern "C" fn HmdDriverFactory(
     }
 
     extern "C" fn get_dynamic_encoder_params() -> FfiDynamicEncoderParams {
-        let (params, stats) = {
+        let maybe_params = {
             let server_data_lock = SERVER_DATA_MANAGER.read();
             BITRATE_MANAGER
                 .lock()
                 .get_encoder_params(&server_data_lock.settings().video.bitrate)
         };
 
-        if let Some(stats) = stats {
+        if let Some((params, stats)) = maybe_params {
             if let Some(stats_manager) = &mut *STATISTICS_MANAGER.lock() {
                 stats_manager.report_nominal_bitrate_stats(stats);
             }
-        }
 
-        params
+            FfiDynamicEncoderParams {
+                updated: 1,
+                bitrate_bps: params.bitrate_bps,
+                framerate: params.framerate,
+            }
+        } else {
+            FfiDynamicEncoderParams {
+                updated: 0,
+                bitrate_bps: 0,
+                framerate: 0.0,
+            }
+        }
     }
 
     extern "C" fn wait_for_vsync() {
@@ -452,3 +485,15 @@ pub unsafe extern "C" fn HmdDriverFactory(
 
     CppEntryPoint(interface_name, return_code)
 }
+
+// Run the streaming pipeline without SteamVR or a GPU, with a synthetic video source. This call is
+// blocking and returns when the driver shutdown is requested.
+#[cfg(feature = "headless")]
+pub fn run_headless() {
+    let mut return_code = 0;
+    unsafe { HmdDriverFactory(ptr::null(), &mut return_code) };
+
+    while !synthetic_driver::IS_SHUTDOWN_REQUESTED.value() {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
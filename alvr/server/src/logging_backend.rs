@@ -44,7 +44,7 @@ pub fn init_logging(events_sender: Sender<Event>) {
         );
     } else {
         // this sink is required to make sure all log gets processed and forwarded to the websocket
-        if cfg!(target_os = "linux") {
+        if cfg!(target_os = "linux") && !cfg!(feature = "headless") {
             log_dispatch = log_dispatch.chain(
                 fs::OpenOptions::new()
                     .write(true)
@@ -0,0 +1,229 @@
+// Prometheus text exposition format (version 0.0.4), served on /metrics. Each metric family is
+// declared once and followed by all its samples.
+
+use crate::{SERVER_DATA_MANAGER, STATISTICS_MANAGER};
+use alvr_common::ConnectionState;
+use std::fmt::Write;
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+#[derive(Default)]
+pub struct MetricsWriter {
+    buffer: String,
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+impl MetricsWriter {
+    pub fn family(&mut self, name: &str, kind: MetricKind, help: &str) {
+        let kind = match kind {
+            MetricKind::Gauge => "gauge",
+            MetricKind::Counter => "counter",
+        };
+
+        writeln!(self.buffer, "# HELP {name} {help}").ok();
+        writeln!(self.buffer, "# TYPE {name} {kind}").ok();
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.buffer.push_str(name);
+
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            write!(self.buffer, "{{{labels}}}").ok();
+        }
+
+        if value.is_infinite() {
+            let sign = if value > 0.0 { '+' } else { '-' };
+            writeln!(self.buffer, " {sign}Inf").ok();
+        } else {
+            writeln!(self.buffer, " {value}").ok();
+        }
+    }
+
+    // Shorthand for families with a single sample
+    pub fn metric(
+        &mut self,
+        name: &str,
+        kind: MetricKind,
+        help: &str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        self.family(name, kind, help);
+        self.sample(name, labels, value);
+    }
+
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+}
+
+// Connection state of every known client, and statistics of the streaming client
+pub fn encode_server_metrics() -> String {
+    let mut writer = MetricsWriter::default();
+
+    writer.family(
+        "alvr_client_connection_state",
+        MetricKind::Gauge,
+        "1 for the current connection state of the client",
+    );
+    for (hostname, client) in SERVER_DATA_MANAGER.read().client_list() {
+        for (state, label) in [
+            (ConnectionState::Disconnected, "disconnected"),
+            (ConnectionState::Connecting, "connecting"),
+            (ConnectionState::Connected, "connected"),
+            (ConnectionState::Streaming, "streaming"),
+            (ConnectionState::Disconnecting, "disconnecting"),
+        ] {
+            writer.sample(
+                "alvr_client_connection_state",
+                &[("client", hostname), ("state", label)],
+                if client.connection_state == state {
+                    1.0
+                } else {
+                    0.0
+                },
+            );
+        }
+    }
+
+    if let Some(stats) = &*STATISTICS_MANAGER.lock() {
+        stats.write_metrics(&mut writer);
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statistics::StatisticsManager;
+    use alvr_common::{SimulatedClock, HEAD_ID, LEFT_HAND_ID};
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+        time::Duration,
+    };
+
+    // Values of the samples, by metric name and labels
+    fn parse_samples(text: &str) -> HashMap<&str, &str> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.rsplit_once(' ').unwrap())
+            .collect()
+    }
+
+    // Every family is declared once with HELP and TYPE, before its samples
+    fn assert_families_declared_once(text: &str) {
+        let mut help_names = HashSet::new();
+        let mut type_names = HashSet::new();
+
+        for line in text.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                let (name, _) = help.split_once(' ').unwrap();
+                assert!(help_names.insert(name), "HELP of {name} repeated");
+            } else if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert!(kind == "gauge" || kind == "counter");
+                assert!(help_names.contains(name));
+                assert!(type_names.insert(name), "TYPE of {name} repeated");
+            } else {
+                let name = line.split(['{', ' ']).next().unwrap();
+                assert!(type_names.contains(name), "{name} is not declared");
+            }
+        }
+
+        assert_eq!(help_names, type_names);
+    }
+
+    #[test]
+    fn test_family_samples() {
+        let mut writer = MetricsWriter::default();
+        writer.family("alvr_test", MetricKind::Gauge, "Test family");
+        writer.sample("alvr_test", &[("client", "a")], 1.0);
+        writer.sample("alvr_test", &[("client", "b")], 0.5);
+        writer.metric("alvr_test_total", MetricKind::Counter, "Test", &[], 3.0);
+        let text = writer.finish();
+
+        assert_families_declared_once(&text);
+
+        let samples = parse_samples(&text);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[r#"alvr_test{client="a"}"#], "1");
+        assert_eq!(samples[r#"alvr_test{client="b"}"#], "0.5");
+        assert_eq!(samples["alvr_test_total"], "3");
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        let mut writer = MetricsWriter::default();
+        writer.metric(
+            "alvr_test",
+            MetricKind::Gauge,
+            "Test",
+            &[("client", "quest \"3\"\\\nline"), ("state", "ok")],
+            1.0,
+        );
+        let text = writer.finish();
+
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(
+            parse_samples(&text)[r#"alvr_test{client="quest \"3\"\\\nline",state="ok"}"#],
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_special_values() {
+        let mut writer = MetricsWriter::default();
+        writer.family("alvr_test", MetricKind::Gauge, "Test");
+        writer.sample("alvr_test", &[("value", "inf")], f64::INFINITY);
+        writer.sample("alvr_test", &[("value", "-inf")], f64::NEG_INFINITY);
+        writer.sample("alvr_test", &[("value", "nan")], f64::NAN);
+        let samples = parse_samples(&writer.finish());
+
+        assert_eq!(samples[r#"alvr_test{value="inf"}"#], "+Inf");
+        assert_eq!(samples[r#"alvr_test{value="-inf"}"#], "-Inf");
+        assert_eq!(samples[r#"alvr_test{value="nan"}"#], "NaN");
+    }
+
+    #[test]
+    fn test_statistics_families() {
+        let mut stats = StatisticsManager::new(
+            Arc::new(SimulatedClock::new()),
+            "client".into(),
+            16,
+            Duration::from_millis(11),
+            0.0,
+        );
+        stats.report_battery(*HEAD_ID, 0.8, false);
+        stats.report_battery(*LEFT_HAND_ID, 0.5, true);
+
+        let mut writer = MetricsWriter::default();
+        stats.write_metrics(&mut writer);
+        let text = writer.finish();
+
+        assert_families_declared_once(&text);
+
+        let samples = parse_samples(&text);
+        assert_eq!(
+            samples[r#"alvr_battery_plugged{client="client",device="/user/hand/left"}"#],
+            "1"
+        );
+    }
+}
@@ -22,8 +22,8 @@ impl WelcomeSocket {
         })
     }
 
-    // Returns: client IP, client hostname
-    pub fn recv_all(&mut self) -> Result<HashMap<String, IpAddr>> {
+    // Returns: client hostname, client IP and identity public key
+    pub fn recv_all(&mut self) -> Result<HashMap<String, (IpAddr, [u8; 32])>> {
         let mut clients = HashMap::new();
 
         loop {
@@ -53,10 +53,15 @@ impl WelcomeSocket {
                             .trim_end_matches('\x00')
                             .to_owned();
 
-                        clients.insert(hostname, address.ip());
+                        let mut identity_public_key = [0; 32];
+                        identity_public_key.copy_from_slice(&self.buffer[56..88]);
+
+                        clients.insert(hostname, (address.ip(), identity_public_key));
                     } else if &self.buffer[..16]
                         == b"\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00ALVR"
                         || &self.buffer[..5] == b"\x01ALVR"
+                        // Announcement without identity key
+                        || (size == 56 && &self.buffer[..ALVR_NAME.len()] == ALVR_NAME.as_bytes())
                     {
                         warn!("Found old client. Please upgrade")
                     } else {
@@ -1,14 +1,38 @@
-use alvr_common::{SlidingWindowAverage, SlidingWindowTimely, SlidingWindowWeighted, HEAD_ID};
+use crate::metrics::{MetricKind, MetricsWriter};
+use alvr_bitrate::NetworkStatisticsReport;
+use alvr_common::{
+    SharedClock, SlidingWindowAverage, SlidingWindowTimely, SlidingWindowWeighted,
+    DEVICE_ID_TO_PATH, HEAD_ID,
+};
 use alvr_events::{
-    EventType, GraphNetworkStatistics, GraphStatistics, NominalBitrateStats, StatisticsSummary,
+    EventType, GraphNetworkStatistics, GraphStatistics, NestVrDecision, NominalBitrateStats,
+    StatisticsSummary,
 };
 use alvr_packets::{ClientStatistics, NetworkStatisticsPacket};
 use std::{
     collections::{HashMap, VecDeque},
+    mem,
     time::{Duration, Instant},
 };
 
 const FULL_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+// A paced frame whose client-side span does not exceed the send duration by this ratio was not
+// slowed down by the network
+const PACING_LIMITED_SPAN_RATIO: f32 = 1.1;
+// Loss bursts are counted in buckets of length 1, 2, 3-4, 5-8 and 9+ shards
+const LOSS_BURST_BUCKETS: usize = 5;
+
+fn loss_burst_bucket(length: usize) -> usize {
+    usize::min(
+        (usize::BITS - (length - 1).leading_zeros()) as usize,
+        LOSS_BURST_BUCKETS - 1,
+    )
+}
+
+// True if lhs is after rhs, using wrapping arithmetics
+fn is_frame_newer(lhs: u32, rhs: u32) -> bool {
+    (lhs.wrapping_sub(rhs) as i32) > 0
+}
 
 #[derive(Clone)]
 pub struct HistoryFrame {
@@ -27,9 +51,8 @@ pub struct HistoryFrame {
     is_encoded: bool,
 }
 
-impl Default for HistoryFrame {
-    fn default() -> Self {
-        let now = Instant::now();
+impl HistoryFrame {
+    fn new(now: Instant) -> Self {
         Self {
             target_timestamp: Duration::ZERO,
 
@@ -55,6 +78,8 @@ struct BatteryData {
 }
 
 pub struct StatisticsManager {
+    clock: SharedClock,
+    client_hostname: String,
     history_buffer: VecDeque<HistoryFrame>,
     max_history_size: usize,
 
@@ -82,6 +107,13 @@ pub struct StatisticsManager {
     packets_skipped_total: usize,
     packets_skipped_partial_sum: usize,
 
+    // reset at every network statistics report
+    retransmitted_shards: usize,
+    lost_feedback_packets: u32,
+    authentication_failures: u32,
+
+    probe_capacity_bps: f32,
+
     battery_gauges: HashMap<u64, BatteryData>,
     steamvr_pipeline_latency: Duration,
 
@@ -102,6 +134,7 @@ pub struct StatisticsManager {
     client_frame_interval_average: SlidingWindowAverage<Duration>,
 
     frame_interarrival_average: SlidingWindowAverage<f32>,
+    shard_loss_rate_average: SlidingWindowAverage<f32>,
 
     server_frames_moving: SlidingWindowTimely<f32>,
     client_frames_moving: SlidingWindowTimely<f32>,
@@ -110,33 +143,54 @@ pub struct StatisticsManager {
     interval_avg_plot_throughput: f32,
     instant_weighted_avg_prev: Instant,
 
-    prev_highest_shard: i32,
-    prev_highest_frame: i32,
+    last_reported_frame: Option<u32>,
+    // Consecutive lost shards so far. Bursts can span multiple frames
+    loss_burst_length: usize,
 
     stats_history_buffer: VecDeque<HistoryFrame>,
     map_frames_spf: HashMap<u32, usize>,
+    // send duration and pacing rate (0 if not paced)
+    map_frames_pacing: HashMap<u32, (Duration, f32)>,
 
     is_first_stats: bool,
+
+    // Latest values and totals exposed on /metrics
+    last_rtt: Duration,
+    last_interarrival_jitter_s: f32,
+    last_filtered_ow_delay_s: f32,
+    last_instant_throughput_bps: f32,
+    last_peak_throughput_bps: f32,
+    last_actual_bitrate_bps: f32,
+    shards_sent_total: usize,
+    shards_lost_total: usize,
+    shards_duplicated_total: usize,
+    nestvr_decisions: HashMap<NestVrDecision, usize>,
 }
 
 impl StatisticsManager {
     // history size used to calculate average total pipeline latency
     pub fn new(
+        clock: SharedClock,
+        client_hostname: String,
         max_history_size: usize,
         nominal_server_frame_interval: Duration,
         steamvr_pipeline_frames: f32,
     ) -> Self {
+        let now = clock.now();
+
         Self {
+            clock,
+            client_hostname,
             history_buffer: VecDeque::new(),
             max_history_size,
 
-            last_full_report_instant: Instant::now(),
+            last_full_report_instant: now,
             last_nominal_bitrate_stats: NominalBitrateStats::default(),
 
-            last_frame_present_instant: Instant::now(),
+            last_frame_present_instant: now,
             last_frame_present_interval: Duration::ZERO,
 
-            last_vsync_time: Instant::now(),
+            last_vsync_time: now,
 
             video_packets_total: 0,
             video_packets_partial_sum: 0,
@@ -154,6 +208,12 @@ impl StatisticsManager {
             packets_skipped_total: 0,
             packets_skipped_partial_sum: 0,
 
+            retransmitted_shards: 0,
+            lost_feedback_packets: 0,
+            authentication_failures: 0,
+
+            probe_capacity_bps: 0.0,
+
             battery_gauges: HashMap::new(),
             steamvr_pipeline_latency: Duration::from_secs_f32(
                 steamvr_pipeline_frames * nominal_server_frame_interval.as_secs_f32(),
@@ -187,21 +247,34 @@ impl StatisticsManager {
             ),
 
             frame_interarrival_average: SlidingWindowAverage::new(0., max_history_size),
+            shard_loss_rate_average: SlidingWindowAverage::new(0., max_history_size),
 
             server_frames_moving: SlidingWindowTimely::new(60., 16., 1.),
             client_frames_moving: SlidingWindowTimely::new(60., 16., 1.),
 
             history_throughput_weighted: SlidingWindowWeighted::new(0., 0.0),
-            instant_weighted_avg_prev: Instant::now(),
+            instant_weighted_avg_prev: now,
             interval_avg_plot_throughput: 0. as f32,
 
-            prev_highest_shard: -1,
-            prev_highest_frame: 0,
+            last_reported_frame: None,
+            loss_burst_length: 0,
 
             stats_history_buffer: VecDeque::new(),
             map_frames_spf: HashMap::new(),
+            map_frames_pacing: HashMap::new(),
 
             is_first_stats: true,
+
+            last_rtt: Duration::ZERO,
+            last_interarrival_jitter_s: 0.0,
+            last_filtered_ow_delay_s: 0.0,
+            last_instant_throughput_bps: 0.0,
+            last_peak_throughput_bps: 0.0,
+            last_actual_bitrate_bps: 0.0,
+            shards_sent_total: 0,
+            shards_lost_total: 0,
+            shards_duplicated_total: 0,
+            nestvr_decisions: HashMap::new(),
         }
     }
 
@@ -211,10 +284,10 @@ impl StatisticsManager {
             .iter()
             .any(|frame| frame.target_timestamp == target_timestamp)
         {
+            let now = self.clock.now();
             self.history_buffer.push_front(HistoryFrame {
                 target_timestamp,
-                tracking_received: Instant::now(),
-                ..Default::default()
+                ..HistoryFrame::new(now)
             });
         }
 
@@ -229,7 +302,7 @@ impl StatisticsManager {
             .iter_mut()
             .find(|frame| frame.target_timestamp == target_timestamp)
         {
-            let now = Instant::now() - offset;
+            let now = self.clock.now() - offset;
 
             let interval = now.saturating_duration_since(self.last_frame_present_instant);
 
@@ -260,7 +333,7 @@ impl StatisticsManager {
         {
             frame.is_composed = true;
 
-            frame.frame_composed = Instant::now() - offset;
+            frame.frame_composed = self.clock.now() - offset;
         }
     }
 
@@ -284,7 +357,7 @@ impl StatisticsManager {
             frame.is_idr = is_idr;
             frame.is_encoded = true;
 
-            frame.frame_encoded = Instant::now();
+            frame.frame_encoded = self.clock.now();
 
             frame.video_packet_bytes = bytes_count;
 
@@ -301,6 +374,8 @@ impl StatisticsManager {
         target_timestamp: Duration,
         frame_index: u32,
         shards_count: usize,
+        send_duration: Duration,
+        pacing_rate_bps: f32,
     ) {
         if let Some(frame) = self
             .stats_history_buffer
@@ -310,6 +385,28 @@ impl StatisticsManager {
             frame.frame_index = frame_index as i32;
         }
         self.map_frames_spf.insert(frame_index, shards_count);
+        self.map_frames_pacing
+            .insert(frame_index, (send_duration, pacing_rate_bps));
+    }
+
+    pub fn report_retransmitted_shards(&mut self, count: usize) {
+        self.retransmitted_shards += count;
+    }
+
+    // Shards received by the server that failed authentication
+    pub fn report_authentication_failures(&mut self, count: u32) {
+        self.authentication_failures += count;
+    }
+
+    // The shard loss reports carried by the lost packets are unknown. Restart the frame sequence
+    // so that their frames are not counted as lost
+    pub fn report_lost_feedback(&mut self, lost_packets: u32) {
+        self.lost_feedback_packets += lost_packets;
+        self.last_reported_frame = None;
+    }
+
+    pub fn report_probe_capacity(&mut self, capacity_bps: f32) {
+        self.probe_capacity_bps = capacity_bps;
     }
 
     pub fn report_battery(&mut self, device_id: u64, gauge_value: f32, is_plugged: bool) {
@@ -320,6 +417,10 @@ impl StatisticsManager {
     }
 
     pub fn report_nominal_bitrate_stats(&mut self, stats: NominalBitrateStats) {
+        if let Some(decision) = stats.nestvr_decision {
+            *self.nestvr_decisions.entry(decision).or_default() += 1;
+        }
+
         self.last_nominal_bitrate_stats = stats;
     }
 
@@ -328,7 +429,8 @@ impl StatisticsManager {
         &mut self,
         network_stats: NetworkStatisticsPacket,
         rtt: Duration,
-    ) -> (f32, f32) {
+        uplink_ow_delay_us: Option<i64>,
+    ) -> NetworkStatisticsReport {
         self.packets_skipped_total += network_stats.frames_skipped as usize;
         self.packets_skipped_partial_sum += network_stats.frames_skipped as usize;
 
@@ -345,12 +447,25 @@ impl StatisticsManager {
             self.is_first_stats = false;
         }
 
-        let peak_network_throughput_bps: f32 = if network_stats.frame_span != 0.0 {
+        let mut peak_network_throughput_bps: f32 = if network_stats.frame_span != 0.0 {
             network_stats.bytes_in_frame as f32 * 8.0 / network_stats.frame_span
         } else {
             0.0
         };
 
+        // With pacing, the frame span is determined by the pacer unless the network is slower.
+        // In that case the capacity is at least the pacing rate
+        let (send_duration, pacing_rate_bps) = self
+            .map_frames_pacing
+            .get(&(network_stats.frame_index as u32))
+            .copied()
+            .unwrap_or((Duration::ZERO, 0.0));
+        let pacing_limited = pacing_rate_bps > 0.0
+            && network_stats.frame_span <= send_duration.as_secs_f32() * PACING_LIMITED_SPAN_RATIO;
+        if pacing_limited {
+            peak_network_throughput_bps = f32::max(peak_network_throughput_bps, pacing_rate_bps);
+        }
+
         let instant_network_throughput_bps: f32 = if network_stats.frame_interarrival != 0.0 {
             network_stats.rx_bytes as f32 * 8.0 / network_stats.frame_interarrival
         } else {
@@ -363,57 +478,79 @@ impl StatisticsManager {
         );
 
         let mut shards_sent: usize = 0;
-        let shards_lost: isize;
-
-        if self.prev_highest_frame == network_stats.highest_rx_frame_index as i32 {
-            if self.prev_highest_shard < network_stats.highest_rx_shard_index as i32 {
-                shards_sent =
-                    (network_stats.highest_rx_shard_index - self.prev_highest_shard) as usize;
+        let mut shards_lost: usize = 0;
+        let mut loss_burst_histogram = [0; LOSS_BURST_BUCKETS];
+        let mut max_reordering_depth = 0;
+
+        for report in &network_stats.shard_loss_reports {
+            if let Some(last_frame) = self.last_reported_frame {
+                if !is_frame_newer(report.frame_index, last_frame) {
+                    continue;
+                }
 
-                self.prev_highest_shard = network_stats.highest_rx_shard_index as i32;
+                // Frames without a report have been lost entirely
+                let lost_frames_shards = self
+                    .map_frames_spf
+                    .iter()
+                    .filter(|&(&frame, _)| {
+                        is_frame_newer(frame, last_frame)
+                            && is_frame_newer(report.frame_index, frame)
+                    })
+                    .map(|(_, &shards_count)| shards_count)
+                    .sum::<usize>();
+                shards_sent += lost_frames_shards;
+                shards_lost += lost_frames_shards;
+                self.loss_burst_length += lost_frames_shards;
             }
-        } else if self.prev_highest_frame < network_stats.highest_rx_frame_index as i32 {
-            let shards_from_prev = match self.map_frames_spf.get(&(self.prev_highest_frame as u32))
-            {
-                Some(&shards_count_prev) => {
-                    shards_count_prev.saturating_sub((self.prev_highest_shard + 1) as usize)
+            self.last_reported_frame = Some(report.frame_index);
+
+            // Runs alternate between received and lost shards, starting with received
+            for (idx, &run) in report.runs.iter().enumerate() {
+                let run = run as usize;
+                shards_sent += run;
+                if idx % 2 == 1 {
+                    shards_lost += run;
+                    self.loss_burst_length += run;
+                } else if run > 0 && self.loss_burst_length > 0 {
+                    loss_burst_histogram[loss_burst_bucket(self.loss_burst_length)] += 1;
+                    self.loss_burst_length = 0;
                 }
-                None => 0,
-            };
-
-            let shards_from_inbetween: usize = self
-                .map_frames_spf
-                .iter()
-                .filter(|&(frame, _)| {
-                    *frame > self.prev_highest_frame as u32
-                        && *frame < network_stats.highest_rx_frame_index as u32
-                })
-                .map(|(_, val)| *val)
-                .sum();
-
-            let shards_from_actual = network_stats.highest_rx_shard_index as usize + 1;
+            }
 
-            shards_sent = shards_from_prev + shards_from_inbetween + shards_from_actual;
+            max_reordering_depth = u32::max(max_reordering_depth, report.reordering_depth);
         }
 
-        shards_lost = shards_sent as isize - network_stats.rx_shard_counter as isize;
-
-        self.prev_highest_frame = network_stats.highest_rx_frame_index as i32;
-        self.prev_highest_shard = network_stats.highest_rx_shard_index as i32;
-
-        let keys_to_drop: Vec<_> = self
-            .map_frames_spf
-            .iter()
-            .filter(|&(frame, _)| *frame < self.prev_highest_frame as u32)
-            .map(|(key, _)| *key)
-            .collect();
+        if shards_sent > 0 {
+            self.shard_loss_rate_average
+                .submit_sample(shards_lost as f32 / shards_sent as f32);
+        }
 
-        for key in keys_to_drop {
-            self.map_frames_spf.remove_entry(&key);
+        self.last_rtt = rtt;
+        self.last_interarrival_jitter_s = network_stats.interarrival_jitter;
+        self.last_filtered_ow_delay_s = network_stats.filtered_ow_delay;
+        self.last_instant_throughput_bps = instant_network_throughput_bps;
+        self.last_peak_throughput_bps = peak_network_throughput_bps;
+        self.shards_sent_total += shards_sent;
+        self.shards_lost_total += shards_lost;
+        self.shards_duplicated_total += network_stats.duplicated_shard_counter as usize;
+
+        if let Some(last_frame) = self.last_reported_frame {
+            let keys_to_drop: Vec<_> = self
+                .map_frames_spf
+                .keys()
+                .filter(|&&frame| !is_frame_newer(frame, last_frame))
+                .copied()
+                .collect();
+
+            for key in keys_to_drop {
+                self.map_frames_spf.remove_entry(&key);
+                self.map_frames_pacing.remove(&key);
+            }
         }
 
-        if Instant::now().duration_since(self.instant_weighted_avg_prev) >= Duration::from_secs(1) {
-            self.instant_weighted_avg_prev = Instant::now();
+        let now = self.clock.now();
+        if now.duration_since(self.instant_weighted_avg_prev) >= Duration::from_secs(1) {
+            self.instant_weighted_avg_prev = now;
             self.interval_avg_plot_throughput = self.history_throughput_weighted.get_average();
         }
 
@@ -441,27 +578,66 @@ impl StatisticsManager {
 
             rtt_ms: rtt.as_secs_f32() * 1000.0,
 
+            downlink_owd_ms: network_stats
+                .downlink_ow_delay_us
+                .map(|delay_us| delay_us as f32 / 1000.0),
+            uplink_owd_ms: uplink_ow_delay_us.map(|delay_us| delay_us as f32 / 1000.0),
+            clock_drift_ppm: network_stats.clock_drift_ppm,
+
             frame_interarrival_ms: network_stats.frame_interarrival * 1000.0,
             frame_jitter_ms: self.frame_interarrival_average.get_std() * 1000.0,
 
             frames_skipped: network_stats.frames_skipped,
-
-            shards_lost: shards_lost,
+            fec_recovered_frames: network_stats.fec_recovered_frames,
+
+            nacks_sent: network_stats.nacks_sent,
+            retransmitted_shards: mem::take(&mut self.retransmitted_shards),
+            lost_feedback_packets: mem::take(&mut self.lost_feedback_packets),
+            late_recovered_frames: network_stats.late_recovered_frames,
+            wasted_retransmissions: network_stats.wasted_retransmissions,
+            evicted_frames: network_stats.evicted_frames,
+            discarded_shards: network_stats.discarded_shards,
+            authentication_failures: network_stats.authentication_failures,
+            uplink_authentication_failures: mem::take(&mut self.authentication_failures),
+
+            pacing_rate_bps,
+            pacing_limited,
+
+            shards_lost: shards_lost as isize,
+            shards_sent: shards_sent,
             shards_duplicated: network_stats.duplicated_shard_counter,
+            shard_loss_rate: self.shard_loss_rate_average.get_average(),
+            loss_burst_histogram,
+            max_reordering_depth,
 
             instant_network_throughput_bps: instant_network_throughput_bps,
             peak_network_throughput_bps: peak_network_throughput_bps,
+            probe_capacity_bps: self.probe_capacity_bps,
 
             nominal_bitrate: self.last_nominal_bitrate_stats.clone(),
 
             interval_avg_plot_throughput: self.interval_avg_plot_throughput,
         }));
 
-        return (peak_network_throughput_bps, frame_interarrival);
+        NetworkStatisticsReport {
+            rtt,
+            peak_throughput_bps: peak_network_throughput_bps,
+            frame_interarrival_s: frame_interarrival,
+            instant_throughput_bps: instant_network_throughput_bps,
+            filtered_ow_delay_s: network_stats.filtered_ow_delay,
+            shards_lost: shards_lost as isize,
+            shards_sent,
+            frames_skipped: network_stats.frames_skipped,
+        }
+    }
+
+    // Average fraction of video shards lost in the network, including FEC parity shards
+    pub fn shard_loss_rate(&self) -> f32 {
+        self.shard_loss_rate_average.get_average()
     }
 
     pub fn report_statistics_summary(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         if self.last_full_report_instant + FULL_REPORT_INTERVAL < now {
             let interval_secs = now
                 .saturating_duration_since(self.last_full_report_instant)
@@ -642,6 +818,7 @@ impl StatisticsManager {
             } else {
                 0.0
             };
+            self.last_actual_bitrate_bps = bitrate_bps;
 
             // todo: use target timestamp in nanoseconds. the dashboard needs to use the first
             // timestamp as the graph time origin.
@@ -687,7 +864,7 @@ impl StatisticsManager {
 
     // NB: this call is non-blocking, waiting should be done externally
     pub fn duration_until_next_vsync(&mut self) -> Duration {
-        let now = Instant::now();
+        let now = self.clock.now();
 
         // update the last vsync if it's too old
         while self.last_vsync_time + self.frame_interval < now {
@@ -696,4 +873,184 @@ impl StatisticsManager {
 
         (self.last_vsync_time + self.frame_interval).saturating_duration_since(now)
     }
+
+    pub fn write_metrics(&self, writer: &mut MetricsWriter) {
+        let client = self.client_hostname.as_str();
+
+        writer.family(
+            "alvr_pipeline_latency_seconds",
+            MetricKind::Gauge,
+            "Average latency of each stage of the video pipeline",
+        );
+        for (stage, average) in [
+            ("total", &self.total_pipeline_latency_average),
+            ("game", &self.game_delay_average),
+            ("server_compositor", &self.server_compositor_average),
+            ("encoder", &self.encode_delay_average),
+            ("network", &self.network_delay_average),
+            ("decoder", &self.decode_delay_average),
+            ("decoder_queue", &self.decoder_queue_delay_average),
+            ("client_compositor", &self.client_compositor_average),
+            ("vsync_queue", &self.vsync_queue_delay_average),
+        ] {
+            writer.sample(
+                "alvr_pipeline_latency_seconds",
+                &[("client", client), ("stage", stage)],
+                average.get_average().as_secs_f64(),
+            );
+        }
+
+        writer.metric(
+            "alvr_vf_rtt_seconds",
+            MetricKind::Gauge,
+            "Latest video frame round-trip time",
+            &[("client", client)],
+            self.last_rtt.as_secs_f64(),
+        );
+        writer.metric(
+            "alvr_interarrival_jitter_seconds",
+            MetricKind::Gauge,
+            "Latest interarrival jitter of the video shards, measured by the client",
+            &[("client", client)],
+            self.last_interarrival_jitter_s as f64,
+        );
+        writer.metric(
+            "alvr_frame_jitter_seconds",
+            MetricKind::Gauge,
+            "Standard deviation of the video frame interarrival time",
+            &[("client", client)],
+            self.frame_interarrival_average.get_std() as f64,
+        );
+        writer.metric(
+            "alvr_owd_gradient_seconds",
+            MetricKind::Gauge,
+            "Latest Kalman-filtered one-way delay gradient",
+            &[("client", client)],
+            self.last_filtered_ow_delay_s as f64,
+        );
+
+        writer.metric(
+            "alvr_shards_sent_total",
+            MetricKind::Counter,
+            "Video shards sent, as accounted by the shard loss reports",
+            &[("client", client)],
+            self.shards_sent_total as f64,
+        );
+        writer.metric(
+            "alvr_shards_lost_total",
+            MetricKind::Counter,
+            "Video shards lost in the network",
+            &[("client", client)],
+            self.shards_lost_total as f64,
+        );
+        writer.metric(
+            "alvr_shards_duplicated_total",
+            MetricKind::Counter,
+            "Duplicated video shards received by the client",
+            &[("client", client)],
+            self.shards_duplicated_total as f64,
+        );
+        writer.metric(
+            "alvr_shard_loss_rate",
+            MetricKind::Gauge,
+            "Average fraction of video shards lost in the network",
+            &[("client", client)],
+            self.shard_loss_rate_average.get_average() as f64,
+        );
+        writer.metric(
+            "alvr_frames_skipped_total",
+            MetricKind::Counter,
+            "Video frames never received by the client",
+            &[("client", client)],
+            self.packets_skipped_total as f64,
+        );
+        writer.metric(
+            "alvr_frames_dropped_total",
+            MetricKind::Counter,
+            "Video frames received but not displayed by the client",
+            &[("client", client)],
+            self.packets_dropped_total as f64,
+        );
+
+        writer.metric(
+            "alvr_instant_network_throughput_bits_per_second",
+            MetricKind::Gauge,
+            "Latest instantaneous network throughput",
+            &[("client", client)],
+            self.last_instant_throughput_bps as f64,
+        );
+        writer.metric(
+            "alvr_peak_network_throughput_bits_per_second",
+            MetricKind::Gauge,
+            "Latest peak network throughput",
+            &[("client", client)],
+            self.last_peak_throughput_bps as f64,
+        );
+        writer.metric(
+            "alvr_requested_bitrate_bits_per_second",
+            MetricKind::Gauge,
+            "Bitrate requested by the bitrate controller",
+            &[("client", client)],
+            self.last_nominal_bitrate_stats.requested_bps as f64,
+        );
+        writer.metric(
+            "alvr_actual_bitrate_bits_per_second",
+            MetricKind::Gauge,
+            "Latest bitrate of the video frames, computed on the network latency",
+            &[("client", client)],
+            self.last_actual_bitrate_bps as f64,
+        );
+
+        writer.family(
+            "alvr_nestvr_decisions_total",
+            MetricKind::Counter,
+            "NeSt-VR adjustment decisions",
+        );
+        for (decision, label) in [
+            (NestVrDecision::Increase, "increase"),
+            (NestVrDecision::Hold, "hold"),
+            (NestVrDecision::DecreaseRtt, "decrease_rtt"),
+            (NestVrDecision::DecreaseLoss, "decrease_loss"),
+            (NestVrDecision::DecreaseNfr, "decrease_nfr"),
+        ] {
+            writer.sample(
+                "alvr_nestvr_decisions_total",
+                &[("client", client), ("decision", label)],
+                self.nestvr_decisions.get(&decision).copied().unwrap_or(0) as f64,
+            );
+        }
+
+        writer.family(
+            "alvr_battery_ratio",
+            MetricKind::Gauge,
+            "Battery level of the tracked devices, from 0 to 1",
+        );
+        for (device_id, battery) in &self.battery_gauges {
+            let device = DEVICE_ID_TO_PATH
+                .get(device_id)
+                .copied()
+                .unwrap_or("unknown");
+            writer.sample(
+                "alvr_battery_ratio",
+                &[("client", client), ("device", device)],
+                battery.gauge_value as f64,
+            );
+        }
+        writer.family(
+            "alvr_battery_plugged",
+            MetricKind::Gauge,
+            "1 if the tracked device is charging",
+        );
+        for (device_id, battery) in &self.battery_gauges {
+            let device = DEVICE_ID_TO_PATH
+                .get(device_id)
+                .copied()
+                .unwrap_or("unknown");
+            writer.sample(
+                "alvr_battery_plugged",
+                &[("client", client), ("device", device)],
+                if battery.is_plugged { 1.0 } else { 0.0 },
+            );
+        }
+    }
 }
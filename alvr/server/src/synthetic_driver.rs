@@ -0,0 +1,360 @@
+// Rust replacement of the C++ driver bindings, used by the headless server. It exposes the same
+// items as the bindgen output, but instead of rendering and encoding with SteamVR and a GPU, a
+// synthetic encoder generates NAL-like payloads whose sizes follow the bitrate and framerate
+// returned by the bitrate manager. The callbacks are registered by HmdDriverFactory as with the
+// real driver.
+
+use crate::SERVER_DATA_MANAGER;
+use alvr_common::{info, parking_lot::Mutex, RelaxedAtomic};
+use alvr_session::CodecType;
+use std::{
+    ffi::{c_char, c_void},
+    ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const INITIAL_BITRATE_BPS: u64 = 30_000_000;
+const INITIAL_FRAMERATE: f32 = 72.0;
+const IDR_INTERVAL: Duration = Duration::from_secs(2);
+// IDR frames are much larger than P frames for the same quality
+const IDR_SIZE_MULTIPLIER: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiFov {
+    pub left: f32,
+    pub right: f32,
+    pub up: f32,
+    pub down: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiQuat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiHandSkeleton {
+    pub jointPositions: [[f32; 3]; 26],
+    pub jointRotations: [FfiQuat; 26],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiDeviceMotion {
+    pub deviceID: u64,
+    pub orientation: FfiQuat,
+    pub position: [f32; 3],
+    pub linearVelocity: [f32; 3],
+    pub angularVelocity: [f32; 3],
+}
+
+pub type FfiOpenvrPropertyType = u32;
+pub const FfiOpenvrPropertyType_Bool: FfiOpenvrPropertyType = 0;
+pub const FfiOpenvrPropertyType_Float: FfiOpenvrPropertyType = 1;
+pub const FfiOpenvrPropertyType_Int32: FfiOpenvrPropertyType = 2;
+pub const FfiOpenvrPropertyType_Uint64: FfiOpenvrPropertyType = 3;
+pub const FfiOpenvrPropertyType_Vector3: FfiOpenvrPropertyType = 4;
+pub const FfiOpenvrPropertyType_Double: FfiOpenvrPropertyType = 5;
+pub const FfiOpenvrPropertyType_String: FfiOpenvrPropertyType = 6;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union FfiOpenvrPropertyValue {
+    pub bool_: u32,
+    pub float_: f32,
+    pub int32: i32,
+    pub uint64: u64,
+    pub vector3: [f32; 3],
+    pub double_: f64,
+    pub string: [c_char; 256],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FfiOpenvrProperty {
+    pub key: u32,
+    pub type_: FfiOpenvrPropertyType,
+    pub value: FfiOpenvrPropertyValue,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiViewsConfig {
+    pub fov: [FfiFov; 2],
+    pub ipd_m: f32,
+}
+
+pub type FfiButtonType = u32;
+pub const FfiButtonType_BUTTON_TYPE_BINARY: FfiButtonType = 0;
+pub const FfiButtonType_BUTTON_TYPE_SCALAR: FfiButtonType = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union FfiButtonValue__bindgen_ty_1 {
+    pub binary: u32,
+    pub scalar: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FfiButtonValue {
+    pub type_: FfiButtonType,
+    pub __bindgen_anon_1: FfiButtonValue__bindgen_ty_1,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FfiDynamicEncoderParams {
+    pub updated: u32,
+    pub bitrate_bps: u64,
+    pub framerate: f32,
+}
+
+// The shaders are not used, the pointers are stored only to mirror the C++ interface
+pub static mut FRAME_RENDER_VS_CSO_PTR: *const u8 = ptr::null();
+pub static mut FRAME_RENDER_VS_CSO_LEN: u32 = 0;
+pub static mut FRAME_RENDER_PS_CSO_PTR: *const u8 = ptr::null();
+pub static mut FRAME_RENDER_PS_CSO_LEN: u32 = 0;
+pub static mut QUAD_SHADER_CSO_PTR: *const u8 = ptr::null();
+pub static mut QUAD_SHADER_CSO_LEN: u32 = 0;
+pub static mut COMPRESS_AXIS_ALIGNED_CSO_PTR: *const u8 = ptr::null();
+pub static mut COMPRESS_AXIS_ALIGNED_CSO_LEN: u32 = 0;
+pub static mut COLOR_CORRECTION_CSO_PTR: *const u8 = ptr::null();
+pub static mut COLOR_CORRECTION_CSO_LEN: u32 = 0;
+pub static mut QUAD_SHADER_COMP_SPV_PTR: *const u8 = ptr::null();
+pub static mut QUAD_SHADER_COMP_SPV_LEN: u32 = 0;
+pub static mut COLOR_SHADER_COMP_SPV_PTR: *const u8 = ptr::null();
+pub static mut COLOR_SHADER_COMP_SPV_LEN: u32 = 0;
+pub static mut FFR_SHADER_COMP_SPV_PTR: *const u8 = ptr::null();
+pub static mut FFR_SHADER_COMP_SPV_LEN: u32 = 0;
+pub static mut RGBTOYUV420_SHADER_COMP_SPV_PTR: *const u8 = ptr::null();
+pub static mut RGBTOYUV420_SHADER_COMP_SPV_LEN: u32 = 0;
+
+pub static mut g_sessionPath: *const c_char = ptr::null();
+pub static mut g_driverRootDir: *const c_char = ptr::null();
+
+pub static mut LogError: Option<unsafe extern "C" fn(stringPtr: *const c_char)> = None;
+pub static mut LogWarn: Option<unsafe extern "C" fn(stringPtr: *const c_char)> = None;
+pub static mut LogInfo: Option<unsafe extern "C" fn(stringPtr: *const c_char)> = None;
+pub static mut LogDebug: Option<unsafe extern "C" fn(stringPtr: *const c_char)> = None;
+pub static mut LogPeriodically: Option<
+    unsafe extern "C" fn(tag: *const c_char, stringPtr: *const c_char),
+> = None;
+pub static mut DriverReadyIdle: Option<unsafe extern "C" fn(setDefaultChaprone: bool)> = None;
+pub static mut SetVideoConfigNals: Option<
+    unsafe extern "C" fn(configBuffer: *const u8, len: i32, codec: i32),
+> = None;
+pub static mut VideoSend: Option<
+    unsafe extern "C" fn(targetTimestampNs: u64, buf: *mut u8, len: i32, isIdr: bool),
+> = None;
+pub static mut HapticsSend: Option<
+    unsafe extern "C" fn(path: u64, duration_s: f32, frequency: f32, amplitude: f32),
+> = None;
+pub static mut ShutdownRuntime: Option<unsafe extern "C" fn()> = None;
+pub static mut PathStringToHash: Option<unsafe extern "C" fn(path: *const c_char) -> u64> = None;
+pub static mut ReportPresent: Option<unsafe extern "C" fn(timestamp_ns: u64, offset_ns: u64)> =
+    None;
+pub static mut ReportComposed: Option<unsafe extern "C" fn(timestamp_ns: u64, offset_ns: u64)> =
+    None;
+pub static mut GetDynamicEncoderParams: Option<unsafe extern "C" fn() -> FfiDynamicEncoderParams> =
+    None;
+pub static mut GetSerialNumber: Option<
+    unsafe extern "C" fn(deviceID: u64, outString: *mut c_char) -> u64,
+> = None;
+pub static mut SetOpenvrProps: Option<unsafe extern "C" fn(deviceID: u64)> = None;
+pub static mut RegisterButtons: Option<unsafe extern "C" fn(deviceID: u64)> = None;
+pub static mut WaitForVSync: Option<unsafe extern "C" fn()> = None;
+
+pub static IS_SHUTDOWN_REQUESTED: RelaxedAtomic = RelaxedAtomic::new(false);
+
+static IDR_REQUESTED: RelaxedAtomic = RelaxedAtomic::new(true);
+// Target timestamp of the last tracking sample, 0 if none has been received yet
+static LAST_TRACKING_TIMESTAMP_NS: AtomicU64 = AtomicU64::new(0);
+// The encoder thread is not joined on deinitialization, because it could be waiting for the
+// session lock held by the caller. Each thread gets its own running flag instead.
+static ENCODER_RUNNING: Mutex<Option<Arc<RelaxedAtomic>>> = Mutex::new(None);
+
+// Annex B start code and NAL header of the config, IDR and P frames for each codec. AV1 uses
+// temporal delimiter OBUs instead.
+fn nal_prefixes(codec: CodecType) -> (&'static [u8], &'static [u8], &'static [u8]) {
+    match codec {
+        CodecType::H264 => (
+            &[0, 0, 0, 1, 0x67, 0x64, 0, 0x1f, 0, 0, 0, 1, 0x68, 0xee],
+            &[0, 0, 0, 1, 0x65],
+            &[0, 0, 0, 1, 0x41],
+        ),
+        CodecType::Hevc => (
+            &[
+                0, 0, 0, 1, 0x40, 0x01, 0x0c, 0, 0, 0, 1, 0x42, 0x01, 0x01, 0, 0, 0, 1, 0x44, 0x01,
+                0xc1,
+            ],
+            &[0, 0, 0, 1, 0x26, 0x01],
+            &[0, 0, 0, 1, 0x02, 0x01],
+        ),
+        CodecType::AV1 => (&[0x0a, 0x0b, 0, 0, 0], &[0x12, 0x00], &[0x12, 0x00]),
+    }
+}
+
+fn codec_index(codec: CodecType) -> i32 {
+    match codec {
+        CodecType::H264 => 0,
+        CodecType::Hevc => 1,
+        CodecType::AV1 => 2,
+    }
+}
+
+fn synthetic_frame(prefix: &[u8], size: usize) -> Vec<u8> {
+    let mut frame = prefix.to_vec();
+    // Filler without zero bytes, to not emulate start codes
+    frame.resize(usize::max(size, prefix.len()), 0xaa);
+
+    frame
+}
+
+fn encoder_loop(running: Arc<RelaxedAtomic>) {
+    let codec = SERVER_DATA_MANAGER.read().settings().video.preferred_codec;
+    let (config_prefix, idr_prefix, p_prefix) = nal_prefixes(codec);
+
+    unsafe {
+        if let Some(set_video_config_nals) = SetVideoConfigNals {
+            set_video_config_nals(
+                config_prefix.as_ptr(),
+                config_prefix.len() as _,
+                codec_index(codec),
+            );
+        }
+    }
+
+    let mut bitrate_bps = INITIAL_BITRATE_BPS;
+    let mut framerate = INITIAL_FRAMERATE;
+    let mut last_idr_instant = Instant::now();
+    let mut next_frame_instant = Instant::now();
+    let mut last_timestamp_ns = 0;
+
+    while running.value() {
+        let params = unsafe { GetDynamicEncoderParams.map(|f| f()) };
+        if let Some(params) = params.filter(|p| p.updated != 0) {
+            bitrate_bps = params.bitrate_bps;
+            framerate = f32::max(params.framerate, 1.0);
+        }
+
+        let frame_interval = Duration::from_secs_f32(1.0 / framerate);
+        next_frame_instant += frame_interval;
+        let now = Instant::now();
+        if next_frame_instant > now {
+            thread::sleep(next_frame_instant - now);
+        } else {
+            // Skip the missed frames instead of sending them in a burst
+            next_frame_instant = now;
+        }
+
+        // Frames are rendered with the latest available pose, as the real driver does
+        let timestamp_ns = LAST_TRACKING_TIMESTAMP_NS.load(Ordering::Relaxed);
+        if timestamp_ns == 0 || timestamp_ns == last_timestamp_ns {
+            continue;
+        }
+        last_timestamp_ns = timestamp_ns;
+
+        unsafe {
+            if let Some(report_present) = ReportPresent {
+                report_present(timestamp_ns, 0);
+            }
+            if let Some(report_composed) = ReportComposed {
+                report_composed(timestamp_ns, 0);
+            }
+        }
+
+        let is_idr = IDR_REQUESTED.value() || last_idr_instant.elapsed() > IDR_INTERVAL;
+        let mut size = bitrate_bps as f32 / 8.0 / framerate;
+        if is_idr {
+            IDR_REQUESTED.set(false);
+            last_idr_instant = Instant::now();
+            size *= IDR_SIZE_MULTIPLIER;
+        }
+
+        let mut frame = synthetic_frame(if is_idr { idr_prefix } else { p_prefix }, size as _);
+
+        unsafe {
+            if let Some(video_send) = VideoSend {
+                video_send(timestamp_ns, frame.as_mut_ptr(), frame.len() as _, is_idr);
+            }
+        }
+    }
+}
+
+pub unsafe fn CppEntryPoint(_pInterfaceName: *const c_char, _pReturnCode: *mut i32) -> *mut c_void {
+    info!("Starting headless driver with synthetic video");
+
+    if let Some(driver_ready_idle) = DriverReadyIdle {
+        driver_ready_idle(false);
+    }
+
+    ptr::null_mut()
+}
+
+pub unsafe fn InitializeStreaming() {
+    IDR_REQUESTED.set(true);
+
+    let running = Arc::new(RelaxedAtomic::new(true));
+    *ENCODER_RUNNING.lock() = Some(Arc::clone(&running));
+
+    thread::spawn(move || encoder_loop(running));
+}
+
+pub unsafe fn DeinitializeStreaming() {
+    if let Some(running) = ENCODER_RUNNING.lock().take() {
+        running.set(false);
+    }
+    LAST_TRACKING_TIMESTAMP_NS.store(0, Ordering::Relaxed);
+}
+
+pub unsafe fn RequestIDR() {
+    IDR_REQUESTED.set(true);
+}
+
+pub unsafe fn VideoErrorReportReceive() {
+    IDR_REQUESTED.set(true);
+}
+
+pub unsafe fn SetTracking(
+    targetTimestampNs: u64,
+    _controllerPoseTimeOffsetS: f32,
+    _deviceMotions: *const FfiDeviceMotion,
+    _motionsCount: i32,
+    _leftHand: *const FfiHandSkeleton,
+    _rightHand: *const FfiHandSkeleton,
+    _controllersTracked: u32,
+) {
+    LAST_TRACKING_TIMESTAMP_NS.store(targetTimestampNs, Ordering::Relaxed);
+}
+
+pub unsafe fn ShutdownSteamvr() {
+    IS_SHUTDOWN_REQUESTED.set(true);
+}
+
+pub unsafe fn SetOpenvrProperty(_deviceID: u64, _prop: FfiOpenvrProperty) {}
+
+pub unsafe fn RegisterButton(_buttonID: u64) {}
+
+pub unsafe fn SetViewsConfig(_config: FfiViewsConfig) {}
+
+pub unsafe fn SetBattery(_deviceID: u64, _gauge_value: f32, _is_plugged: bool) {}
+
+pub unsafe fn SetButton(_buttonID: u64, _value: FfiButtonValue) {}
+
+pub unsafe fn InitOpenvrClient() {}
+
+pub unsafe fn ShutdownOpenvrClient() {}
+
+pub unsafe fn SetChaperoneArea(_areaWidth: f32, _areaHeight: f32) {}
@@ -1,23 +1,31 @@
 use crate::{
-    bindings::FfiButtonValue, connection::CLIENTS_TO_BE_REMOVED, DECODER_CONFIG, FILESYSTEM_LAYOUT,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER, VIDEO_RECORDING_FILE,
+    bindings::FfiButtonValue, connection::CLIENTS_TO_BE_REMOVED, metrics, DECODER_CONFIG,
+    FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_MIRROR_SENDER,
+    VIDEO_RECORDING_FILE,
 };
 use alvr_common::{
     anyhow::{self, Result},
     error, info, log, warn, ConnectionState,
 };
 use alvr_events::{ButtonEvent, Event, EventType};
-use alvr_packets::{ButtonValue, ClientListAction, ServerRequest};
+use alvr_packets::{ButtonValue, ClientListAction, ServerRequest, WEB_API_TOKEN_HEADER};
+use alvr_session::SessionConfig;
 use bytes::Buf;
 use futures::SinkExt;
 use headers::HeaderMapExt;
 use hyper::{
-    header::{self, HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE},
-    service, Body, Request, Response, StatusCode,
+    header::{
+        self, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+        ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE, ORIGIN, VARY,
+    },
+    service, Body, Method, Request, Response, StatusCode,
 };
 use serde::de::DeserializeOwned;
 use serde_json as json;
-use std::{net::SocketAddr, thread};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    thread,
+};
 use tokio::sync::broadcast::{self, error::RecvError};
 use tokio_tungstenite::{tungstenite::protocol, WebSocketStream};
 use tokio_util::codec::{BytesCodec, FramedRead};
@@ -28,6 +36,35 @@ fn reply(code: StatusCode) -> Result<Response<Body>> {
     Ok(Response::builder().status(code).body(Body::empty())?)
 }
 
+// Constant time comparison. An empty expected token never matches, this way requests are rejected
+// if the token has not been generated
+fn is_valid_token(token: &[u8], expected_token: &str) -> bool {
+    !expected_token.is_empty()
+        && token.len() == expected_token.len()
+        && token
+            .iter()
+            .zip(expected_token.as_bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn is_authorized(request: &Request<Body>) -> bool {
+    let Some(token) = request.headers().get(WEB_API_TOKEN_HEADER) else {
+        return false;
+    };
+
+    is_valid_token(
+        token.as_bytes(),
+        &SERVER_DATA_MANAGER.read().session().web_api_token,
+    )
+}
+
+// The token is removed from the sessions sent to the web clients, the sessions they send back must
+// not overwrite it
+fn keep_web_api_token(session: &mut SessionConfig, current_session: &SessionConfig) {
+    session.web_api_token = current_session.web_api_token.clone();
+}
+
 async fn from_request_body<T: DeserializeOwned>(request: Request<Body>) -> Result<T> {
     Ok(json::from_reader(
         hyper::body::aggregate(request).await?.reader(),
@@ -91,10 +128,16 @@ async fn http_api(
     request: Request<Body>,
     events_sender: broadcast::Sender<Event>,
 ) -> Result<Response<Body>> {
+    let authorized = is_authorized(&request);
+    let origin = request.headers().get(ORIGIN).cloned();
+
     let mut response = match request.uri().path() {
+        // CORS preflight
+        _ if request.method() == Method::OPTIONS => reply(StatusCode::NO_CONTENT)?,
         // New unified requests
-        "/api/dashboard-request" => {
-            if let Ok(request) = from_request_body::<ServerRequest>(request).await {
+        "/api/dashboard-request" => match from_request_body::<ServerRequest>(request).await {
+            Ok(request) if request.is_mutating() && !authorized => reply(StatusCode::UNAUTHORIZED)?,
+            Ok(request) => {
                 match request {
                     ServerRequest::Log(event) => {
                         let level = event.severity.into_log_level();
@@ -105,8 +148,10 @@ async fn http_api(
                             SERVER_DATA_MANAGER.read().session().clone(),
                         )));
                     }
-                    ServerRequest::UpdateSession(session) => {
-                        *SERVER_DATA_MANAGER.write().session_mut() = *session
+                    ServerRequest::UpdateSession(mut session) => {
+                        let mut data_manager = SERVER_DATA_MANAGER.write();
+                        keep_web_api_token(&mut session, data_manager.session());
+                        *data_manager.session_mut() = *session;
                     }
                     ServerRequest::SetValues(descs) => {
                         SERVER_DATA_MANAGER.write().set_values(descs).ok();
@@ -182,12 +227,16 @@ async fn http_api(
                 }
 
                 reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
             }
-        }
+            Err(_) => reply(StatusCode::BAD_REQUEST)?,
+        },
         "/api/events" => {
-            websocket(request, events_sender, |e| {
+            websocket(request, events_sender, |mut e| {
+                // Already cleared by send_event(), in case an event is built elsewhere
+                if let EventType::Session(session) = &mut e.event_type {
+                    session.web_api_token.clear();
+                }
+
                 protocol::Message::Text(json::to_string(&e).unwrap())
             })
             .await?
@@ -215,6 +264,7 @@ async fn http_api(
 
             res
         }
+        "/api/set-buttons" if !authorized => reply(StatusCode::UNAUTHORIZED)?,
         "/api/set-buttons" => {
             let buttons = from_request_body::<Vec<ButtonEvent>>(request).await?;
 
@@ -250,6 +300,9 @@ async fn http_api(
                 .body(latency.to_string().into())?
         }
         "/api/ping" => reply(StatusCode::OK)?,
+        "/metrics" => Response::builder()
+            .header(CONTENT_TYPE, metrics::METRICS_CONTENT_TYPE)
+            .body(metrics::encode_server_metrics().into())?,
         other_uri => {
             if other_uri.contains("..") {
                 // Attempted tree traversal
@@ -287,19 +340,53 @@ async fn http_api(
         CACHE_CONTROL,
         HeaderValue::from_str("no-cache, no-store, must-revalidate")?,
     );
+
+    // Only the origins in the allow-list can read the responses from a browser. The dashboard is
+    // served by this web server, so it's not a cross-origin client
+    if let Some(origin) = origin {
+        let allowed = SERVER_DATA_MANAGER
+            .read()
+            .settings()
+            .connection
+            .web_server_cors_allowed_origins
+            .iter()
+            .any(|allowed_origin| allowed_origin.as_bytes() == origin.as_bytes());
+
+        if allowed {
+            let headers = response.headers_mut();
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static("GET, POST, OPTIONS"),
+            );
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_static("content-type, x-alvr-api-token"),
+            );
+        }
+    }
     response
         .headers_mut()
-        .insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        .insert(VARY, HeaderValue::from_static("Origin"));
 
     Ok(response)
 }
 
 pub async fn web_server(events_sender: broadcast::Sender<Event>) -> Result<()> {
-    let web_server_port = SERVER_DATA_MANAGER
-        .read()
-        .settings()
-        .connection
-        .web_server_port;
+    let (web_server_port, localhost_only) = {
+        let data_manager = SERVER_DATA_MANAGER.read();
+        let connection = &data_manager.settings().connection;
+
+        (
+            connection.web_server_port,
+            connection.web_server_localhost_only,
+        )
+    };
+    let address = if localhost_only {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    };
 
     let service = service::make_service_fn(|_| {
         let events_sender = events_sender.clone();
@@ -318,10 +405,48 @@ pub async fn web_server(events_sender: broadcast::Sender<Event>) -> Result<()> {
         }
     });
 
-    Ok(hyper::Server::bind(&SocketAddr::new(
-        "0.0.0.0".parse().unwrap(),
-        web_server_port,
-    ))
-    .serve(service)
-    .await?)
+    Ok(
+        hyper::Server::bind(&SocketAddr::new(address, web_server_port))
+            .serve(service)
+            .await?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_token() {
+        assert!(is_valid_token(b"0123abcd", "0123abcd"));
+        assert!(!is_valid_token(b"0123abce", "0123abcd"));
+        assert!(!is_valid_token(b"0123abc", "0123abcd"));
+        assert!(!is_valid_token(b"0123abcd0", "0123abcd"));
+        assert!(!is_valid_token(b"", "0123abcd"));
+    }
+
+    #[test]
+    fn test_empty_token_rejected() {
+        assert!(!is_valid_token(b"", ""));
+    }
+
+    #[test]
+    fn test_update_session_keeps_token() {
+        let current_session = SessionConfig {
+            web_api_token: "0123abcd".into(),
+            ..Default::default()
+        };
+
+        // Sessions received from the web clients have the token cleared
+        let mut session = SessionConfig::default();
+        keep_web_api_token(&mut session, &current_session);
+        assert_eq!(session.web_api_token, "0123abcd");
+
+        let mut session = SessionConfig {
+            web_api_token: "ffffffff".into(),
+            ..Default::default()
+        };
+        keep_web_api_token(&mut session, &current_session);
+        assert_eq!(session.web_api_token, "0123abcd");
+    }
 }
@@ -71,7 +71,14 @@ impl ServerDataManager {
     pub fn new(session_path: &Path) -> Self {
         let config_dir = session_path.parent().unwrap();
         fs::create_dir_all(config_dir).ok();
-        let session_desc = Self::load_session(session_path, config_dir);
+        let mut session_desc = Self::load_session(session_path, config_dir);
+
+        if session_desc.web_api_token.is_empty() {
+            session_desc.web_api_token = (0..16)
+                .map(|_| format!("{:02x}", rand::random::<u8>()))
+                .collect();
+            save_session(&session_desc, session_path).ok();
+        }
 
         Self {
             session: session_desc.clone(),
@@ -204,6 +211,7 @@ impl ServerDataManager {
                         trusted,
                         connection_state: ConnectionState::Disconnected,
                         cabled: false,
+                        public_key: None,
                     };
                     new_entry.insert(client_connection_desc);
 
@@ -252,6 +260,15 @@ impl ServerDataManager {
                     if entry.get().connection_state != state {
                         entry.get_mut().connection_state = state;
 
+                        updated = true;
+                    }
+                }
+            }
+            ClientListAction::SetPublicKey(key) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    if entry.get().public_key != Some(key) {
+                        entry.get_mut().public_key = Some(key);
+
                         updated = true;
                     }
                 }
@@ -110,6 +110,10 @@ pub struct ClientConnectionConfig {
     pub trusted: bool,
     pub connection_state: ConnectionState,
     pub cabled: bool,
+    // Ed25519 identity key of the client. It is announced by untrusted clients and pinned once the
+    // client is trusted
+    #[serde(default)]
+    pub public_key: Option<[u8; 32]>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -119,6 +123,10 @@ pub struct SessionConfig {
     pub openvr_config: OpenvrConfig,
     // The hashmap key is the hostname
     pub client_connections: HashMap<String, ClientConnectionConfig>,
+    // Required by the web server for requests that change the server state. It is generated on the
+    // first start and it's never sent with session events
+    #[serde(default)]
+    pub web_api_token: String,
     pub session_settings: SessionSettings,
 }
 
@@ -144,6 +152,7 @@ impl Default for SessionConfig {
                 ..<_>::default()
             },
             client_connections: HashMap::new(),
+            web_api_token: "".into(),
             session_settings: settings::session_settings_default(),
         }
     }
@@ -341,6 +341,93 @@ pub enum BitrateMode {
         #[schema(flag = "real-time")]
         #[schema(gui(slider(min = 0.1, max = 5.0, logarithmic)))]
         rtt_thresh_scaling_factor: f32,
+
+        #[schema(strings(
+            display_name = "Packet loss rate threshold (lambda)",
+            help = "Decrease the bitrate when the average packet loss rate, frame skip rate or network feedback loss rate exceeds this value"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.001, max = 0.5, logarithmic)))]
+        loss_rate_thresh: Switch<f32>,
+
+        #[schema(strings(
+            display_name = "Use probe-based capacity estimate",
+            help = "Bound the bitrate using the capacity measured with probe trains instead of the peak network throughput. Requires capacity probing"
+        ))]
+        #[schema(flag = "real-time")]
+        use_probe_capacity: bool,
+    },
+    #[schema(collapsible)]
+    DelayBased {
+        #[schema(strings(display_name = "Adjustment period"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.05, max = 5.0, logarithmic)), suffix = "s")]
+        update_interval_s: f32,
+
+        #[schema(strings(display_name = "Maximum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)), suffix = "Mbps")]
+        max_bitrate_mbps: Switch<f32>,
+        #[schema(strings(display_name = "Minimum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)), suffix = "Mbps")]
+        min_bitrate_mbps: Switch<f32>,
+        #[schema(strings(display_name = "Initial bitrate"))]
+        #[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)), suffix = "Mbps")]
+        initial_bitrate_mbps: f32,
+
+        #[schema(strings(
+            display_name = "Initial overuse threshold (gamma_0)",
+            help = "Initial value of the adaptive threshold the filtered one-way delay gradient is compared against"
+        ))]
+        #[schema(gui(slider(min = 1.0, max = 100.0, logarithmic)), suffix = "ms")]
+        initial_threshold_ms: f32,
+        #[schema(strings(display_name = "Minimum overuse threshold"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.1, max = 100.0, logarithmic)), suffix = "ms")]
+        min_threshold_ms: f32,
+        #[schema(strings(display_name = "Maximum overuse threshold"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 10.0, max = 1000.0, logarithmic)), suffix = "ms")]
+        max_threshold_ms: f32,
+
+        #[schema(strings(
+            display_name = "Threshold increase gain (k_u)",
+            help = "Adaptation gain used when the delay gradient is above the threshold"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.0001, max = 0.1, logarithmic)))]
+        threshold_gain_up: f32,
+        #[schema(strings(
+            display_name = "Threshold decrease gain (k_d)",
+            help = "Adaptation gain used when the delay gradient is below the threshold"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.00001, max = 0.01, logarithmic)))]
+        threshold_gain_down: f32,
+
+        #[schema(strings(
+            display_name = "Overuse time threshold",
+            help = "Time the delay gradient must stay above the threshold before signaling overuse"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.0, max = 100.0)), suffix = "ms")]
+        overuse_time_threshold_ms: f32,
+
+        #[schema(strings(
+            display_name = "Rate increase factor (eta)",
+            help = "Multiplicative bitrate increase per second while in the increase state"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1.0, max = 2.0, step = 0.01)))]
+        increase_factor: f32,
+        #[schema(strings(
+            display_name = "Rate decrease factor (beta)",
+            help = "Fraction of the received throughput used as bitrate on overuse"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.5, max = 1.0, step = 0.01)))]
+        decrease_factor: f32,
     },
 }
 
@@ -959,6 +1046,15 @@ pub enum SocketProtocol {
     Udp,
     #[schema(strings(display_name = "TCP"))]
     Tcp,
+    #[schema(strings(display_name = "QUIC"))]
+    Quic,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(gui = "button_group")]
+pub enum NetworkFeedbackTransport {
+    ControlSocket,
+    StreamSocket,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -981,7 +1077,8 @@ pub enum SocketBufferSize {
 pub struct ConnectionConfig {
     #[schema(strings(
         help = r#"UDP: Faster, but less stable than TCP. Try this if your network is well optimized and free of interference.
-TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP."#
+TCP: Slower than UDP, but more stable. Pick this if you experience video or audio stutters with UDP.
+QUIC: Encrypted. Video is sent as unreliable datagrams, the other streams are reliable. The connection survives address changes of the streamer."#
     ))]
     pub stream_protocol: SocketProtocol,
 
@@ -989,6 +1086,18 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
 
     pub stream_port: u16,
     pub web_server_port: u16,
+
+    #[schema(strings(
+        help = "Accept web server connections only from this PC. The web dashboard and the API will not be reachable from other devices. Requires a restart."
+    ))]
+    pub web_server_localhost_only: bool,
+
+    #[schema(strings(
+        display_name = "Web server CORS allowed origins",
+        help = "Origins of the web pages allowed to call the web server API, for example http://localhost:3000. The dashboard doesn't need any."
+    ))]
+    pub web_server_cors_allowed_origins: Vec<String>,
+
     pub osc_local_port: u16,
 
     pub dscp: Option<DscpTos>,
@@ -1044,8 +1153,140 @@ For now works only on Windows+Nvidia"#
     #[schema(gui(slider(min = 1024, max = 65507, logarithmic)), suffix = "B")]
     pub packet_size: i32,
 
+    #[schema(strings(
+        display_name = "Stream receive buffer size",
+        help = r#"Memory budget for the packets of each stream being received or waiting to be processed.
+When it's exceeded, the oldest incomplete packet is dropped."#
+    ))]
+    #[schema(gui(slider(min = 1, max = 256, logarithmic)), suffix = "MB")]
+    pub stream_receive_buffer_mb: u64,
+
     #[schema(suffix = " frames")]
     pub statistics_history_size: usize,
+
+    #[schema(strings(
+        display_name = "Video forward error correction",
+        help = r#"Send Reed-Solomon parity shards with each video frame, so that frames can be recovered when some shards are lost.
+Used only with UDP and QUIC."#
+    ))]
+    pub video_fec: Switch<VideoFecConfig>,
+
+    #[schema(strings(
+        display_name = "Video NACK retransmission",
+        help = r#"The client requests the retransmission of lost video shards instead of waiting for a IDR frame.
+Used only with UDP and QUIC."#
+    ))]
+    pub video_nack: Switch<VideoNackConfig>,
+
+    #[schema(strings(
+        help = r#"Encrypt and authenticate each packet of the stream socket with ChaCha20-Poly1305. The keys are exchanged on the control socket for each connection. Replayed packets are dropped.
+Packets are sent one at a time, and 26 bytes of each packet are used by the encryption."#
+    ))]
+    pub stream_encryption: bool,
+
+    #[schema(strings(
+        help = r#"Spread the packets of each video frame over time instead of sending them in a burst. This reduces queue build-up at the access point.
+The peak network throughput is a capacity estimate only when the network is slower than the pacer."#
+    ))]
+    pub video_pacing: Switch<VideoPacingConfig>,
+
+    #[schema(strings(
+        help = r#"Periodically send trains of padding packets on a dedicated stream. The client measures how much the train is spread out on arrival to estimate the network capacity.
+The estimate can be used by the NeSt-VR bitrate mode."#
+    ))]
+    pub capacity_probing: Switch<CapacityProbingConfig>,
+
+    #[schema(strings(
+        help = r#"Send the per-frame network statistics in batches instead of one control packet per received frame. This reduces the uplink traffic at high framerates.
+The bitrate controllers receive the statistics with a delay of up to one batch."#
+    ))]
+    pub network_statistics_batching: Switch<NetworkStatisticsBatchingConfig>,
+
+    #[schema(strings(
+        help = r#"Control socket: the network statistics are sent reliably over TCP, but they can be delayed by head-of-line blocking during congestion, inflating the VF-RTT.
+Stream socket: the network statistics are sent on a dedicated stream. With UDP they are never delayed by retransmissions, lost feedback is reported to the bitrate controllers."#
+    ))]
+    pub network_feedback_transport: NetworkFeedbackTransport,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct AdaptiveFecConfig {
+    #[schema(strings(
+        help = "The overhead ratio is set to the average shard loss rate multiplied by this factor"
+    ))]
+    #[schema(gui(slider(min = 1.0, max = 5.0, step = 0.1)), suffix = "x")]
+    pub loss_rate_multiplier: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub max_overhead_ratio: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct VideoFecConfig {
+    #[schema(strings(
+        help = "Number of parity shards sent per video shard. If adaptive, this is the minimum ratio"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub overhead_ratio: f32,
+
+    #[schema(strings(help = "Adapt the overhead ratio to the measured shard loss rate"))]
+    pub adaptive: Switch<AdaptiveFecConfig>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct VideoNackConfig {
+    #[schema(strings(
+        help = "Missing shards are requested only for frames whose first shard was received within this time"
+    ))]
+    #[schema(gui(slider(min = 5, max = 200, step = 5)), suffix = "ms")]
+    pub deadline_ms: u64,
+
+    #[schema(strings(help = "Minimum time between two requests for the same frame"))]
+    #[schema(gui(slider(min = 1, max = 50)), suffix = "ms")]
+    pub retry_interval_ms: u64,
+
+    #[schema(strings(
+        help = "Number of recently sent frames kept by the streamer for retransmission"
+    ))]
+    #[schema(gui(slider(min = 1, max = 32)), suffix = " frames")]
+    pub max_cached_frames: usize,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct VideoPacingConfig {
+    #[schema(strings(
+        help = "Pacing rate as a multiple of the target bitrate. Frames are sent over about 1/multiplier of the frame interval"
+    ))]
+    #[schema(gui(slider(min = 1.0, max = 10.0, step = 0.1)), suffix = "x")]
+    pub bitrate_multiplier: f32,
+
+    #[schema(strings(help = "Maximum number of shards sent back-to-back"))]
+    #[schema(gui(slider(min = 1, max = 64)), suffix = " shards")]
+    pub burst_shards: usize,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct CapacityProbingConfig {
+    #[schema(strings(help = "Time between two probe trains"))]
+    #[schema(gui(slider(min = 100, max = 10000, step = 100)), suffix = "ms")]
+    pub interval_ms: u64,
+
+    #[schema(strings(
+        help = "Number of padding packets sent back-to-back in each train. 2 corresponds to a packet pair"
+    ))]
+    #[schema(gui(slider(min = 2, max = 100)), suffix = " packets")]
+    pub train_length: usize,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct NetworkStatisticsBatchingConfig {
+    #[schema(strings(help = "Maximum time a frame record is held before the batch is sent"))]
+    #[schema(gui(slider(min = 5, max = 500, step = 5)), suffix = "ms")]
+    pub interval_ms: u64,
+
+    #[schema(strings(help = "The batch is sent as soon as it contains this many frames"))]
+    #[schema(gui(slider(min = 1, max = 120)), suffix = " frames")]
+    pub max_frames: usize,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1166,6 +1407,83 @@ pub struct Patches {
     pub linux_async_reprojection: bool,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub enum PacketLossModel {
+    Random {
+        #[schema(gui(slider(min = 0.0, max = 0.5, step = 0.001)))]
+        probability: f32,
+    },
+    #[schema(strings(
+        help = "Two-state Markov chain that produces bursts of lost packets. The state transition probabilities are evaluated for each packet"
+    ))]
+    GilbertElliott {
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        good_to_bad_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        bad_to_good_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        good_loss_probability: f32,
+        #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.001)))]
+        bad_loss_probability: f32,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct PacketReorderingConfig {
+    #[schema(gui(slider(min = 0.0, max = 0.5, step = 0.001)))]
+    pub probability: f32,
+
+    #[schema(strings(help = "Additional delay of the reordered packets"))]
+    #[schema(gui(slider(min = 0.0, max = 50.0, step = 0.5)), suffix = "ms")]
+    pub extra_delay_ms: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+pub struct BandwidthLimitConfig {
+    #[schema(gui(slider(min = 1.0, max = 1000.0, logarithmic)), suffix = "Mbps")]
+    pub rate_mbps: f32,
+
+    #[schema(strings(help = "Maximum amount of data sent at line rate"))]
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "KB")]
+    pub bucket_size_kb: usize,
+
+    #[schema(strings(
+        help = "Packets that would wait longer than this in the bottleneck queue are dropped"
+    ))]
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "ms")]
+    pub max_queue_delay_ms: u64,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct NetworkImpairmentConfig {
+    #[schema(gui(slider(min = 0.0, max = 500.0, step = 1.0)), suffix = "ms")]
+    pub delay_ms: f32,
+
+    #[schema(strings(help = "Maximum deviation from the delay, uniformly distributed"))]
+    #[schema(gui(slider(min = 0.0, max = 100.0, step = 0.5)), suffix = "ms")]
+    pub jitter_ms: f32,
+
+    pub packet_loss: Switch<PacketLossModel>,
+
+    #[schema(gui(slider(min = 0.0, max = 0.5, step = 0.001)))]
+    pub duplication_probability: f32,
+
+    pub reordering: Switch<PacketReorderingConfig>,
+
+    pub bandwidth_limit: Switch<BandwidthLimitConfig>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct DebugConfig {
+    #[schema(strings(
+        help = r#"Emulate a degraded network on the stream socket, for testing. Each peer impairs the packets it sends, using the settings it had at connection time. The streamer applies changes at runtime.
+Used only with UDP."#
+    ))]
+    #[schema(flag = "real-time")]
+    pub network_impairment: Switch<NetworkImpairmentConfig>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct Settings {
     pub video: VideoConfig,
@@ -1176,6 +1494,7 @@ pub struct Settings {
     pub steamvr_launcher: SteamvrLauncher,
     pub capture: CaptureConfig,
     pub patches: Patches,
+    pub debug: DebugConfig,
     pub open_setup_wizard: bool,
 }
 
@@ -1275,6 +1594,40 @@ pub fn session_settings_default() -> SettingsDefault {
                         nfr_thresh: 0.95,
 
                         rtt_thresh_scaling_factor: 2.0,
+
+                        loss_rate_thresh: SwitchDefault {
+                            enabled: false,
+                            content: 0.05,
+                        },
+
+                        use_probe_capacity: false,
+                    },
+                    DelayBased: BitrateModeDelayBasedDefault {
+                        gui_collapsed: true,
+
+                        update_interval_s: 0.2,
+
+                        max_bitrate_mbps: SwitchDefault {
+                            enabled: true,
+                            content: 100.0,
+                        },
+                        min_bitrate_mbps: SwitchDefault {
+                            enabled: true,
+                            content: 10.0,
+                        },
+                        initial_bitrate_mbps: 30.0,
+
+                        initial_threshold_ms: 12.5,
+                        min_threshold_ms: 6.0,
+                        max_threshold_ms: 600.0,
+
+                        threshold_gain_up: 0.01,
+                        threshold_gain_down: 0.00018,
+
+                        overuse_time_threshold_ms: 10.0,
+
+                        increase_factor: 1.08,
+                        decrease_factor: 0.85,
                     },
                     variant: BitrateModeDefaultVariant::NestVr,
                 },
@@ -1617,6 +1970,12 @@ pub fn session_settings_default() -> SettingsDefault {
                 },
             },
             web_server_port: 8082,
+            web_server_localhost_only: false,
+            web_server_cors_allowed_origins: VectorDefault {
+                gui_collapsed: true,
+                element: "http://localhost".into(),
+                content: vec![],
+            },
             stream_port: 9944,
             osc_local_port: 9942,
             dscp: OptionalDefault {
@@ -1644,7 +2003,54 @@ pub fn session_settings_default() -> SettingsDefault {
             on_connect_script: "".into(),
             on_disconnect_script: "".into(),
             packet_size: 1400,
+            stream_receive_buffer_mb: 32,
             statistics_history_size: 256,
+            video_fec: SwitchDefault {
+                enabled: false,
+                content: VideoFecConfigDefault {
+                    overhead_ratio: 0.1,
+                    adaptive: SwitchDefault {
+                        enabled: true,
+                        content: AdaptiveFecConfigDefault {
+                            loss_rate_multiplier: 2.0,
+                            max_overhead_ratio: 0.5,
+                        },
+                    },
+                },
+            },
+            video_nack: SwitchDefault {
+                enabled: false,
+                content: VideoNackConfigDefault {
+                    deadline_ms: 50,
+                    retry_interval_ms: 10,
+                    max_cached_frames: 8,
+                },
+            },
+            stream_encryption: false,
+            video_pacing: SwitchDefault {
+                enabled: false,
+                content: VideoPacingConfigDefault {
+                    bitrate_multiplier: 2.0,
+                    burst_shards: 4,
+                },
+            },
+            capacity_probing: SwitchDefault {
+                enabled: false,
+                content: CapacityProbingConfigDefault {
+                    interval_ms: 1000,
+                    train_length: 20,
+                },
+            },
+            network_statistics_batching: SwitchDefault {
+                enabled: false,
+                content: NetworkStatisticsBatchingConfigDefault {
+                    interval_ms: 50,
+                    max_frames: 8,
+                },
+            },
+            network_feedback_transport: NetworkFeedbackTransportDefault {
+                variant: NetworkFeedbackTransportDefaultVariant::ControlSocket,
+            },
         },
         logging: LoggingConfigDefault {
             gui_collapsed: false,
@@ -1699,6 +2105,45 @@ pub fn session_settings_default() -> SettingsDefault {
             linux_async_compute: false,
             linux_async_reprojection: false,
         },
+        debug: DebugConfigDefault {
+            gui_collapsed: true,
+            network_impairment: SwitchDefault {
+                enabled: false,
+                content: NetworkImpairmentConfigDefault {
+                    delay_ms: 0.0,
+                    jitter_ms: 0.0,
+                    packet_loss: SwitchDefault {
+                        enabled: true,
+                        content: PacketLossModelDefault {
+                            variant: PacketLossModelDefaultVariant::Random,
+                            Random: PacketLossModelRandomDefault { probability: 0.01 },
+                            GilbertElliott: PacketLossModelGilbertElliottDefault {
+                                good_to_bad_probability: 0.01,
+                                bad_to_good_probability: 0.3,
+                                good_loss_probability: 0.0,
+                                bad_loss_probability: 0.5,
+                            },
+                        },
+                    },
+                    duplication_probability: 0.0,
+                    reordering: SwitchDefault {
+                        enabled: false,
+                        content: PacketReorderingConfigDefault {
+                            probability: 0.01,
+                            extra_delay_ms: 5.0,
+                        },
+                    },
+                    bandwidth_limit: SwitchDefault {
+                        enabled: false,
+                        content: BandwidthLimitConfigDefault {
+                            rate_mbps: 100.0,
+                            bucket_size_kb: 64,
+                            max_queue_delay_ms: 100,
+                        },
+                    },
+                },
+            },
+        },
         open_setup_wizard: alvr_common::is_stable() || alvr_common::is_nightly(),
     }
 }
@@ -0,0 +1,560 @@
+// Authenticated encryption of the shards with ChaCha20-Poly1305. Each shard is sealed in an
+// envelope: [length: u16][sequence: u64][tag: 16B][ciphertext], little endian. The length and the
+// sequence number are authenticated as associated data. The sequence number is the nonce: it's
+// incremented for every shard sent with a key, including retransmissions of the same shard.
+// The keys of each direction are derived from an ephemeral X25519 key exchange done on the control
+// socket.
+
+use super::{SocketReader, SocketWriter};
+use alvr_common::{
+    anyhow::{anyhow, bail, Result},
+    ConResult, SharedClock,
+};
+use alvr_session::SocketProtocol;
+use chacha20poly1305::{
+    aead::{AeadInPlace, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, Tag,
+};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+const LENGTH_SIZE: usize = 2;
+const SEQUENCE_SIZE: usize = 8;
+const TAG_SIZE: usize = 16;
+const ASSOCIATED_DATA_SIZE: usize = LENGTH_SIZE + SEQUENCE_SIZE;
+
+// Bytes added to each shard
+pub const ENCRYPTION_OVERHEAD: usize = ASSOCIATED_DATA_SIZE + TAG_SIZE;
+
+const SERVER_TO_CLIENT_INFO: &[u8] = b"alvr stream server to client";
+const CLIENT_TO_SERVER_INFO: &[u8] = b"alvr stream client to server";
+
+#[derive(Clone)]
+pub struct StreamKeys {
+    send_key: [u8; 32],
+    receive_key: [u8; 32],
+}
+
+pub struct KeyExchange {
+    secret: EphemeralSecret,
+    public_key: PublicKey,
+}
+
+impl KeyExchange {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public_key = PublicKey::from(&secret);
+
+        Self { secret, public_key }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public_key.to_bytes()
+    }
+
+    // The server and the client derive the same pair of keys, swapped
+    pub fn derive_keys(self, peer_public_key: [u8; 32], is_server: bool) -> Result<StreamKeys> {
+        let peer_public_key = PublicKey::from(peer_public_key);
+
+        let shared_secret = self.secret.diffie_hellman(&peer_public_key);
+        if !shared_secret.was_contributory() {
+            bail!("Invalid stream encryption public key");
+        }
+
+        let (server_public_key, client_public_key) = if is_server {
+            (self.public_key, peer_public_key)
+        } else {
+            (peer_public_key, self.public_key)
+        };
+        let mut salt = [0; 64];
+        salt[..32].copy_from_slice(server_public_key.as_bytes());
+        salt[32..].copy_from_slice(client_public_key.as_bytes());
+
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared_secret.as_bytes());
+        let mut server_to_client_key = [0; 32];
+        let mut client_to_server_key = [0; 32];
+        hkdf.expand(SERVER_TO_CLIENT_INFO, &mut server_to_client_key)
+            .map_err(|e| anyhow!("{e}"))?;
+        hkdf.expand(CLIENT_TO_SERVER_INFO, &mut client_to_server_key)
+            .map_err(|e| anyhow!("{e}"))?;
+
+        Ok(if is_server {
+            StreamKeys {
+                send_key: server_to_client_key,
+                receive_key: client_to_server_key,
+            }
+        } else {
+            StreamKeys {
+                send_key: client_to_server_key,
+                receive_key: server_to_client_key,
+            }
+        })
+    }
+}
+
+fn nonce(sequence: u64) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[4..].copy_from_slice(&sequence.to_le_bytes());
+
+    nonce
+}
+
+pub fn encrypt(
+    writer: Box<dyn SocketWriter>,
+    reader: Box<dyn SocketReader>,
+    protocol: &SocketProtocol,
+    clock: SharedClock,
+    keys: StreamKeys,
+) -> (Box<dyn SocketWriter>, Box<dyn SocketReader>) {
+    (
+        Box::new(EncryptedWriter {
+            inner: writer,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&keys.send_key)),
+            next_sequence: 0,
+            envelope: vec![],
+        }),
+        Box::new(EncryptedReader {
+            clock,
+            inner: reader,
+            // The QUIC reader returns whole packets for both datagrams and the reliable stream
+            is_byte_stream: matches!(protocol, SocketProtocol::Tcp),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&keys.receive_key)),
+            envelope: vec![],
+            cursor: 0,
+            is_open: false,
+            rx_instant: None,
+            authentication_failures: 0,
+        }),
+    )
+}
+
+// Batching is not supported, each shard is sealed into the same envelope buffer
+struct EncryptedWriter {
+    inner: Box<dyn SocketWriter>,
+    cipher: ChaCha20Poly1305,
+    next_sequence: u64,
+    envelope: Vec<u8>,
+}
+
+impl EncryptedWriter {
+    fn seal(&mut self, shard: &[u8]) -> Result<()> {
+        let Ok(length) = u16::try_from(ENCRYPTION_OVERHEAD + shard.len()) else {
+            bail!("Shard too big to be encrypted");
+        };
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        self.envelope.clear();
+        self.envelope.extend_from_slice(&length.to_le_bytes());
+        self.envelope.extend_from_slice(&sequence.to_le_bytes());
+        self.envelope.extend_from_slice(&[0; TAG_SIZE]);
+        self.envelope.extend_from_slice(shard);
+
+        let (header, ciphertext) = self.envelope.split_at_mut(ENCRYPTION_OVERHEAD);
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(
+                &nonce(sequence),
+                &header[..ASSOCIATED_DATA_SIZE],
+                ciphertext,
+            )
+            .map_err(|e| anyhow!("Shard encryption failed: {e}"))?;
+        header[ASSOCIATED_DATA_SIZE..].copy_from_slice(&tag);
+
+        Ok(())
+    }
+}
+
+impl SocketWriter for EncryptedWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        self.seal(buffer)?;
+        self.inner.send(&self.envelope)
+    }
+
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        self.seal(buffer)?;
+        self.inner.send_unreliable(&self.envelope)
+    }
+}
+
+// Envelopes are received like shards by the stream socket: the length is peeked, then the
+// envelope is received, possibly over multiple calls on TCP if the socket times out. On packet
+// transports each envelope is exactly one packet. Shards that fail authentication are dropped and
+// counted.
+struct EncryptedReader {
+    clock: SharedClock,
+    inner: Box<dyn SocketReader>,
+    is_byte_stream: bool,
+    cipher: ChaCha20Poly1305,
+    envelope: Vec<u8>,
+    cursor: usize,
+    // The envelope has been authenticated and decrypted in place
+    is_open: bool,
+    rx_instant: Option<Instant>,
+    authentication_failures: u32,
+}
+
+impl EncryptedReader {
+    fn receive_envelope(&mut self) -> ConResult {
+        if self.envelope.is_empty() {
+            let mut length_bytes = [0; LENGTH_SIZE];
+            let packet_length = self.inner.peek(&mut length_bytes)?;
+            if self.is_byte_stream && packet_length < LENGTH_SIZE {
+                return alvr_common::try_again();
+            }
+            let now = self.clock.now();
+            self.rx_instant = Some(
+                self.inner
+                    .peeked_packet_age()
+                    .and_then(|age| now.checked_sub(age))
+                    .unwrap_or(now),
+            );
+
+            let length = u16::from_le_bytes(length_bytes) as usize;
+            self.cursor = 0;
+
+            if self.is_byte_stream {
+                // The length is authenticated only once the envelope is opened. A corrupted length
+                // desynchronizes the stream and the following envelopes fail authentication
+                self.envelope
+                    .resize(usize::max(length, ENCRYPTION_OVERHEAD), 0);
+            } else {
+                // peek() returns the full packet length. The packet is received with a single call
+                // and dropped if the lengths don't match, so that a forged length can't pull the
+                // following packets into the envelope
+                self.envelope.resize(packet_length, 0);
+                let count = match self.inner.recv(&mut self.envelope) {
+                    Ok(count) => count,
+                    Err(e) => {
+                        self.envelope.clear();
+                        return Err(e);
+                    }
+                };
+
+                if count != length || length < ENCRYPTION_OVERHEAD {
+                    self.authentication_failures += 1;
+                    self.envelope.clear();
+
+                    return Ok(());
+                }
+
+                self.cursor = count;
+            }
+        }
+
+        while self.cursor < self.envelope.len() {
+            self.cursor += self.inner.recv(&mut self.envelope[self.cursor..])?;
+        }
+
+        let (header, ciphertext) = self.envelope.split_at_mut(ENCRYPTION_OVERHEAD);
+        let sequence = u64::from_le_bytes(
+            header[LENGTH_SIZE..ASSOCIATED_DATA_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+        let res = self.cipher.decrypt_in_place_detached(
+            &nonce(sequence),
+            &header[..ASSOCIATED_DATA_SIZE],
+            ciphertext,
+            Tag::from_slice(&header[ASSOCIATED_DATA_SIZE..]),
+        );
+
+        if res.is_ok() {
+            self.is_open = true;
+        } else {
+            self.authentication_failures += 1;
+            self.envelope.clear();
+        }
+
+        Ok(())
+    }
+
+    fn next_shard(&mut self) -> ConResult<&[u8]> {
+        while !self.is_open {
+            self.receive_envelope()?;
+        }
+
+        Ok(&self.envelope[ENCRYPTION_OVERHEAD..])
+    }
+}
+
+impl SocketReader for EncryptedReader {
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let shard = self.next_shard()?;
+        let count = usize::min(shard.len(), buffer.len());
+        buffer[..count].copy_from_slice(&shard[..count]);
+
+        self.envelope.clear();
+        self.is_open = false;
+
+        Ok(count)
+    }
+
+    // Returns the full length of the shard, like MSG_TRUNC
+    fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let shard = self.next_shard()?;
+        let count = usize::min(shard.len(), buffer.len());
+        buffer[..count].copy_from_slice(&shard[..count]);
+
+        Ok(shard.len())
+    }
+
+    fn peeked_packet_age(&self) -> Option<Duration> {
+        Some(self.clock.now().saturating_duration_since(self.rx_instant?))
+    }
+
+    fn take_authentication_failures(&mut self) -> u32 {
+        std::mem::take(&mut self.authentication_failures)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::{parking_lot::Mutex, ConnectionError, SimulatedClock};
+    use std::{collections::VecDeque, sync::Arc};
+
+    // In-memory datagram transport
+    #[derive(Clone, Default)]
+    struct Loopback(Arc<Mutex<VecDeque<Vec<u8>>>>);
+
+    impl SocketWriter for Loopback {
+        fn send(&mut self, buffer: &[u8]) -> Result<()> {
+            self.0.lock().push_back(buffer.to_vec());
+
+            Ok(())
+        }
+    }
+
+    impl SocketReader for Loopback {
+        fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let Some(packet) = self.0.lock().pop_front() else {
+                return alvr_common::try_again();
+            };
+            let count = usize::min(packet.len(), buffer.len());
+            buffer[..count].copy_from_slice(&packet[..count]);
+
+            Ok(count)
+        }
+
+        fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let queue = self.0.lock();
+            let Some(packet) = queue.front() else {
+                return alvr_common::try_again();
+            };
+            let count = usize::min(packet.len(), buffer.len());
+            buffer[..count].copy_from_slice(&packet[..count]);
+
+            Ok(packet.len())
+        }
+
+        fn set_timeout(&mut self, _: Duration) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    // Both directions of an encrypted link, with keys from a real exchange
+    struct Link {
+        clock: Arc<SimulatedClock>,
+        downlink: Loopback,
+        server_writer: Box<dyn SocketWriter>,
+        client_reader: Box<dyn SocketReader>,
+        client_writer: Box<dyn SocketWriter>,
+        server_reader: Box<dyn SocketReader>,
+    }
+
+    impl Link {
+        fn new(server_keys: StreamKeys, client_keys: StreamKeys) -> Self {
+            let clock = Arc::new(SimulatedClock::new());
+            let downlink = Loopback::default();
+            let uplink = Loopback::default();
+
+            let (server_writer, server_reader) = encrypt(
+                Box::new(downlink.clone()),
+                Box::new(uplink.clone()),
+                &SocketProtocol::Udp,
+                Arc::clone(&clock) as _,
+                server_keys,
+            );
+            let (client_writer, client_reader) = encrypt(
+                Box::new(uplink),
+                Box::new(downlink.clone()),
+                &SocketProtocol::Udp,
+                Arc::clone(&clock) as _,
+                client_keys,
+            );
+
+            Self {
+                clock,
+                downlink,
+                server_writer,
+                client_reader,
+                client_writer,
+                server_reader,
+            }
+        }
+    }
+
+    fn exchange_keys() -> (StreamKeys, StreamKeys) {
+        let server_exchange = KeyExchange::new();
+        let client_exchange = KeyExchange::new();
+        let server_public_key = server_exchange.public_key();
+        let client_public_key = client_exchange.public_key();
+
+        (
+            server_exchange
+                .derive_keys(client_public_key, true)
+                .unwrap(),
+            client_exchange
+                .derive_keys(server_public_key, false)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_derived_keys() {
+        let (server_keys, client_keys) = exchange_keys();
+
+        assert_eq!(server_keys.send_key, client_keys.receive_key);
+        assert_eq!(server_keys.receive_key, client_keys.send_key);
+        assert_ne!(server_keys.send_key, server_keys.receive_key);
+
+        // The all-zero point would give a predictable shared secret
+        assert!(KeyExchange::new().derive_keys([0; 32], true).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (server_keys, client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, client_keys);
+
+        link.server_writer.send(b"first shard").unwrap();
+        link.server_writer.send_unreliable(b"second").unwrap();
+        link.client_writer.send(b"uplink").unwrap();
+
+        let envelope = link.downlink.0.lock()[0].clone();
+        assert_eq!(envelope.len(), ENCRYPTION_OVERHEAD + b"first shard".len());
+        assert_ne!(&envelope[ENCRYPTION_OVERHEAD..], b"first shard");
+
+        // peek() returns the full shard length and leaves the shard in place
+        let mut buffer = [0; 64];
+        assert_eq!(
+            link.client_reader.peek(&mut buffer[..4]).unwrap(),
+            b"first shard".len()
+        );
+        assert_eq!(&buffer[..4], b"firs");
+        let count = link.client_reader.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"first shard");
+        let count = link.client_reader.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"second");
+        assert!(matches!(
+            link.client_reader.recv(&mut buffer),
+            Err(ConnectionError::TryAgain(_))
+        ));
+
+        let count = link.server_reader.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"uplink");
+
+        assert_eq!(link.client_reader.take_authentication_failures(), 0);
+        assert_eq!(link.server_reader.take_authentication_failures(), 0);
+    }
+
+    #[test]
+    fn test_oversized_shard() {
+        let (server_keys, client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, client_keys);
+
+        let shard = vec![0; u16::MAX as usize - ENCRYPTION_OVERHEAD + 1];
+        assert!(link.server_writer.send(&shard).is_err());
+        assert!(link.downlink.0.lock().is_empty());
+    }
+
+    #[test]
+    fn test_tampered_envelope() {
+        let (server_keys, client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, client_keys);
+
+        // Sequence number, tag and ciphertext
+        let tampered_offsets = [LENGTH_SIZE, ASSOCIATED_DATA_SIZE, ENCRYPTION_OVERHEAD];
+        for offset in tampered_offsets {
+            link.server_writer.send(b"tampered").unwrap();
+            link.downlink.0.lock().back_mut().unwrap()[offset] ^= 1;
+        }
+        link.server_writer.send(b"valid").unwrap();
+
+        // Tampered envelopes are dropped and don't affect the following ones
+        let mut buffer = [0; 16];
+        let count = link.client_reader.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"valid");
+        assert_eq!(
+            link.client_reader.take_authentication_failures(),
+            tampered_offsets.len() as u32
+        );
+        assert_eq!(link.client_reader.take_authentication_failures(), 0);
+    }
+
+    #[test]
+    fn test_forged_length() {
+        let (server_keys, client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, client_keys);
+
+        let forged_lengths = [ENCRYPTION_OVERHEAD + 64, ENCRYPTION_OVERHEAD + 4, 0];
+        for length in forged_lengths {
+            link.server_writer.send(b"forged").unwrap();
+            link.downlink.0.lock().back_mut().unwrap()[..LENGTH_SIZE]
+                .copy_from_slice(&(length as u16).to_le_bytes());
+        }
+        link.downlink.0.lock().push_back(vec![0]);
+        link.server_writer.send(b"valid").unwrap();
+
+        // Each bad datagram is dropped alone, the following envelope is not consumed
+        let mut buffer = [0; 16];
+        let count = link.client_reader.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"valid");
+        assert_eq!(
+            link.client_reader.take_authentication_failures(),
+            forged_lengths.len() as u32 + 1
+        );
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let (server_keys, _) = exchange_keys();
+        let (_, unrelated_client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, unrelated_client_keys);
+
+        for _ in 0..3 {
+            link.server_writer.send(b"shard").unwrap();
+        }
+
+        let mut buffer = [0; 16];
+        assert!(link.client_reader.recv(&mut buffer).is_err());
+        assert_eq!(link.client_reader.take_authentication_failures(), 3);
+    }
+
+    #[test]
+    fn test_peeked_packet_age() {
+        let (server_keys, client_keys) = exchange_keys();
+        let mut link = Link::new(server_keys, client_keys);
+
+        assert_eq!(link.client_reader.peeked_packet_age(), None);
+
+        link.server_writer.send(b"shard").unwrap();
+        link.client_reader.peek(&mut [0; 16]).unwrap();
+        link.clock.advance(Duration::from_millis(5));
+
+        assert_eq!(
+            link.client_reader.peeked_packet_age(),
+            Some(Duration::from_millis(5))
+        );
+    }
+}
@@ -0,0 +1,256 @@
+// Network impairment emulation, used to test the stream without a real degraded network.
+// The impairment is applied to the outgoing packets: each packet is dropped, duplicated or
+// scheduled for delivery after a delay, then a background thread sends it when its delivery time
+// is reached. Every call to send() is treated as a packet, so this is meaningful only for datagram
+// sockets.
+
+use super::SocketWriter;
+use crate::pacer::Pacer;
+use alvr_common::{
+    anyhow::{bail, Result},
+    debug,
+    parking_lot::Mutex,
+    SharedClock,
+};
+use alvr_session::{settings_schema::Switch, NetworkImpairmentConfig, PacketLossModel};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    mem,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Handle used to change the impairment parameters at runtime. None disables the impairment.
+#[derive(Clone, Default)]
+pub struct NetworkImpairment(Arc<Mutex<Option<NetworkImpairmentConfig>>>);
+
+impl NetworkImpairment {
+    pub fn new(config: Option<NetworkImpairmentConfig>) -> Self {
+        Self(Arc::new(Mutex::new(config)))
+    }
+
+    pub fn set_config(&self, config: Option<NetworkImpairmentConfig>) {
+        *self.0.lock() = config;
+    }
+}
+
+struct ScheduledPacket {
+    delivery_instant: Instant,
+    // keeps the send order for packets with the same delivery instant
+    sequence: u64,
+    data: Vec<u8>,
+}
+
+impl PartialEq for ScheduledPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledPacket {}
+
+impl PartialOrd for ScheduledPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledPacket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.delivery_instant, self.sequence).cmp(&(other.delivery_instant, other.sequence))
+    }
+}
+
+fn delivery_loop(
+    clock: SharedClock,
+    mut inner: Box<dyn SocketWriter>,
+    packet_receiver: mpsc::Receiver<ScheduledPacket>,
+) {
+    let mut queue = BinaryHeap::new();
+    loop {
+        let now = clock.now();
+        while let Some(Reverse(packet)) = queue.peek() {
+            if packet.delivery_instant > now {
+                break;
+            }
+
+            let Reverse(packet) = queue.pop().unwrap();
+            if let Err(e) = inner.send(&packet.data) {
+                debug!("Failed to deliver impaired packet: {e}");
+            }
+        }
+
+        let res = if let Some(Reverse(packet)) = queue.peek() {
+            packet_receiver.recv_timeout(packet.delivery_instant.saturating_duration_since(now))
+        } else {
+            packet_receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match res {
+            Ok(packet) => queue.push(Reverse(packet)),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+pub struct ImpairedWriter {
+    clock: SharedClock,
+    impairment: NetworkImpairment,
+    packet_sender: mpsc::Sender<ScheduledPacket>,
+    next_sequence: u64,
+    gilbert_elliott_bad_state: bool,
+    // The pacer is recreated when the bucket size changes
+    bandwidth_limiter: Option<(Pacer, usize)>,
+}
+
+impl ImpairedWriter {
+    pub fn new(
+        clock: SharedClock,
+        inner: Box<dyn SocketWriter>,
+        impairment: NetworkImpairment,
+    ) -> Self {
+        let (packet_sender, packet_receiver) = mpsc::channel();
+
+        thread::spawn({
+            let clock = Arc::clone(&clock);
+            move || delivery_loop(clock, inner, packet_receiver)
+        });
+
+        Self {
+            clock,
+            impairment,
+            packet_sender,
+            next_sequence: 0,
+            gilbert_elliott_bad_state: false,
+            bandwidth_limiter: None,
+        }
+    }
+
+    fn is_lost(&mut self, model: &PacketLossModel) -> bool {
+        match model {
+            PacketLossModel::Random { probability } => rand::random::<f32>() < *probability,
+            PacketLossModel::GilbertElliott {
+                good_to_bad_probability,
+                bad_to_good_probability,
+                good_loss_probability,
+                bad_loss_probability,
+            } => {
+                let transition_probability = if self.gilbert_elliott_bad_state {
+                    *bad_to_good_probability
+                } else {
+                    *good_to_bad_probability
+                };
+                if rand::random::<f32>() < transition_probability {
+                    self.gilbert_elliott_bad_state = !self.gilbert_elliott_bad_state;
+                }
+
+                let loss_probability = if self.gilbert_elliott_bad_state {
+                    *bad_loss_probability
+                } else {
+                    *good_loss_probability
+                };
+                rand::random::<f32>() < loss_probability
+            }
+        }
+    }
+
+    fn schedule(&mut self, delivery_instant: Instant, buffer: &[u8]) -> Result<()> {
+        let packet = ScheduledPacket {
+            delivery_instant,
+            sequence: self.next_sequence,
+            data: buffer.to_vec(),
+        };
+        self.next_sequence += 1;
+
+        if self.packet_sender.send(packet).is_err() {
+            bail!("Impairment delivery thread stopped");
+        }
+
+        Ok(())
+    }
+}
+
+impl SocketWriter for ImpairedWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        let now = self.clock.now();
+
+        // Packets go through the queue even with no impairment, to not overtake delayed packets
+        let Some(config) = self.impairment.0.lock().clone() else {
+            self.bandwidth_limiter = None;
+            return self.schedule(now, buffer);
+        };
+
+        if let Switch::Enabled(model) = &config.packet_loss {
+            if self.is_lost(model) {
+                return Ok(());
+            }
+        }
+
+        let mut delivery_instant = now;
+
+        if let Switch::Enabled(limit) = &config.bandwidth_limit {
+            let rate_bps = limit.rate_mbps as f64 * 1e6;
+            let bucket_size = limit.bucket_size_kb * 1000;
+            if !matches!(&self.bandwidth_limiter, Some((_, size)) if *size == bucket_size) {
+                self.bandwidth_limiter =
+                    Some((Pacer::new(now, rate_bps, bucket_size), bucket_size));
+            }
+            let (limiter, _) = self.bandwidth_limiter.as_mut().unwrap();
+            limiter.set_rate(rate_bps);
+
+            let queue_delay = limiter.reserve(now, buffer.len());
+            if queue_delay > Duration::from_millis(limit.max_queue_delay_ms) {
+                // The bottleneck queue is full, tail drop
+                limiter.refund(buffer.len());
+                return Ok(());
+            }
+            delivery_instant += queue_delay;
+        } else {
+            self.bandwidth_limiter = None;
+        }
+
+        let jitter_ms = (rand::random::<f32>() * 2.0 - 1.0) * config.jitter_ms;
+        delivery_instant +=
+            Duration::from_secs_f32(f32::max(config.delay_ms + jitter_ms, 0.0) / 1000.0);
+
+        if let Switch::Enabled(reordering) = &config.reordering {
+            if rand::random::<f32>() < reordering.probability {
+                delivery_instant += Duration::from_secs_f32(reordering.extra_delay_ms / 1000.0);
+            }
+        }
+
+        self.schedule(delivery_instant, buffer)?;
+
+        if rand::random::<f32>() < config.duplication_probability {
+            self.schedule(delivery_instant, buffer)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Placeholder used while the writer is being wrapped
+struct DetachedWriter;
+
+impl SocketWriter for DetachedWriter {
+    fn send(&mut self, _: &[u8]) -> Result<()> {
+        bail!("Socket detached")
+    }
+}
+
+// Wrap the writer in place
+pub fn impair(
+    writer: &mut Box<dyn SocketWriter>,
+    clock: SharedClock,
+    impairment: NetworkImpairment,
+) {
+    let inner = mem::replace(writer, Box::new(DetachedWriter));
+    *writer = Box::new(ImpairedWriter::new(clock, inner, impairment));
+}
@@ -1,10 +1,38 @@
+pub mod encryption;
+pub mod impairment;
+pub mod quic;
 pub mod tcp;
 pub mod udp;
 
 use alvr_common::{anyhow::Result, ConResult};
+use std::time::Duration;
 
 pub trait SocketWriter: Send {
     fn send(&mut self, buffer: &[u8]) -> Result<()>;
+
+    // Send a packet that may be lost or reordered. Transports that have no separate unreliable
+    // channel send it like any other packet
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        self.send(buffer)
+    }
+
+    // Whether send_batch() sends multiple packets with a single syscall
+    fn supports_batching(&self) -> bool {
+        false
+    }
+
+    // Send multiple packets, each one made of a prefix and a payload
+    fn send_batch(&mut self, packets: &[(&[u8], &[u8])]) -> Result<()> {
+        let mut buffer = vec![];
+        for (prefix, payload) in packets {
+            buffer.clear();
+            buffer.extend_from_slice(prefix);
+            buffer.extend_from_slice(payload);
+            self.send(&buffer)?;
+        }
+
+        Ok(())
+    }
 }
 
 // Trait used to abstract different socket (or other input/output) implementations. The funtionality
@@ -15,5 +43,19 @@ pub trait SocketReader: Send {
     // packet (size of MTU) otherwise data will be corrupted. The size of the data is
     fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize>;
 
-    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize>;
+    fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize>;
+
+    // Time elapsed since the packet returned by the last peek() was received by the OS. Readers
+    // that receive packets ahead of time use this to keep accurate receive timestamps
+    fn peeked_packet_age(&self) -> Option<Duration> {
+        None
+    }
+
+    // Packets dropped because they failed authentication, since the last call
+    fn take_authentication_failures(&mut self) -> u32 {
+        0
+    }
+
+    // Change the maximum time recv() and peek() wait for a packet before returning TryAgain
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()>;
 }
@@ -0,0 +1,452 @@
+// QUIC transport. Unreliable packets (video shards) are sent as QUIC datagrams, all other packets
+// go through a single reliable unidirectional stream per direction, framed with a u32 length.
+// quinn is async, the connection is driven by a small tokio runtime owned by the writer and the
+// reader, and packets are exchanged with the stream socket threads through channels.
+//
+// Like for TCP, the client listens and the server connects, so the client is the QUIC server.
+// The client certificate is self-signed and regenerated for each connection. Its SHA-256 hash is
+// signed with the identity key of the client during the control socket handshake, and the server
+// accepts only the certificate with that hash.
+
+use super::{udp, SocketReader, SocketWriter};
+use crate::{KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT};
+use alvr_common::{anyhow::Result, con_bail, debug, ConResult, HandleTryAgain, ToCon};
+use alvr_session::{DscpTos, SocketBufferSize};
+use bytes::Bytes;
+use quinn::{
+    ClientConfig, Connection, Endpoint, EndpointConfig, IdleTimeout, SendDatagramError,
+    ServerConfig, TokioRuntime, TransportConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{mpsc, Arc},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{runtime::Runtime, sync::mpsc as tokio_mpsc};
+
+// Short header with 8 bytes connection ID and 4 bytes packet number, AEAD tag and datagram frame
+// header. Upper bound of the overhead of QUIC for each shard
+pub const QUIC_HEADER_SIZE: usize = 1 + 8 + 4 + 16 + 3;
+
+// Minimum UDP payload size allowed by QUIC
+const MIN_INITIAL_MTU: usize = 1200;
+
+// Datagrams are dropped if they don't fit in these queues, like with a full UDP socket buffer
+const DATAGRAM_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+const SERVER_NAME: &str = "alvr.client";
+
+struct ReceivedPacket {
+    data: Bytes,
+    rx_instant: Instant,
+}
+
+pub struct QuicCertificate {
+    der: Vec<u8>,
+    private_key_der: Vec<u8>,
+}
+
+impl QuicCertificate {
+    pub fn generate() -> Result<Self> {
+        let certificate = rcgen::generate_simple_self_signed(vec![SERVER_NAME.into()])?;
+
+        // The DER is serialized once, the signature changes with every serialization
+        Ok(Self {
+            der: certificate.serialize_der()?,
+            private_key_der: certificate.serialize_private_key_der(),
+        })
+    }
+
+    // Sent to the server in the signed part of the handshake
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(&self.der).into()
+    }
+}
+
+pub struct QuicListener {
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+    certificate: QuicCertificate,
+}
+
+fn new_runtime() -> Result<Runtime> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("alvr-quic")
+        .enable_all()
+        .build()?)
+}
+
+fn new_endpoint(
+    runtime: &Runtime,
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<Endpoint> {
+    let socket = udp::bind(port, dscp, send_buffer_bytes, recv_buffer_bytes)?;
+
+    // The endpoint registers the socket with the runtime reactor
+    let _guard = runtime.enter();
+
+    Ok(Endpoint::new(
+        EndpointConfig::default(),
+        None,
+        socket,
+        Arc::new(TokioRuntime),
+    )?)
+}
+
+fn transport_config(max_packet_size: usize) -> Result<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config
+        .max_idle_timeout(Some(IdleTimeout::try_from(KEEPALIVE_TIMEOUT)?))
+        .keep_alive_interval(Some(KEEPALIVE_INTERVAL))
+        // Full size shards should fit in a datagram before the path MTU is discovered
+        .initial_mtu(usize::max(max_packet_size + QUIC_HEADER_SIZE, MIN_INITIAL_MTU) as u16)
+        .datagram_receive_buffer_size(Some(DATAGRAM_BUFFER_SIZE))
+        .datagram_send_buffer_size(DATAGRAM_BUFFER_SIZE);
+
+    Ok(config)
+}
+
+pub fn bind(
+    certificate: QuicCertificate,
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+) -> Result<QuicListener> {
+    let runtime = Arc::new(new_runtime()?);
+    let endpoint = new_endpoint(&runtime, port, dscp, send_buffer_bytes, recv_buffer_bytes)?;
+
+    Ok(QuicListener {
+        runtime,
+        endpoint,
+        certificate,
+    })
+}
+
+pub fn accept_from_server(
+    listener: QuicListener,
+    server_ip: IpAddr,
+    timeout: Duration,
+    max_packet_size: usize,
+) -> ConResult<(QuicWriter, QuicReader)> {
+    let mut server_config = ServerConfig::with_single_cert(
+        vec![rustls::Certificate(listener.certificate.der)],
+        rustls::PrivateKey(listener.certificate.private_key_der),
+    )
+    .to_con()?;
+    server_config
+        .transport_config(Arc::new(transport_config(max_packet_size).to_con()?))
+        // Keep the connection if the server address changes, for example after a NAT rebinding
+        .migration(true);
+    listener.endpoint.set_server_config(Some(server_config));
+
+    let connection = listener.runtime.block_on(async {
+        let Ok(connecting) = tokio::time::timeout(timeout, listener.endpoint.accept()).await else {
+            return alvr_common::try_again();
+        };
+        let Some(connecting) = connecting else {
+            con_bail!("QUIC endpoint closed");
+        };
+
+        if connecting.remote_address().ip() != server_ip {
+            con_bail!(
+                "Connected to wrong server: Expected: {server_ip}, Found {}",
+                connecting.remote_address().ip()
+            );
+        }
+
+        tokio::time::timeout(timeout, connecting)
+            .await
+            .to_con()?
+            .to_con()
+    })?;
+
+    Ok(split(
+        listener.runtime,
+        listener.endpoint,
+        connection,
+        timeout,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn connect_to_client(
+    timeout: Duration,
+    client_ip: IpAddr,
+    client_certificate_hash: [u8; 32],
+    port: u16,
+    dscp: Option<DscpTos>,
+    send_buffer_bytes: SocketBufferSize,
+    recv_buffer_bytes: SocketBufferSize,
+    max_packet_size: usize,
+) -> ConResult<(QuicWriter, QuicReader)> {
+    let runtime = Arc::new(new_runtime().to_con()?);
+    // Ephemeral port, so that the server and the client can run on the same machine
+    let endpoint =
+        new_endpoint(&runtime, 0, dscp, send_buffer_bytes, recv_buffer_bytes).to_con()?;
+
+    let crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertificateVerifier {
+            hash: client_certificate_hash,
+        }))
+        .with_no_client_auth();
+    let mut client_config = ClientConfig::new(Arc::new(crypto));
+    client_config.transport_config(Arc::new(transport_config(max_packet_size).to_con()?));
+
+    let connection = runtime.block_on(async {
+        let connecting = endpoint
+            .connect_with(client_config, SocketAddr::new(client_ip, port), SERVER_NAME)
+            .to_con()?;
+
+        match tokio::time::timeout(timeout, connecting).await {
+            Ok(res) => res.to_con(),
+            Err(_) => alvr_common::try_again(),
+        }
+    })?;
+
+    Ok(split(runtime, endpoint, connection, timeout))
+}
+
+// The certificate of the client is self-signed, so there is no chain to verify. It is accepted
+// only if it matches the hash received on the authenticated control socket. The handshake
+// signature is still checked against it by rustls, proving the ownership of the private key
+struct PinnedCertificateVerifier {
+    hash: [u8; 32],
+}
+
+impl rustls::client::ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _: &[rustls::Certificate],
+        _: &rustls::ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        if Sha256::digest(&end_entity.0)[..] == self.hash {
+            Ok(rustls::client::ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+fn split(
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+    connection: Connection,
+    timeout: Duration,
+) -> (QuicWriter, QuicReader) {
+    let (reliable_sender, reliable_receiver) = tokio_mpsc::unbounded_channel();
+    let (packet_sender, packet_receiver) = mpsc::channel();
+
+    runtime.spawn({
+        let connection = connection.clone();
+        async move {
+            if let Err(e) = send_reliable(connection, reliable_receiver).await {
+                debug!("QUIC stream send ended: {e}");
+            }
+        }
+    });
+    runtime.spawn({
+        let connection = connection.clone();
+        let packet_sender = packet_sender.clone();
+        async move {
+            if let Err(e) = receive_datagrams(connection, packet_sender).await {
+                debug!("QUIC datagram receive ended: {e}");
+            }
+        }
+    });
+    runtime.spawn({
+        let connection = connection.clone();
+        async move {
+            if let Err(e) = receive_reliable(connection, packet_sender).await {
+                debug!("QUIC stream receive ended: {e}");
+            }
+        }
+    });
+
+    (
+        QuicWriter {
+            _runtime: Arc::clone(&runtime),
+            _endpoint: endpoint,
+            connection,
+            reliable_sender,
+        },
+        QuicReader {
+            _runtime: runtime,
+            receiver: packet_receiver,
+            timeout,
+            next_packet: None,
+        },
+    )
+}
+
+async fn send_reliable(
+    connection: Connection,
+    mut receiver: tokio_mpsc::UnboundedReceiver<Bytes>,
+) -> Result<()> {
+    let mut stream = connection.open_uni().await?;
+    while let Some(packet) = receiver.recv().await {
+        stream
+            .write_all(&(packet.len() as u32).to_le_bytes())
+            .await?;
+        stream.write_all(&packet).await?;
+    }
+
+    Ok(())
+}
+
+async fn receive_datagrams(
+    connection: Connection,
+    sender: mpsc::Sender<ReceivedPacket>,
+) -> Result<()> {
+    loop {
+        let data = connection.read_datagram().await?;
+        sender.send(ReceivedPacket {
+            data,
+            rx_instant: Instant::now(),
+        })?;
+    }
+}
+
+async fn receive_reliable(
+    connection: Connection,
+    sender: mpsc::Sender<ReceivedPacket>,
+) -> Result<()> {
+    let mut stream = connection.accept_uni().await?;
+    loop {
+        let mut length_bytes = [0; 4];
+        stream.read_exact(&mut length_bytes).await?;
+
+        let mut data = vec![0; u32::from_le_bytes(length_bytes) as usize];
+        stream.read_exact(&mut data).await?;
+
+        sender.send(ReceivedPacket {
+            data: data.into(),
+            rx_instant: Instant::now(),
+        })?;
+    }
+}
+
+pub struct QuicWriter {
+    _runtime: Arc<Runtime>,
+    _endpoint: Endpoint,
+    connection: Connection,
+    reliable_sender: tokio_mpsc::UnboundedSender<Bytes>,
+}
+
+impl SocketWriter for QuicWriter {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        self.reliable_sender.send(Bytes::copy_from_slice(buffer))?;
+
+        Ok(())
+    }
+
+    fn send_unreliable(&mut self, buffer: &[u8]) -> Result<()> {
+        match self
+            .connection
+            .send_datagram(Bytes::copy_from_slice(buffer))
+        {
+            // The path does not allow datagrams this big, the shard must not be lost
+            Err(SendDatagramError::TooLarge) => self.send(buffer),
+            res => Ok(res?),
+        }
+    }
+}
+
+// Packets from the datagrams and from the reliable stream are merged in arrival order. Each recv()
+// returns a whole packet, like for UDP
+pub struct QuicReader {
+    _runtime: Arc<Runtime>,
+    receiver: mpsc::Receiver<ReceivedPacket>,
+    timeout: Duration,
+    next_packet: Option<ReceivedPacket>,
+}
+
+impl QuicReader {
+    fn next_packet(&mut self) -> ConResult<&[u8]> {
+        if self.next_packet.is_none() {
+            self.next_packet = Some(
+                self.receiver
+                    .recv_timeout(self.timeout)
+                    .handle_try_again()?,
+            );
+        }
+
+        Ok(&self.next_packet.as_ref().unwrap().data)
+    }
+}
+
+impl SocketReader for QuicReader {
+    // The rest of the packet is dropped if the buffer is too small
+    fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let packet = self.next_packet()?;
+        let count = usize::min(packet.len(), buffer.len());
+        buffer[..count].copy_from_slice(&packet[..count]);
+
+        self.next_packet = None;
+
+        Ok(count)
+    }
+
+    // Returns the full length of the packet, like MSG_TRUNC
+    fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+        let packet = self.next_packet()?;
+        let count = usize::min(packet.len(), buffer.len());
+        buffer[..count].copy_from_slice(&packet[..count]);
+
+        Ok(packet.len())
+    }
+
+    fn peeked_packet_age(&self) -> Option<Duration> {
+        Some(self.next_packet.as_ref()?.rx_instant.elapsed())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.timeout = timeout;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::client::ServerCertVerifier;
+
+    #[test]
+    fn test_pinned_certificate() {
+        let certificate = QuicCertificate::generate().unwrap();
+        let other_certificate = QuicCertificate::generate().unwrap();
+        assert_ne!(certificate.hash(), other_certificate.hash());
+
+        let verifier = PinnedCertificateVerifier {
+            hash: certificate.hash(),
+        };
+        let verify = |certificate: &QuicCertificate| {
+            verifier
+                .verify_server_cert(
+                    &rustls::Certificate(certificate.der.clone()),
+                    &[],
+                    &rustls::ServerName::try_from(SERVER_NAME).unwrap(),
+                    &mut std::iter::empty::<&[u8]>(),
+                    &[],
+                    SystemTime::now(),
+                )
+                .is_ok()
+        };
+
+        assert!(verify(&certificate));
+        assert!(!verify(&other_certificate));
+    }
+}
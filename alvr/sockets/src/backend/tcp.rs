@@ -94,7 +94,13 @@ impl SocketReader for TcpStream {
         Read::read(self, buffer).handle_try_again()
     }
 
-    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+    fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
         TcpStream::peek(self, buffer).handle_try_again()
     }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+
+        Ok(())
+    }
 }
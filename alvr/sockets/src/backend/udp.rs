@@ -28,16 +28,25 @@ pub fn bind(
     Ok(socket.into())
 }
 
+// max_packet_size is used to size the receive buffers when packets are received in batches
 pub fn connect(
     socket: &UdpSocket,
     peer_ip: IpAddr,
     port: u16,
     timeout: Duration,
-) -> Result<(UdpSocket, Socket)> {
+    max_packet_size: usize,
+) -> Result<(UdpSocket, Box<dyn SocketReader>)> {
     socket.connect((peer_ip, port))?;
     socket.set_read_timeout(Some(timeout))?;
 
-    Ok((socket.try_clone()?, socket.try_clone()?.into()))
+    let receive_socket: Socket = socket.try_clone()?.into();
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let receive_socket = batched::BatchedReader::new(receive_socket, max_packet_size);
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = max_packet_size;
+
+    Ok((socket.try_clone()?, Box::new(receive_socket)))
 }
 
 impl SocketWriter for UdpSocket {
@@ -46,6 +55,16 @@ impl SocketWriter for UdpSocket {
 
         Ok(())
     }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn supports_batching(&self) -> bool {
+        true
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn send_batch(&mut self, packets: &[(&[u8], &[u8])]) -> Result<()> {
+        batched::send_batch(self, packets)
+    }
 }
 
 impl SocketReader for Socket {
@@ -53,7 +72,7 @@ impl SocketReader for Socket {
         Socket::recv(self, unsafe { mem::transmute(buffer) }).handle_try_again()
     }
 
-    fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+    fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
         #[cfg(windows)]
         const FLAGS: c_int = 0x02 | 0x8000; // MSG_PEEK | MSG_PARTIAL
         #[cfg(not(windows))]
@@ -65,4 +84,217 @@ impl SocketReader for Socket {
             .handle_try_again()?
             .0)
     }
+
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+
+        Ok(())
+    }
+}
+
+// Send and receive multiple packets per syscall with sendmmsg/recvmmsg. Packets are received ahead
+// of time into internal buffers and timestamped by the kernel
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod batched {
+    use super::SocketReader;
+    use alvr_common::{anyhow::Result, ConResult, HandleTryAgain};
+    use socket2::Socket;
+    use std::{
+        ffi::{c_int, c_void},
+        io, mem,
+        net::UdpSocket,
+        os::fd::AsRawFd,
+        ptr,
+        time::{Duration, SystemTime},
+    };
+
+    const MAX_BATCH_PACKETS: usize = 32;
+
+    // Block until at least one packet is received, then return the packets already queued
+    const MSG_WAITFORONE: c_int = 0x10000;
+
+    // u64 for the alignment of cmsghdr. Fits one timespec control message
+    type ControlBuffer = [u64; 8];
+
+    pub fn send_batch(socket: &UdpSocket, packets: &[(&[u8], &[u8])]) -> Result<()> {
+        for batch in packets.chunks(MAX_BATCH_PACKETS) {
+            let mut iovecs: [[libc::iovec; 2]; MAX_BATCH_PACKETS] = unsafe { mem::zeroed() };
+            let mut headers: [libc::mmsghdr; MAX_BATCH_PACKETS] = unsafe { mem::zeroed() };
+            for (idx, (prefix, payload)) in batch.iter().enumerate() {
+                iovecs[idx] = [
+                    libc::iovec {
+                        iov_base: prefix.as_ptr() as *mut c_void,
+                        iov_len: prefix.len(),
+                    },
+                    libc::iovec {
+                        iov_base: payload.as_ptr() as *mut c_void,
+                        iov_len: payload.len(),
+                    },
+                ];
+                headers[idx].msg_hdr.msg_iov = iovecs[idx].as_mut_ptr();
+                headers[idx].msg_hdr.msg_iovlen = 2;
+            }
+
+            // sendmmsg may send only part of the batch
+            let mut sent_count = 0;
+            while sent_count < batch.len() {
+                let res = unsafe {
+                    libc::sendmmsg(
+                        socket.as_raw_fd(),
+                        headers[sent_count..].as_mut_ptr(),
+                        (batch.len() - sent_count) as _,
+                        0,
+                    )
+                };
+                if res < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+                sent_count += res as usize;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub struct BatchedReader {
+        socket: Socket,
+        buffers: Vec<Vec<u8>>,
+        control_buffers: Vec<ControlBuffer>,
+        lengths: [usize; MAX_BATCH_PACKETS],
+        rx_times: [SystemTime; MAX_BATCH_PACKETS],
+        received_count: usize,
+        next_index: usize,
+    }
+
+    impl BatchedReader {
+        pub fn new(socket: Socket, max_packet_size: usize) -> Self {
+            // Without kernel timestamps, packets are timestamped when the batch is received
+            let enable: c_int = 1;
+            unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_TIMESTAMPNS,
+                    &enable as *const c_int as *const c_void,
+                    mem::size_of::<c_int>() as _,
+                )
+            };
+
+            Self {
+                socket,
+                buffers: vec![vec![0; max_packet_size]; MAX_BATCH_PACKETS],
+                control_buffers: vec![[0; 8]; MAX_BATCH_PACKETS],
+                lengths: [0; MAX_BATCH_PACKETS],
+                rx_times: [SystemTime::UNIX_EPOCH; MAX_BATCH_PACKETS],
+                received_count: 0,
+                next_index: 0,
+            }
+        }
+
+        fn receive_batch(&mut self) -> ConResult {
+            let mut iovecs: [libc::iovec; MAX_BATCH_PACKETS] = unsafe { mem::zeroed() };
+            let mut headers: [libc::mmsghdr; MAX_BATCH_PACKETS] = unsafe { mem::zeroed() };
+            for idx in 0..MAX_BATCH_PACKETS {
+                iovecs[idx] = libc::iovec {
+                    iov_base: self.buffers[idx].as_mut_ptr() as *mut c_void,
+                    iov_len: self.buffers[idx].len(),
+                };
+                headers[idx].msg_hdr.msg_iov = &mut iovecs[idx];
+                headers[idx].msg_hdr.msg_iovlen = 1;
+                headers[idx].msg_hdr.msg_control =
+                    self.control_buffers[idx].as_mut_ptr() as *mut c_void;
+                headers[idx].msg_hdr.msg_controllen = mem::size_of::<ControlBuffer>() as _;
+            }
+
+            // The receive timeout of the socket applies to the first packet
+            let count = unsafe {
+                libc::recvmmsg(
+                    self.socket.as_raw_fd(),
+                    headers.as_mut_ptr(),
+                    MAX_BATCH_PACKETS as _,
+                    MSG_WAITFORONE as _,
+                    ptr::null_mut(),
+                )
+            };
+            if count < 0 {
+                return Err(io::Error::last_os_error()).handle_try_again();
+            }
+
+            let batch_rx_time = SystemTime::now();
+            for (idx, header) in headers.iter().enumerate().take(count as usize) {
+                self.lengths[idx] = header.msg_len as usize;
+                self.rx_times[idx] = kernel_rx_time(&header.msg_hdr).unwrap_or(batch_rx_time);
+            }
+            self.received_count = count as usize;
+            self.next_index = 0;
+
+            Ok(())
+        }
+
+        fn next_packet(&mut self) -> ConResult<&[u8]> {
+            if self.next_index >= self.received_count {
+                self.receive_batch()?;
+            }
+
+            Ok(&self.buffers[self.next_index][..self.lengths[self.next_index]])
+        }
+    }
+
+    fn kernel_rx_time(header: &libc::msghdr) -> Option<SystemTime> {
+        unsafe {
+            let mut control_message = libc::CMSG_FIRSTHDR(header);
+            while !control_message.is_null() {
+                if (*control_message).cmsg_level == libc::SOL_SOCKET
+                    && (*control_message).cmsg_type == libc::SO_TIMESTAMPNS
+                {
+                    let timestamp = ptr::read_unaligned(
+                        libc::CMSG_DATA(control_message) as *const libc::timespec
+                    );
+
+                    return Some(
+                        SystemTime::UNIX_EPOCH
+                            + Duration::new(timestamp.tv_sec as u64, timestamp.tv_nsec as u32),
+                    );
+                }
+                control_message = libc::CMSG_NXTHDR(header, control_message);
+            }
+        }
+
+        None
+    }
+
+    impl SocketReader for BatchedReader {
+        // Like a normal UDP socket, the rest of the packet is dropped if the buffer is too small
+        fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let packet = self.next_packet()?;
+            let count = usize::min(packet.len(), buffer.len());
+            buffer[..count].copy_from_slice(&packet[..count]);
+
+            self.next_index += 1;
+
+            Ok(count)
+        }
+
+        // Returns the full length of the packet, like MSG_TRUNC
+        fn peek(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let packet = self.next_packet()?;
+            let count = usize::min(packet.len(), buffer.len());
+            buffer[..count].copy_from_slice(&packet[..count]);
+
+            Ok(packet.len())
+        }
+
+        fn peeked_packet_age(&self) -> Option<Duration> {
+            let rx_time = self.rx_times[..self.received_count].get(self.next_index)?;
+
+            SystemTime::now().duration_since(*rx_time).ok()
+        }
+
+        // The timeout applies to the first packet of each batch
+        fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+            self.socket.set_read_timeout(Some(timeout))?;
+
+            Ok(())
+        }
+    }
 }
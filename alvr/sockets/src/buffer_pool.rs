@@ -0,0 +1,71 @@
+use std::sync::mpsc;
+
+// Receive buffers of a stream, bounded by a byte budget. The budget accounts for the free buffers,
+// the buffers of the packets being reconstructed and the buffers of the packets not yet released
+// by the StreamReceiver. Sizes are measured by the buffer length.
+pub(crate) struct BufferPool {
+    max_bytes: usize,
+    used_bytes: usize,
+    free_buffers: Vec<Vec<u8>>,
+    release_sender: mpsc::Sender<Vec<u8>>,
+    release_receiver: mpsc::Receiver<Vec<u8>>,
+}
+
+impl BufferPool {
+    pub fn new(max_bytes: usize) -> Self {
+        let (release_sender, release_receiver) = mpsc::channel();
+
+        Self {
+            max_bytes,
+            used_bytes: 0,
+            free_buffers: vec![],
+            release_sender,
+            release_receiver,
+        }
+    }
+
+    // Buffers sent to this queue from other threads are returned to the pool
+    pub fn release_queue(&self) -> mpsc::Sender<Vec<u8>> {
+        self.release_sender.clone()
+    }
+
+    fn reclaim_released(&mut self) {
+        while let Ok(buffer) = self.release_receiver.try_recv() {
+            self.free_buffers.push(buffer);
+        }
+    }
+
+    // The buffer may be empty, memory is reserved only when it grows
+    pub fn take(&mut self) -> Vec<u8> {
+        self.reclaim_released();
+
+        self.free_buffers.pop().unwrap_or_default()
+    }
+
+    pub fn put(&mut self, buffer: Vec<u8>) {
+        self.free_buffers.push(buffer);
+    }
+
+    // Resize the buffer if it's smaller than `size`. Free buffers are dropped to make room if
+    // needed. Returns false if the budget would still be exceeded, the buffer is left untouched.
+    pub fn try_grow(&mut self, buffer: &mut Vec<u8>, size: usize) -> bool {
+        let additional = size.saturating_sub(buffer.len());
+        if additional == 0 {
+            return true;
+        }
+
+        self.reclaim_released();
+
+        while self.used_bytes + additional > self.max_bytes {
+            let Some(free_buffer) = self.free_buffers.pop() else {
+                return false;
+            };
+            self.used_bytes -= free_buffer.len();
+        }
+
+        buffer.resize(size, 0);
+        self.used_bytes += additional;
+
+        true
+    }
+}
@@ -0,0 +1,395 @@
+// Reed-Solomon forward error correction for the shards of a packet.
+// The codec is limited to 256 shards, so the data shards are split into blocks. Shards are
+// assigned to blocks in an interleaved way (shard i belongs to block i % blocks_count), this way a
+// burst of lost shards is spread across blocks. Every block has the same number of parity shards.
+//
+// Packet layout when FEC is enabled: data shards carry at most `shard_size` bytes, parity shards
+// carry the packet data size (u32) followed by `shard_size` parity bytes, so they have the same
+// size as a full shard without FEC. Parity shards are indexed after the data shards.
+
+use crate::stream_socket::SHARD_PREFIX_SIZE;
+use alvr_common::anyhow::{bail, Result};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    mem,
+};
+
+const MAX_BLOCK_DATA_SHARDS: usize = 192;
+const MAX_BLOCK_PARITY_SHARDS: usize = 64;
+
+pub const PARITY_HEADER_SIZE: usize = mem::size_of::<u32>();
+
+// The shards count field of the shard prefix carries the data shards count in the low 16 bits and
+// the parity shards count in the high 16 bits
+pub fn pack_shards_count(data_shards: usize, parity_shards: usize) -> u32 {
+    (data_shards | (parity_shards << 16)) as u32
+}
+
+pub fn unpack_shards_count(shards_count_field: u32) -> (usize, usize) {
+    (
+        (shards_count_field & 0xFFFF) as usize,
+        (shards_count_field >> 16) as usize,
+    )
+}
+
+fn blocks_count(data_shards: usize) -> usize {
+    usize::max(
+        (data_shards + MAX_BLOCK_DATA_SHARDS - 1) / MAX_BLOCK_DATA_SHARDS,
+        1,
+    )
+}
+
+pub struct FecLayout {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    // data bytes per shard
+    pub shard_size: usize,
+    blocks: usize,
+}
+
+impl FecLayout {
+    pub fn new(data_shards: usize, shard_size: usize, overhead_ratio: f32) -> Self {
+        let blocks = blocks_count(data_shards);
+        let largest_block_data_shards = (data_shards + blocks - 1) / blocks;
+
+        let block_parity_shards = if overhead_ratio > 0.0 && data_shards > 0 {
+            ((largest_block_data_shards as f32 * overhead_ratio).ceil() as usize)
+                .clamp(1, MAX_BLOCK_PARITY_SHARDS)
+        } else {
+            0
+        };
+
+        Self {
+            data_shards,
+            parity_shards: block_parity_shards * blocks,
+            shard_size,
+            blocks,
+        }
+    }
+
+    pub fn from_counts(data_shards: usize, parity_shards: usize, shard_size: usize) -> Self {
+        Self {
+            data_shards,
+            parity_shards,
+            shard_size,
+            blocks: blocks_count(data_shards),
+        }
+    }
+
+    // Position of the shard (including its prefix) inside the packet buffer
+    pub fn shard_position(&self, shard_index: usize) -> usize {
+        if shard_index < self.data_shards {
+            shard_index * self.shard_size
+        } else {
+            self.data_shards * self.shard_size
+                + (shard_index - self.data_shards) * (PARITY_HEADER_SIZE + self.shard_size)
+        }
+    }
+
+    fn block_data_indices(&self, block: usize) -> impl Iterator<Item = usize> {
+        (block..self.data_shards).step_by(self.blocks)
+    }
+
+    fn block_parity_indices(&self, block: usize) -> impl Iterator<Item = usize> {
+        (self.data_shards + block..self.data_shards + self.parity_shards).step_by(self.blocks)
+    }
+
+    pub fn can_reconstruct(&self, received_shards: &HashSet<usize>) -> bool {
+        if self.parity_shards == 0 {
+            return false;
+        }
+
+        (0..self.blocks).all(|block| {
+            let received_count = self
+                .block_data_indices(block)
+                .chain(self.block_parity_indices(block))
+                .filter(|idx| received_shards.contains(idx))
+                .count();
+
+            received_count >= self.block_data_indices(block).count()
+        })
+    }
+}
+
+// Codec instances are cached by shard counts, since the encoding matrix is expensive to compute
+#[derive(Default)]
+pub struct FecCoder {
+    codecs: HashMap<(usize, usize), ReedSolomon>,
+}
+
+impl Clone for FecCoder {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl FecCoder {
+    fn codec(&mut self, data_shards: usize, parity_shards: usize) -> Result<&ReedSolomon> {
+        Ok(match self.codecs.entry((data_shards, parity_shards)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(ReedSolomon::new(data_shards, parity_shards)?),
+        })
+    }
+
+    // data: packet data without prefix. The parity bytes are written to each parity buffer after
+    // the shard prefix and the parity header
+    pub fn encode(
+        &mut self,
+        layout: &FecLayout,
+        data: &[u8],
+        parity_buffers: &mut [Vec<u8>],
+    ) -> Result<()> {
+        let shard_size = layout.shard_size;
+
+        // The last data shard is zero padded
+        let last_shard_start = (layout.data_shards - 1) * shard_size;
+        let mut padded_last_shard = data[last_shard_start..].to_vec();
+        padded_last_shard.resize(shard_size, 0);
+
+        for block in 0..layout.blocks {
+            let data_shards = layout
+                .block_data_indices(block)
+                .map(|idx| {
+                    if idx == layout.data_shards - 1 {
+                        &padded_last_shard[..]
+                    } else {
+                        &data[idx * shard_size..][..shard_size]
+                    }
+                })
+                .collect::<Vec<_>>();
+            let mut parity_shards = parity_buffers
+                .iter_mut()
+                .skip(block)
+                .step_by(layout.blocks)
+                .map(|buffer| &mut buffer[SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE..][..shard_size])
+                .collect::<Vec<_>>();
+
+            self.codec(data_shards.len(), parity_shards.len())?
+                .encode_sep(&data_shards, &mut parity_shards)?;
+        }
+
+        Ok(())
+    }
+
+    // Recover the missing data shards in place. buffer: whole packet buffer, including prefix.
+    pub fn reconstruct(
+        &mut self,
+        layout: &FecLayout,
+        buffer: &mut [u8],
+        received_shards: &HashSet<usize>,
+        data_size: usize,
+    ) -> Result<()> {
+        let shard_size = layout.shard_size;
+
+        if data_size > layout.data_shards * shard_size
+            || data_size + shard_size <= layout.data_shards * shard_size
+        {
+            bail!("Invalid FEC packet data size: {data_size}");
+        }
+        let required_size = received_shards
+            .iter()
+            .filter(|&&idx| idx >= layout.data_shards)
+            .map(|&idx| layout.shard_position(idx) + SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE)
+            .max()
+            .unwrap_or(0)
+            + shard_size;
+        if buffer.len() < usize::max(required_size, SHARD_PREFIX_SIZE + data_size) {
+            bail!("FEC packet buffer too small");
+        }
+
+        let data_shard_range = |idx: usize| {
+            let start = SHARD_PREFIX_SIZE + idx * shard_size;
+            start..usize::min(start + shard_size, SHARD_PREFIX_SIZE + data_size)
+        };
+
+        for block in 0..layout.blocks {
+            if layout
+                .block_data_indices(block)
+                .all(|idx| received_shards.contains(&idx))
+            {
+                continue;
+            }
+
+            let mut shards = layout
+                .block_data_indices(block)
+                .map(|idx| {
+                    received_shards.contains(&idx).then(|| {
+                        let mut shard = buffer[data_shard_range(idx)].to_vec();
+                        shard.resize(shard_size, 0);
+                        shard
+                    })
+                })
+                .chain(layout.block_parity_indices(block).map(|idx| {
+                    received_shards.contains(&idx).then(|| {
+                        let start =
+                            layout.shard_position(idx) + SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE;
+                        buffer[start..][..shard_size].to_vec()
+                    })
+                }))
+                .collect::<Vec<_>>();
+
+            let data_shards_count = layout.block_data_indices(block).count();
+            self.codec(data_shards_count, shards.len() - data_shards_count)?
+                .reconstruct_data(&mut shards)?;
+
+            for (idx, shard) in layout.block_data_indices(block).zip(shards) {
+                if !received_shards.contains(&idx) {
+                    let range = data_shard_range(idx);
+                    // reconstruct_data() fills every missing data shard when it succeeds
+                    let shard = shard.unwrap();
+                    buffer[range.clone()].copy_from_slice(&shard[..range.len()]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_data(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    // Encode the packet and lay out data and parity shards like the receive buffer
+    fn encode_packet(coder: &mut FecCoder, layout: &FecLayout, data: &[u8]) -> Vec<u8> {
+        let mut parity_buffers =
+            vec![
+                vec![0; SHARD_PREFIX_SIZE + PARITY_HEADER_SIZE + layout.shard_size];
+                layout.parity_shards
+            ];
+        coder.encode(layout, data, &mut parity_buffers).unwrap();
+
+        let total_shards = layout.data_shards + layout.parity_shards;
+        let mut buffer = vec![0; SHARD_PREFIX_SIZE + layout.shard_position(total_shards)];
+        buffer[SHARD_PREFIX_SIZE..][..data.len()].copy_from_slice(data);
+        for (idx, parity_buffer) in parity_buffers.iter().enumerate() {
+            let start = SHARD_PREFIX_SIZE + layout.shard_position(layout.data_shards + idx);
+            buffer[start..][..PARITY_HEADER_SIZE + layout.shard_size]
+                .copy_from_slice(&parity_buffer[SHARD_PREFIX_SIZE..]);
+        }
+
+        buffer
+    }
+
+    // Zero the lost shards and return the indices of the received ones
+    fn drop_shards(layout: &FecLayout, buffer: &mut [u8], lost: &[usize]) -> HashSet<usize> {
+        for &idx in lost {
+            let start = SHARD_PREFIX_SIZE + layout.shard_position(idx);
+            let end = SHARD_PREFIX_SIZE + layout.shard_position(idx + 1);
+            buffer[start..end].fill(0);
+        }
+
+        (0..layout.data_shards + layout.parity_shards)
+            .filter(|idx| !lost.contains(idx))
+            .collect()
+    }
+
+    #[test]
+    fn test_reconstruct_single_block() {
+        let data = test_data(950);
+        let layout = FecLayout::new(10, 100, 0.3);
+        assert_eq!(layout.parity_shards, 3);
+
+        let mut coder = FecCoder::default();
+        let mut buffer = encode_packet(&mut coder, &layout, &data);
+
+        // Lose as many data shards as parity shards, including the partial last one
+        let received = drop_shards(&layout, &mut buffer, &[0, 4, 9]);
+        assert!(layout.can_reconstruct(&received));
+
+        coder
+            .reconstruct(&layout, &mut buffer, &received, data.len())
+            .unwrap();
+        assert_eq!(&buffer[SHARD_PREFIX_SIZE..][..data.len()], &data[..]);
+    }
+
+    #[test]
+    fn test_reconstruct_with_lost_parity() {
+        let data = test_data(1000);
+        let layout = FecLayout::new(10, 100, 0.3);
+
+        let mut coder = FecCoder::default();
+        let mut buffer = encode_packet(&mut coder, &layout, &data);
+
+        let received = drop_shards(&layout, &mut buffer, &[2, 10, 12]);
+        assert!(layout.can_reconstruct(&received));
+
+        coder
+            .reconstruct(&layout, &mut buffer, &received, data.len())
+            .unwrap();
+        assert_eq!(&buffer[SHARD_PREFIX_SIZE..][..data.len()], &data[..]);
+    }
+
+    #[test]
+    fn test_too_many_lost_shards() {
+        let layout = FecLayout::new(10, 100, 0.3);
+
+        let received = (0..13).filter(|idx| ![1, 2, 3, 4].contains(idx)).collect();
+        assert!(!layout.can_reconstruct(&received));
+    }
+
+    #[test]
+    fn test_no_fec() {
+        let layout = FecLayout::new(10, 100, 0.0);
+        assert_eq!(layout.parity_shards, 0);
+
+        let received = (0..10).collect();
+        assert!(!layout.can_reconstruct(&received));
+    }
+
+    #[test]
+    fn test_reconstruct_interleaved_blocks() {
+        let shard_size = 16;
+        let data = test_data(399 * shard_size + 5);
+        let layout = FecLayout::new(400, shard_size, 0.05);
+        // 3 blocks, the largest with 134 data shards, ceil(134 * 0.05) = 7 parity shards each
+        assert_eq!(layout.blocks, 3);
+        assert_eq!(layout.parity_shards, 21);
+
+        let mut coder = FecCoder::default();
+        let mut buffer = encode_packet(&mut coder, &layout, &data);
+
+        // A burst of consecutive lost shards is spread evenly across blocks
+        let burst = (100..121).collect::<Vec<_>>();
+        let received = drop_shards(&layout, &mut buffer, &burst);
+        assert!(layout.can_reconstruct(&received));
+
+        coder
+            .reconstruct(&layout, &mut buffer, &received, data.len())
+            .unwrap();
+        assert_eq!(&buffer[SHARD_PREFIX_SIZE..][..data.len()], &data[..]);
+
+        // One more lost shard exceeds the parity of one block
+        let received = (0..421).filter(|idx| !(100..122).contains(idx)).collect();
+        assert!(!layout.can_reconstruct(&received));
+    }
+
+    #[test]
+    fn test_shards_count_packing() {
+        let layout = FecLayout::new(400, 16, 0.05);
+
+        let field = pack_shards_count(layout.data_shards, layout.parity_shards);
+        assert_eq!(field, 400 | (21 << 16));
+
+        // The receiver rebuilds the same layout from the packed counts
+        let (data_shards, parity_shards) = unpack_shards_count(field);
+        let receiver_layout = FecLayout::from_counts(data_shards, parity_shards, 16);
+        assert_eq!(receiver_layout.data_shards, 400);
+        assert_eq!(receiver_layout.parity_shards, 21);
+        assert_eq!(receiver_layout.blocks, layout.blocks);
+
+        assert_eq!(
+            unpack_shards_count(pack_shards_count(0xFFFF, 0)),
+            (0xFFFF, 0)
+        );
+        assert_eq!(
+            unpack_shards_count(pack_shards_count(1, 0xFFFF)),
+            (1, 0xFFFF)
+        );
+    }
+}
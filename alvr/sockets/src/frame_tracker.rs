@@ -0,0 +1,191 @@
+use std::{
+    sync::atomic::{self, AtomicU32, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+// Number of latest packets tracked. Older packets are overwritten
+const FRAME_TRACKER_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrameSendInfo {
+    pub send_start: Instant,
+    pub send_end: Instant,
+    // includes parity shards
+    pub shards_count: usize,
+}
+
+impl FrameSendInfo {
+    pub fn send_duration(&self) -> Duration {
+        self.send_end.saturating_duration_since(self.send_start)
+    }
+}
+
+struct Slot {
+    // Seqlock: odd while the slot is being written, 0 if the slot was never written
+    sequence: AtomicU32,
+    packet_index: AtomicU32,
+    // relative to the reference time
+    send_start_ns: AtomicU64,
+    send_end_ns: AtomicU64,
+    shards_count: AtomicU32,
+}
+
+// Send times of the latest packets of a stream, indexed by packet index modulo the buffer size.
+// It is written only by the StreamSender that owns it and can be read from any thread without
+// locks or allocations.
+pub struct FrameTracker {
+    reference_time: Instant,
+    slots: Box<[Slot]>,
+}
+
+impl FrameTracker {
+    pub(crate) fn new(reference_time: Instant) -> Self {
+        Self {
+            reference_time,
+            slots: (0..FRAME_TRACKER_SIZE)
+                .map(|_| Slot {
+                    sequence: AtomicU32::new(0),
+                    packet_index: AtomicU32::new(0),
+                    send_start_ns: AtomicU64::new(0),
+                    send_end_ns: AtomicU64::new(0),
+                    shards_count: AtomicU32::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    fn to_ns(&self, instant: Instant) -> u64 {
+        instant
+            .saturating_duration_since(self.reference_time)
+            .as_nanos() as u64
+    }
+
+    // Must not be called concurrently
+    pub(crate) fn insert(
+        &self,
+        packet_index: u32,
+        send_start: Instant,
+        send_end: Instant,
+        shards_count: usize,
+    ) {
+        let slot = &self.slots[packet_index as usize % FRAME_TRACKER_SIZE];
+
+        let sequence = slot.sequence.load(Ordering::Relaxed);
+        slot.sequence
+            .store(sequence.wrapping_add(1), Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        slot.packet_index.store(packet_index, Ordering::Relaxed);
+        slot.send_start_ns
+            .store(self.to_ns(send_start), Ordering::Relaxed);
+        slot.send_end_ns
+            .store(self.to_ns(send_end), Ordering::Relaxed);
+        slot.shards_count
+            .store(shards_count as u32, Ordering::Relaxed);
+
+        // Skip 0 on wrap around, it marks a slot never written
+        let next_sequence = match sequence.wrapping_add(2) {
+            0 => 2,
+            sequence => sequence,
+        };
+        slot.sequence.store(next_sequence, Ordering::Release);
+    }
+
+    // Returns None if the packet was not sent or has been overwritten by a newer packet
+    pub fn get(&self, packet_index: u32) -> Option<FrameSendInfo> {
+        let slot = &self.slots[packet_index as usize % FRAME_TRACKER_SIZE];
+
+        loop {
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            if sequence == 0 {
+                return None;
+            }
+            if sequence % 2 == 1 {
+                // The writer updates each slot once per packet, it will be done shortly
+                std::hint::spin_loop();
+                continue;
+            }
+
+            let stored_index = slot.packet_index.load(Ordering::Relaxed);
+            let send_start_ns = slot.send_start_ns.load(Ordering::Relaxed);
+            let send_end_ns = slot.send_end_ns.load(Ordering::Relaxed);
+            let shards_count = slot.shards_count.load(Ordering::Relaxed);
+
+            atomic::fence(Ordering::Acquire);
+            if slot.sequence.load(Ordering::Relaxed) != sequence {
+                continue;
+            }
+
+            return (stored_index == packet_index).then(|| FrameSendInfo {
+                send_start: self.reference_time + Duration::from_nanos(send_start_ns),
+                send_end: self.reference_time + Duration::from_nanos(send_end_ns),
+                shards_count: shards_count as usize,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_after_insert() {
+        let reference_time = Instant::now();
+        let tracker = FrameTracker::new(reference_time);
+
+        let send_start = reference_time + Duration::from_millis(10);
+        let send_end = send_start + Duration::from_micros(1500);
+        tracker.insert(7, send_start, send_end, 12);
+
+        let info = tracker.get(7).unwrap();
+        assert_eq!(info.send_start, send_start);
+        assert_eq!(info.send_end, send_end);
+        assert_eq!(info.send_duration(), Duration::from_micros(1500));
+        assert_eq!(info.shards_count, 12);
+
+        assert!(tracker.get(8).is_none());
+    }
+
+    #[test]
+    fn test_overwrite_after_wrap_around() {
+        let reference_time = Instant::now();
+        let tracker = FrameTracker::new(reference_time);
+
+        for packet_index in 0..=FRAME_TRACKER_SIZE as u32 {
+            let send_start = reference_time + Duration::from_millis(packet_index as u64);
+            tracker.insert(packet_index, send_start, send_start, packet_index as usize);
+        }
+
+        // Packet 0 shares the slot with the newest packet
+        assert!(tracker.get(0).is_none());
+        let info = tracker.get(FRAME_TRACKER_SIZE as u32).unwrap();
+        assert_eq!(info.shards_count, FRAME_TRACKER_SIZE);
+        assert_eq!(
+            info.send_start,
+            reference_time + Duration::from_millis(FRAME_TRACKER_SIZE as u64)
+        );
+
+        // The other packets are still available
+        assert_eq!(tracker.get(1).unwrap().shards_count, 1);
+        assert_eq!(
+            tracker
+                .get(FRAME_TRACKER_SIZE as u32 - 1)
+                .unwrap()
+                .shards_count,
+            FRAME_TRACKER_SIZE - 1
+        );
+    }
+
+    #[test]
+    fn test_packet_index_wrap_around() {
+        let reference_time = Instant::now();
+        let tracker = FrameTracker::new(reference_time);
+
+        tracker.insert(u32::MAX, reference_time, reference_time, 1);
+        tracker.insert(0, reference_time, reference_time, 2);
+
+        assert_eq!(tracker.get(u32::MAX).unwrap().shards_count, 1);
+        assert_eq!(tracker.get(0).unwrap().shards_count, 2);
+    }
+}
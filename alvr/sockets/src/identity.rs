@@ -0,0 +1,224 @@
+// Authentication of the clients on the control socket. Each client owns a persistent Ed25519
+// keypair. The server sends a random challenge, the client signs it together with its identity key,
+// its ephemeral stream key and the hash of its QUIC certificate, so that the stream encryption keys
+// and the QUIC connection are bound to the identity of the client. The server checks the signature
+// against the key pinned for the client hostname.
+
+use alvr_common::anyhow::{bail, Result};
+use alvr_packets::{AuthenticationChallenge, AuthenticationResponse};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::{rngs::OsRng, RngCore};
+
+const CHALLENGE_CONTEXT: &[u8] = b"alvr client authentication";
+
+fn signed_message(
+    challenge: &AuthenticationChallenge,
+    identity_public_key: &[u8; 32],
+    stream_public_key: &[u8; 32],
+    quic_certificate_hash: &[u8; 32],
+) -> Vec<u8> {
+    [
+        CHALLENGE_CONTEXT,
+        &challenge.nonce[..],
+        &identity_public_key[..],
+        &stream_public_key[..],
+        &quic_certificate_hash[..],
+    ]
+    .concat()
+}
+
+pub struct ClientIdentity {
+    signing_key: SigningKey,
+}
+
+impl ClientIdentity {
+    pub fn generate_secret_key() -> [u8; 32] {
+        let mut secret_key = [0; 32];
+        OsRng.fill_bytes(&mut secret_key);
+
+        secret_key
+    }
+
+    pub fn from_secret_key(secret_key: &[u8; 32]) -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(secret_key),
+        }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    pub fn sign_challenge(
+        &self,
+        challenge: &AuthenticationChallenge,
+        stream_public_key: &[u8; 32],
+        quic_certificate_hash: &[u8; 32],
+    ) -> AuthenticationResponse {
+        let message = signed_message(
+            challenge,
+            &self.public_key(),
+            stream_public_key,
+            quic_certificate_hash,
+        );
+
+        AuthenticationResponse {
+            signature: self.signing_key.sign(&message).to_bytes().to_vec(),
+        }
+    }
+}
+
+pub fn new_authentication_challenge() -> AuthenticationChallenge {
+    let mut nonce = [0; 32];
+    OsRng.fill_bytes(&mut nonce);
+
+    AuthenticationChallenge { nonce }
+}
+
+pub fn verify_authentication_response(
+    challenge: &AuthenticationChallenge,
+    response: &AuthenticationResponse,
+    identity_public_key: &[u8; 32],
+    stream_public_key: &[u8; 32],
+    quic_certificate_hash: &[u8; 32],
+) -> Result<()> {
+    let verifying_key = VerifyingKey::from_bytes(identity_public_key)?;
+    let signature = Signature::from_slice(&response.signature)?;
+
+    let message = signed_message(
+        challenge,
+        identity_public_key,
+        stream_public_key,
+        quic_certificate_hash,
+    );
+    if verifying_key.verify_strict(&message, &signature).is_err() {
+        bail!("Invalid signature");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_is_persistent() {
+        let secret_key = ClientIdentity::generate_secret_key();
+
+        assert_eq!(
+            ClientIdentity::from_secret_key(&secret_key).public_key(),
+            ClientIdentity::from_secret_key(&secret_key).public_key()
+        );
+        assert_ne!(secret_key, ClientIdentity::generate_secret_key());
+    }
+
+    #[test]
+    fn test_verify_response() {
+        let identity = ClientIdentity::from_secret_key(&ClientIdentity::generate_secret_key());
+        let identity_public_key = identity.public_key();
+        let stream_public_key = [7; 32];
+        let quic_certificate_hash = [9; 32];
+        let challenge = new_authentication_challenge();
+
+        let response =
+            identity.sign_challenge(&challenge, &stream_public_key, &quic_certificate_hash);
+        assert_eq!(response.signature.len(), 64);
+
+        let verify = |challenge: &AuthenticationChallenge,
+                      response: &AuthenticationResponse,
+                      identity_public_key: &[u8; 32],
+                      stream_public_key: &[u8; 32],
+                      quic_certificate_hash: &[u8; 32]| {
+            verify_authentication_response(
+                challenge,
+                response,
+                identity_public_key,
+                stream_public_key,
+                quic_certificate_hash,
+            )
+            .is_ok()
+        };
+
+        assert!(verify(
+            &challenge,
+            &response,
+            &identity_public_key,
+            &stream_public_key,
+            &quic_certificate_hash
+        ));
+
+        // Every signed field is bound to the signature
+        let mut other_nonce = challenge.nonce;
+        other_nonce[31] ^= 0x80;
+        assert!(!verify(
+            &AuthenticationChallenge { nonce: other_nonce },
+            &response,
+            &identity_public_key,
+            &stream_public_key,
+            &quic_certificate_hash
+        ));
+        let mut other_stream_public_key = stream_public_key;
+        other_stream_public_key[0] ^= 1;
+        assert!(!verify(
+            &challenge,
+            &response,
+            &identity_public_key,
+            &other_stream_public_key,
+            &quic_certificate_hash
+        ));
+        let mut other_quic_certificate_hash = quic_certificate_hash;
+        other_quic_certificate_hash[0] ^= 1;
+        assert!(!verify(
+            &challenge,
+            &response,
+            &identity_public_key,
+            &stream_public_key,
+            &other_quic_certificate_hash
+        ));
+        let other_identity =
+            ClientIdentity::from_secret_key(&ClientIdentity::generate_secret_key());
+        assert!(!verify(
+            &challenge,
+            &response,
+            &other_identity.public_key(),
+            &stream_public_key,
+            &quic_certificate_hash
+        ));
+
+        let mut truncated_response = AuthenticationResponse {
+            signature: response.signature.clone(),
+        };
+        truncated_response.signature.pop();
+        assert!(!verify(
+            &challenge,
+            &truncated_response,
+            &identity_public_key,
+            &stream_public_key,
+            &quic_certificate_hash
+        ));
+    }
+
+    // A response captured on a previous connection can't be replayed, the nonce is random
+    #[test]
+    fn test_replayed_response() {
+        let identity = ClientIdentity::from_secret_key(&ClientIdentity::generate_secret_key());
+        let stream_public_key = [7; 32];
+        let quic_certificate_hash = [9; 32];
+
+        let old_challenge = new_authentication_challenge();
+        let old_response =
+            identity.sign_challenge(&old_challenge, &stream_public_key, &quic_certificate_hash);
+
+        let challenge = new_authentication_challenge();
+        assert_ne!(challenge.nonce, old_challenge.nonce);
+        assert!(verify_authentication_response(
+            &challenge,
+            &old_response,
+            &identity.public_key(),
+            &stream_public_key,
+            &quic_certificate_hash,
+        )
+        .is_err());
+    }
+}
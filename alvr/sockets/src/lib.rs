@@ -1,5 +1,10 @@
 mod backend;
+mod buffer_pool;
 mod control_socket;
+mod fec;
+mod frame_tracker;
+mod identity;
+mod pacer;
 mod stream_socket;
 
 use alvr_common::{anyhow::Result, info};
@@ -10,12 +15,19 @@ use std::{
     time::Duration,
 };
 
+pub use backend::{
+    encryption::{KeyExchange, StreamKeys},
+    impairment::NetworkImpairment,
+    quic::QuicCertificate,
+};
 pub use control_socket::*;
+pub use frame_tracker::{FrameSendInfo, FrameTracker};
+pub use identity::*;
 pub use stream_socket::*;
 
 pub const LOCAL_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
 pub const CONTROL_PORT: u16 = 9943;
-pub const HANDSHAKE_PACKET_SIZE_BYTES: usize = 56; // this may change in future protocols
+pub const HANDSHAKE_PACKET_SIZE_BYTES: usize = 88; // this may change in future protocols
 pub const KEEPALIVE_INTERVAL: Duration = Duration::from_millis(500);
 pub const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(2);
 
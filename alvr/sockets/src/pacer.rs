@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+// Token bucket used to spread the shards of a packet over time instead of sending them in a
+// burst. Tokens are bytes. The token count can become negative, in which case the caller must
+// wait for the debt to be repaid.
+pub struct Pacer {
+    rate_bytes_per_sec: f64,
+    bucket_size: f64,
+    tokens: f64,
+    last_refill_instant: Instant,
+}
+
+impl Pacer {
+    pub fn new(now: Instant, rate_bps: f64, bucket_size: usize) -> Self {
+        Self {
+            rate_bytes_per_sec: rate_bps / 8.0,
+            bucket_size: bucket_size as f64,
+            tokens: bucket_size as f64,
+            last_refill_instant: now,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate_bps: f64) {
+        self.rate_bytes_per_sec = rate_bps / 8.0;
+    }
+
+    pub fn rate_bps(&self) -> f64 {
+        self.rate_bytes_per_sec * 8.0
+    }
+
+    // Give back the tokens of data that has not been sent
+    pub fn refund(&mut self, size: usize) {
+        self.tokens = f64::min(self.tokens + size as f64, self.bucket_size);
+    }
+
+    // Consume the tokens for `size` bytes. Returns the time to wait before sending them
+    pub fn reserve(&mut self, now: Instant, size: usize) -> Duration {
+        if self.rate_bytes_per_sec <= 0.0 {
+            return Duration::ZERO;
+        }
+
+        let elapsed_s = now
+            .saturating_duration_since(self.last_refill_instant)
+            .as_secs_f64();
+        self.tokens = f64::min(
+            self.tokens + elapsed_s * self.rate_bytes_per_sec,
+            self.bucket_size,
+        );
+        self.last_refill_instant = now;
+
+        self.tokens -= size as f64;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate_bytes_per_sec)
+        }
+    }
+}
@@ -16,11 +16,22 @@
 // Note: We can't clone the underlying socket for each StreamSender and the mutex around the socket
 // cannot be removed. This is because we need to make sure at least shards are written whole.
 
-use crate::backend::{tcp, udp, SocketReader, SocketWriter};
+use crate::{
+    backend::{
+        encryption::{self, StreamKeys},
+        impairment::{self, NetworkImpairment},
+        quic, tcp, udp, SocketReader, SocketWriter,
+    },
+    buffer_pool::BufferPool,
+    fec::{self, FecCoder, FecLayout},
+    frame_tracker::FrameTracker,
+    pacer::Pacer,
+};
 use alvr_common::{
-    anyhow::Result, debug, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+    anyhow::Result, con_bail, debug, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain,
+    SharedClock, ToCon,
 };
-use alvr_packets::VIDEO;
+use alvr_packets::{NackPacket, ShardLossReport, PROBE, VIDEO};
 use alvr_session::{DscpTos, SocketBufferSize, SocketProtocol};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -30,11 +41,18 @@ use std::{
     mem,
     net::{IpAddr, TcpListener, UdpSocket},
     sync::{mpsc, Arc},
+    thread,
     time::{Duration, Instant},
 };
 
 const Q_KALMAN: f32 = 10E-8;
 
+// Limit the size of a NACK request for a single packet
+const MAX_NACK_SHARDS: usize = 512;
+
+// Shards sent with a single lock of the socket, when the socket supports batching
+const MAX_BATCH_SHARDS: usize = 32;
+
 pub struct KalmanFilter {
     ow_delay: f32,
     m_current: f32,
@@ -65,19 +83,179 @@ impl Default for KalmanFilter {
     }
 }
 
-const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
+const V1_SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
     + mem::size_of::<u16>() // stream ID
     + mem::size_of::<u32>() // packet index
-    + mem::size_of::<u32>() // shards count
+    + mem::size_of::<u32>() // shards count (data shards: low 16 bits, FEC parity shards: high 16 bits)
     + mem::size_of::<u32>() // shards index
     + mem::size_of::<f32>(); // tx relative timestamp
 
+const V2_SHARD_PREFIX_SIZE: usize = mem::size_of::<u8>() // version
+    + mem::size_of::<u8>() // flags
+    + mem::size_of::<u16>() // shard length, including the prefix
+    + mem::size_of::<u16>() // stream ID
+    + mem::size_of::<u32>() // packet index
+    + mem::size_of::<u32>() // shards count (data shards: low 16 bits, FEC parity shards: high 16 bits)
+    + mem::size_of::<u32>() // shards index
+    + mem::size_of::<u32>() // tx relative timestamp in microseconds (wrapping)
+    + mem::size_of::<u32>(); // packet data size
+
+// Space reserved for the shard prefix in the packet buffers. Shorter prefixes are written right
+// before the shard data, so the data layout does not depend on the prefix version.
+pub(crate) const SHARD_PREFIX_SIZE: usize = V2_SHARD_PREFIX_SIZE;
+
+const SHARD_FLAG_IDR: u8 = 1 << 0;
+const SHARD_FLAG_PARITY: u8 = 1 << 1;
+const SHARD_FLAG_RETRANSMISSION: u8 = 1 << 2;
+const SHARD_FLAG_PROBE: u8 = 1 << 3;
+
+/// Layout of the prefix of each shard. The version is negotiated during the handshake.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShardHeaderVersion {
+    /// Big endian, timestamp as f32 seconds. Used by peers that don't negotiate the version
+    V1,
+    /// Little endian, with flags, timestamp in microseconds and packet data size
+    V2,
+}
+
+impl ShardHeaderVersion {
+    pub const LATEST: Self = Self::V2;
+
+    pub fn from_number(number: u64) -> Option<Self> {
+        match number {
+            1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+
+    fn prefix_size(self) -> usize {
+        match self {
+            Self::V1 => V1_SHARD_PREFIX_SIZE,
+            Self::V2 => V2_SHARD_PREFIX_SIZE,
+        }
+    }
+
+    // Position of the prefix inside the space reserved at the start of each shard
+    fn prefix_offset(self) -> usize {
+        SHARD_PREFIX_SIZE - self.prefix_size()
+    }
+
+    // Maximum shard size on the wire, including the prefix
+    fn max_packet_size(self, packet_size: usize) -> usize {
+        match self {
+            // +4 is a workaround to retain compatibilty with the old protocol, where the shard
+            // length field does not count itself
+            Self::V1 => packet_size + 4,
+            Self::V2 => packet_size,
+        }
+    }
+}
+
+struct ShardPrefix {
+    shard_length: usize, // contains prefix length itself
+    stream_id: u16,
+    packet_index: u32,
+    shards_count_field: u32,
+    shard_index: usize,
+    tx_r_instant: f64,
+    // Not sent with V1
+    flags: u8,
+    data_size: Option<usize>,
+}
+
+fn write_shard_prefix(version: ShardHeaderVersion, sub_buffer: &mut [u8], prefix: &ShardPrefix) {
+    match version {
+        ShardHeaderVersion::V1 => {
+            sub_buffer[0..4].copy_from_slice(
+                &((prefix.shard_length - mem::size_of::<u32>()) as u32).to_be_bytes(),
+            );
+            sub_buffer[4..6].copy_from_slice(&prefix.stream_id.to_be_bytes());
+            sub_buffer[6..10].copy_from_slice(&prefix.packet_index.to_be_bytes());
+            sub_buffer[10..14].copy_from_slice(&prefix.shards_count_field.to_be_bytes());
+            sub_buffer[14..18].copy_from_slice(&(prefix.shard_index as u32).to_be_bytes());
+            sub_buffer[18..22].copy_from_slice(&(prefix.tx_r_instant as f32).to_be_bytes());
+        }
+        ShardHeaderVersion::V2 => {
+            sub_buffer[0] = version.number();
+            sub_buffer[1] = prefix.flags;
+            sub_buffer[2..4].copy_from_slice(&(prefix.shard_length as u16).to_le_bytes());
+            sub_buffer[4..6].copy_from_slice(&prefix.stream_id.to_le_bytes());
+            sub_buffer[6..10].copy_from_slice(&prefix.packet_index.to_le_bytes());
+            sub_buffer[10..14].copy_from_slice(&prefix.shards_count_field.to_le_bytes());
+            sub_buffer[14..18].copy_from_slice(&(prefix.shard_index as u32).to_le_bytes());
+            sub_buffer[18..22]
+                .copy_from_slice(&((prefix.tx_r_instant * 1e6) as u64 as u32).to_le_bytes());
+            sub_buffer[22..26]
+                .copy_from_slice(&(prefix.data_size.unwrap_or(0) as u32).to_le_bytes());
+        }
+    }
+}
+
+// rx_r_instant is used to unwrap the V2 timestamp. It works as long as the difference between the
+// relative send and receive times is less than half the wrapping period (~35 minutes).
+fn read_shard_prefix(
+    version: ShardHeaderVersion,
+    bytes: &[u8],
+    rx_r_instant: f64,
+) -> ConResult<ShardPrefix> {
+    Ok(match version {
+        ShardHeaderVersion::V1 => ShardPrefix {
+            shard_length: mem::size_of::<u32>()
+                + u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize,
+            stream_id: u16::from_be_bytes(bytes[4..6].try_into().unwrap()),
+            packet_index: u32::from_be_bytes(bytes[6..10].try_into().unwrap()),
+            shards_count_field: u32::from_be_bytes(bytes[10..14].try_into().unwrap()),
+            shard_index: u32::from_be_bytes(bytes[14..18].try_into().unwrap()) as usize,
+            tx_r_instant: f32::from_be_bytes(bytes[18..22].try_into().unwrap()) as f64,
+            flags: 0,
+            data_size: None,
+        },
+        ShardHeaderVersion::V2 => {
+            if bytes[0] != version.number() {
+                con_bail!("Unexpected shard header version {}", bytes[0]);
+            }
+
+            let tx_r_micros = u32::from_le_bytes(bytes[18..22].try_into().unwrap());
+            let rx_r_micros = (rx_r_instant * 1e6) as u64 as u32;
+
+            ShardPrefix {
+                shard_length: u16::from_le_bytes(bytes[2..4].try_into().unwrap()) as usize,
+                stream_id: u16::from_le_bytes(bytes[4..6].try_into().unwrap()),
+                packet_index: u32::from_le_bytes(bytes[6..10].try_into().unwrap()),
+                shards_count_field: u32::from_le_bytes(bytes[10..14].try_into().unwrap()),
+                shard_index: u32::from_le_bytes(bytes[14..18].try_into().unwrap()) as usize,
+                tx_r_instant: rx_r_instant
+                    - rx_r_micros.wrapping_sub(tx_r_micros) as i32 as f64 / 1e6,
+                flags: bytes[1],
+                data_size: Some(u32::from_le_bytes(bytes[22..26].try_into().unwrap()) as usize),
+            }
+        }
+    })
+}
+
+fn send_shard(socket: &mut dyn SocketWriter, unreliable: bool, shard: &[u8]) -> Result<()> {
+    if unreliable {
+        socket.send_unreliable(shard)
+    } else {
+        socket.send(shard)
+    }
+}
+
 /// Memory buffer that contains a hidden prefix
 #[derive(Default)]
 pub struct Buffer<H = ()> {
     inner: Vec<u8>,
     hidden_offset: usize, // this corresponds to prefix + header
     length: usize,
+    flags: u8,
     _phantom: PhantomData<H>,
 }
 
@@ -116,41 +294,130 @@ impl<H> Buffer<H> {
         self.inner.resize(self.hidden_offset + length, 0);
         self.length = length;
     }
+
+    /// Flag the shards of this packet as part of an IDR frame. Not signaled with shard header V1
+    pub fn set_idr(&mut self, is_idr: bool) {
+        if is_idr {
+            self.flags |= SHARD_FLAG_IDR;
+        } else {
+            self.flags &= !SHARD_FLAG_IDR;
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct FrameTracker {
-    // Struct to store frame index and transmission instant pairs
-    map: HashMap<u32, Instant>,
-    queue: VecDeque<u32>,
-    max_size: usize,
+struct CachedPacket {
+    packet_index: u32,
+    buffer: Vec<u8>,
+    size: usize, // contains prefix
+    shard_data_size: usize,
+    shards_count: usize, // data shards only
+    shards_count_field: u32,
+    flags: u8,
 }
 
-impl FrameTracker {
-    fn new() -> Self {
-        FrameTracker {
-            map: HashMap::new(),
-            queue: VecDeque::new(),
-            max_size: 256,
-        }
+struct RetransmissionCache {
+    packets: VecDeque<CachedPacket>,
+    max_packets: usize,
+}
+
+impl RetransmissionCache {
+    // Returns the buffer of the evicted packet, if any
+    fn insert(&mut self, packet: CachedPacket) -> Option<Vec<u8>> {
+        self.packets.push_back(packet);
+
+        (self.packets.len() > self.max_packets)
+            .then(|| self.packets.pop_front().map(|packet| packet.buffer))
+            .flatten()
     }
-    fn insert(&mut self, frame_id: u32, instant: Instant) {
-        self.map.insert(frame_id, instant);
-        self.queue.push_back(frame_id);
+}
 
-        // Drop oldest pairs if size exceeds max_size
-        while self.queue.len() > self.max_size {
-            if let Some(oldest_frame_id) = self.queue.pop_front() {
-                self.map.remove(&oldest_frame_id);
+/// Resends shards of the packets recently sent by a StreamSender, in response to NACKs.
+#[derive(Clone)]
+pub struct StreamRetransmitter {
+    clock: SharedClock,
+    inner: Arc<Mutex<Box<dyn SocketWriter>>>,
+    stream_id: u16,
+    unreliable: bool,
+    shard_header_version: ShardHeaderVersion,
+    reference_time: Instant,
+    cache: Arc<Mutex<RetransmissionCache>>,
+}
+
+impl StreamRetransmitter {
+    /// Send again the requested data shards of a packet, if it is still cached. FEC parity shards
+    /// are not cached. Returns the number of retransmitted shards.
+    pub fn retransmit(&self, packet_index: u32, shard_indices: &[u32]) -> Result<usize> {
+        let mut cache = self.cache.lock();
+        let Some(packet) = cache
+            .packets
+            .iter_mut()
+            .find(|packet| packet.packet_index == packet_index)
+        else {
+            return Ok(0);
+        };
+
+        let tx_r_instant = self
+            .clock
+            .now()
+            .duration_since(self.reference_time)
+            .as_secs_f64();
+        let prefix_size = self.shard_header_version.prefix_size();
+
+        let mut count = 0;
+        for &shard_index in shard_indices {
+            let shard_index = shard_index as usize;
+            if shard_index >= packet.shards_count {
+                continue;
             }
+
+            let packet_start_position =
+                shard_index * packet.shard_data_size + self.shard_header_version.prefix_offset();
+            let packet_length = usize::min(
+                prefix_size + packet.shard_data_size,
+                packet.size - packet_start_position,
+            );
+            let sub_buffer = &mut packet.buffer[packet_start_position..];
+
+            // The prefix overwrites the end of the previous shard, which could be requested again
+            let mut backup = [0; SHARD_PREFIX_SIZE];
+            backup[..prefix_size].copy_from_slice(&sub_buffer[..prefix_size]);
+            write_shard_prefix(
+                self.shard_header_version,
+                sub_buffer,
+                &ShardPrefix {
+                    shard_length: packet_length,
+                    stream_id: self.stream_id,
+                    packet_index,
+                    shards_count_field: packet.shards_count_field,
+                    shard_index,
+                    tx_r_instant,
+                    flags: packet.flags | SHARD_FLAG_RETRANSMISSION,
+                    data_size: Some(packet.size - SHARD_PREFIX_SIZE),
+                },
+            );
+            let res = send_shard(
+                &mut **self.inner.lock(),
+                self.unreliable,
+                &sub_buffer[..packet_length],
+            );
+            sub_buffer[..prefix_size].copy_from_slice(&backup[..prefix_size]);
+            res?;
+
+            count += 1;
         }
+
+        Ok(count)
     }
 }
 
 #[derive(Clone)]
 pub struct StreamSender<H> {
+    clock: SharedClock,
     inner: Arc<Mutex<Box<dyn SocketWriter>>>,
     stream_id: u16,
+    // Shards may be lost, on transports with an unreliable channel
+    unreliable: bool,
+    shard_header_version: ShardHeaderVersion,
     max_packet_size: usize,
     // if the packet index overflows the worst that happens is a false positive packet loss
     next_packet_index: u32,
@@ -160,7 +427,18 @@ pub struct StreamSender<H> {
     shards_count: usize,
     reference_time: Instant,
 
-    frame_tracker: FrameTracker,
+    frame_tracker: Arc<FrameTracker>,
+
+    fec_overhead_ratio: f32,
+    fec_coder: FecCoder,
+    parity_buffers: Vec<Vec<u8>>,
+
+    retransmission_cache: Option<Arc<Mutex<RetransmissionCache>>>,
+    prefix_backups: Vec<[u8; SHARD_PREFIX_SIZE]>,
+    batch_prefixes: Vec<[u8; SHARD_PREFIX_SIZE]>,
+
+    pacer: Option<Pacer>,
+    last_send_duration: Duration,
 }
 
 impl<H> StreamSender<H> {
@@ -170,56 +448,322 @@ impl<H> StreamSender<H> {
     pub fn get_last_packet_id(&self) -> u32 {
         self.next_packet_index - 1
     }
-    pub fn get_frame_tracker_map(&self) -> HashMap<u32, Instant> {
-        self.frame_tracker.map.clone()
+    /// Send times of the latest packets, readable from other threads
+    pub fn frame_tracker(&self) -> Arc<FrameTracker> {
+        Arc::clone(&self.frame_tracker)
+    }
+
+    /// Time between the start of the transmission of the first shard and the end of the
+    /// transmission of the last shard of the last packet
+    pub fn get_last_send_duration(&self) -> Duration {
+        self.last_send_duration
+    }
+
+    /// Spread the shards of each packet at the given rate. The bucket size is the maximum number
+    /// of shards sent back-to-back. None disables pacing.
+    pub fn set_pacing_rate(&mut self, rate_bps: Option<f64>, bucket_shards: usize) {
+        match (rate_bps, &mut self.pacer) {
+            (Some(rate_bps), Some(pacer)) => pacer.set_rate(rate_bps),
+            (Some(rate_bps), None) => {
+                self.pacer = Some(Pacer::new(
+                    self.clock.now(),
+                    rate_bps,
+                    bucket_shards * self.max_packet_size,
+                ))
+            }
+            (None, _) => self.pacer = None,
+        }
+    }
+
+    pub fn get_pacing_rate(&self) -> Option<f64> {
+        self.pacer.as_ref().map(|pacer| pacer.rate_bps())
+    }
+
+    fn wait_pacer(&mut self, size: usize) {
+        if let Some(pacer) = &mut self.pacer {
+            let wait = pacer.reserve(self.clock.now(), size);
+            if !wait.is_zero() {
+                thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Number of FEC parity shards generated per data shard. 0 disables FEC.
+    pub fn set_fec_overhead_ratio(&mut self, ratio: f32) {
+        self.fec_overhead_ratio = ratio;
+    }
+
+    /// Keep the last sent packets so that their shards can be retransmitted. The returned handle
+    /// can be used from other threads.
+    pub fn enable_retransmission(&mut self, max_cached_packets: usize) -> StreamRetransmitter {
+        let cache = Arc::clone(self.retransmission_cache.get_or_insert_with(|| {
+            Arc::new(Mutex::new(RetransmissionCache {
+                packets: VecDeque::new(),
+                max_packets: max_cached_packets,
+            }))
+        }));
+
+        StreamRetransmitter {
+            clock: Arc::clone(&self.clock),
+            inner: Arc::clone(&self.inner),
+            stream_id: self.stream_id,
+            unreliable: self.unreliable,
+            shard_header_version: self.shard_header_version,
+            reference_time: self.reference_time,
+            cache,
+        }
+    }
+
+    fn write_shard_prefix(
+        &self,
+        sub_buffer: &mut [u8],
+        packet_length: usize,
+        shards_count_field: u32,
+        shard_index: usize,
+        flags: u8,
+        data_size: usize,
+    ) {
+        let tx_r_instant = self
+            .clock
+            .now()
+            .duration_since(self.reference_time)
+            .as_secs_f64();
+
+        write_shard_prefix(
+            self.shard_header_version,
+            sub_buffer,
+            &ShardPrefix {
+                shard_length: packet_length,
+                stream_id: self.stream_id,
+                packet_index: self.next_packet_index,
+                shards_count_field,
+                shard_index,
+                tx_r_instant,
+                flags,
+                data_size: Some(data_size),
+            },
+        );
+    }
+
+    // Send the shards in batches of few syscalls. The prefixes are kept apart from the data, so
+    // they don't overwrite the end of the previous shard. Shards of other streams can be
+    // interleaved between batches
+    fn send_shards_batched(
+        &mut self,
+        buffer: &[u8],
+        shard_data_size: usize,
+        shards_count: usize,
+        parity_shards_count: usize,
+        shards_count_field: u32,
+        flags: u8,
+    ) -> Result<()> {
+        let prefix_size = self.shard_header_version.prefix_size();
+        let data_size = buffer.len() - SHARD_PREFIX_SIZE;
+        let total_shards_count = shards_count + parity_shards_count;
+
+        let mut prefixes = mem::take(&mut self.batch_prefixes);
+        prefixes.resize(MAX_BATCH_SHARDS, [0; SHARD_PREFIX_SIZE]);
+
+        let payload = |idx: usize| {
+            if idx < shards_count {
+                let start = SHARD_PREFIX_SIZE + idx * shard_data_size;
+                &buffer[start..usize::min(start + shard_data_size, buffer.len())]
+            } else {
+                // Parity header and parity data
+                &self.parity_buffers[idx - shards_count][SHARD_PREFIX_SIZE..]
+            }
+        };
+
+        for batch_start in (0..total_shards_count).step_by(MAX_BATCH_SHARDS) {
+            let batch = batch_start..usize::min(batch_start + MAX_BATCH_SHARDS, total_shards_count);
+
+            for (idx, prefix) in batch.clone().zip(&mut prefixes) {
+                let flags = if idx < shards_count {
+                    flags
+                } else {
+                    flags | SHARD_FLAG_PARITY
+                };
+                self.write_shard_prefix(
+                    prefix,
+                    prefix_size + payload(idx).len(),
+                    shards_count_field,
+                    idx,
+                    flags,
+                    data_size,
+                );
+            }
+
+            let mut packets: [(&[u8], &[u8]); MAX_BATCH_SHARDS] = [(&[], &[]); MAX_BATCH_SHARDS];
+            for ((idx, prefix), packet) in batch.clone().zip(&prefixes).zip(&mut packets) {
+                *packet = (&prefix[..prefix_size], payload(idx));
+            }
+
+            self.inner.lock().send_batch(&packets[..batch.len()])?;
+        }
+
+        self.batch_prefixes = prefixes;
+
+        Ok(())
     }
 
     /// Shard and send a buffer with zero copies and zero allocations.
     /// The prefix of each shard is written over the previously sent shard to avoid reallocations.
+    /// If FEC is enabled, parity shards are sent after the data shards.
     pub fn send(&mut self, mut buffer: Buffer<H>) -> Result<()> {
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
+        let prefix_size = self.shard_header_version.prefix_size();
+        let prefix_offset = self.shard_header_version.prefix_offset();
+        let max_shard_data_size = self.max_packet_size - prefix_size;
         let actual_buffer_size = buffer.hidden_offset + buffer.length;
         let data_size = actual_buffer_size - SHARD_PREFIX_SIZE;
-        let shards_count = (data_size as f32 / max_shard_data_size as f32).ceil() as usize;
 
-        for idx in 0..shards_count {
-            // this overlaps with the previous shard, this is intended behavior and allows to
-            // reduce allocations
+        // With FEC, data shards leave room for the parity header so that parity shards still fit
+        // in max_packet_size. The shards count field has 16 bits for each shard type, FEC is not
+        // used for bigger packets (never sent in practice)
+        let fec_shard_data_size = max_shard_data_size - fec::PARITY_HEADER_SIZE;
+        let fec_overhead_ratio = if data_size <= u16::MAX as usize * fec_shard_data_size {
+            self.fec_overhead_ratio
+        } else {
+            0.0
+        };
+        let shard_data_size = if fec_overhead_ratio > 0.0 {
+            fec_shard_data_size
+        } else {
+            max_shard_data_size
+        };
+        let shards_count = (data_size as f32 / shard_data_size as f32).ceil() as usize;
+
+        let fec_layout = FecLayout::new(shards_count, shard_data_size, fec_overhead_ratio);
+        let parity_shards_count = fec_layout.parity_shards;
+        let shards_count_field = fec::pack_shards_count(shards_count, parity_shards_count);
+
+        if parity_shards_count > 0 {
+            let parity_shard_length = prefix_offset + self.max_packet_size;
+            self.parity_buffers
+                .resize_with(parity_shards_count, Vec::new);
+            for parity_buffer in &mut self.parity_buffers {
+                parity_buffer.resize(parity_shard_length, 0);
+                parity_buffer[SHARD_PREFIX_SIZE..][..fec::PARITY_HEADER_SIZE]
+                    .copy_from_slice(&(data_size as u32).to_be_bytes());
+            }
 
-            let packet_start_position = idx * max_shard_data_size;
-            let sub_buffer = &mut buffer.inner[packet_start_position..];
+            self.fec_coder.encode(
+                &fec_layout,
+                &buffer.inner[SHARD_PREFIX_SIZE..actual_buffer_size],
+                &mut self.parity_buffers,
+            )?;
+        }
 
-            // NB: true shard length (account for last shard that is smaller)
-            let packet_length = usize::min(
-                self.max_packet_size,
-                actual_buffer_size - packet_start_position,
-            );
+        self.prefix_backups.clear();
 
-            let tx_r_instant: f32 = Instant::now()
-                .duration_since(self.reference_time)
-                .as_secs_f32();
+        let send_start_instant = self.clock.now();
 
-            // todo: switch to little endian
-            // todo: do not remove sizeof<u32> for packet length
-            sub_buffer[0..4]
-                .copy_from_slice(&((packet_length - mem::size_of::<u32>()) as u32).to_be_bytes());
-            sub_buffer[4..6].copy_from_slice(&self.stream_id.to_be_bytes());
-            sub_buffer[6..10].copy_from_slice(&self.next_packet_index.to_be_bytes());
-            sub_buffer[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
-            sub_buffer[14..18].copy_from_slice(&(idx as u32).to_be_bytes());
-            sub_buffer[18..22].copy_from_slice(&tx_r_instant.to_be_bytes());
-
-            self.inner.lock().send(&sub_buffer[..packet_length])?;
-
-            if idx == 0 {
-                //store next_packet_index - Instant value pair for RTT
-                self.frame_tracker
-                    .insert(self.next_packet_index, Instant::now())
+        // Batches are sent back-to-back, they are used only without pacing
+        if self.pacer.is_none() && self.inner.lock().supports_batching() {
+            self.send_shards_batched(
+                &buffer.inner[..actual_buffer_size],
+                shard_data_size,
+                shards_count,
+                parity_shards_count,
+                shards_count_field,
+                buffer.flags,
+            )?;
+        } else {
+            for idx in 0..shards_count {
+                // this overlaps with the previous shard, this is intended behavior and allows to
+                // reduce allocations
+
+                let packet_start_position = idx * shard_data_size + prefix_offset;
+                let sub_buffer = &mut buffer.inner[packet_start_position..];
+
+                // NB: true shard length (account for last shard that is smaller)
+                let packet_length = usize::min(
+                    prefix_size + shard_data_size,
+                    actual_buffer_size - packet_start_position,
+                );
+
+                if self.retransmission_cache.is_some() && idx > 0 {
+                    let mut backup = [0; SHARD_PREFIX_SIZE];
+                    backup[..prefix_size].copy_from_slice(&sub_buffer[..prefix_size]);
+                    self.prefix_backups.push(backup);
+                }
+
+                self.wait_pacer(packet_length);
+
+                self.write_shard_prefix(
+                    sub_buffer,
+                    packet_length,
+                    shards_count_field,
+                    idx,
+                    buffer.flags,
+                    data_size,
+                );
+
+                send_shard(
+                    &mut **self.inner.lock(),
+                    self.unreliable,
+                    &sub_buffer[..packet_length],
+                )?;
+            }
+
+            for idx in 0..parity_shards_count {
+                let mut parity_buffer = mem::take(&mut self.parity_buffers[idx]);
+                let packet_length = parity_buffer.len() - prefix_offset;
+
+                self.wait_pacer(packet_length);
+
+                self.write_shard_prefix(
+                    &mut parity_buffer[prefix_offset..],
+                    packet_length,
+                    shards_count_field,
+                    shards_count + idx,
+                    buffer.flags | SHARD_FLAG_PARITY,
+                    data_size,
+                );
+                let res = send_shard(
+                    &mut **self.inner.lock(),
+                    self.unreliable,
+                    &parity_buffer[prefix_offset..],
+                );
+
+                self.parity_buffers[idx] = parity_buffer;
+                res?;
             }
         }
-        self.shards_count = shards_count;
+
+        let send_end_instant = self.clock.now();
+        self.last_send_duration = send_end_instant.saturating_duration_since(send_start_instant);
+        self.frame_tracker.insert(
+            self.next_packet_index,
+            send_start_instant,
+            send_end_instant,
+            shards_count + parity_shards_count,
+        );
+
+        if let Some(cache) = &self.retransmission_cache {
+            // Restore the data overwritten by the shard prefixes
+            for (idx, backup) in self.prefix_backups.iter().enumerate() {
+                buffer.inner[(idx + 1) * shard_data_size + prefix_offset..][..prefix_size]
+                    .copy_from_slice(&backup[..prefix_size]);
+            }
+
+            let evicted_buffer = cache.lock().insert(CachedPacket {
+                packet_index: self.next_packet_index,
+                buffer: buffer.inner,
+                size: actual_buffer_size,
+                shard_data_size,
+                shards_count,
+                shards_count_field,
+                flags: buffer.flags,
+            });
+            if let Some(evicted_buffer) = evicted_buffer {
+                self.used_buffers.push(evicted_buffer);
+            }
+        } else {
+            self.used_buffers.push(buffer.inner);
+        }
+
+        self.shards_count = shards_count + parity_shards_count;
         self.next_packet_index += 1;
-        self.used_buffers.push(buffer.inner);
 
         Ok(())
     }
@@ -242,6 +786,7 @@ impl<H: Serialize> StreamSender<H> {
             inner: buffer,
             hidden_offset,
             length: 0,
+            flags: 0,
             _phantom: PhantomData,
         })
     }
@@ -250,6 +795,24 @@ impl<H: Serialize> StreamSender<H> {
         let buffer = self.get_buffer(header)?;
         self.send(buffer)
     }
+
+    /// Send a packet made of `shards_count` full size shards of padding. The shards are sent
+    /// back-to-back, ignoring the pacing rate, so that the receiver can measure their dispersion.
+    pub fn send_padding_train(&mut self, header: &H, shards_count: usize) -> Result<()> {
+        let mut buffer = self.get_buffer(header)?;
+
+        let train_size =
+            shards_count * (self.max_packet_size - self.shard_header_version.prefix_size());
+        let padding_size = train_size.saturating_sub(buffer.hidden_offset - SHARD_PREFIX_SIZE);
+        buffer.get_range_mut(0, padding_size).fill(0);
+        buffer.flags |= SHARD_FLAG_PROBE;
+
+        let pacer = self.pacer.take();
+        let res = self.send(buffer);
+        self.pacer = pacer;
+
+        res
+    }
 }
 
 pub struct ReceiverData<H> {
@@ -267,18 +830,29 @@ pub struct ReceiverData<H> {
     interarrival_jitter: f32,
     ow_delay: f32,
     filtered_ow_delay: f32,
+    raw_ow_delay: f64,
 
     rx_bytes: u32,
     bytes_in_frame: u32,
     bytes_in_frame_app: u32,
 
     frames_skipped: u32,
+    fec_recovered_frames: u32,
+
+    nacks_sent: u32,
+    late_recovered_frames: u32,
+    wasted_retransmissions: u32,
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
+    authentication_failures: u32,
+
+    shard_loss_reports: Vec<ShardLossReport>,
+
+    evicted_packets: u32,
+    discarded_shards: u32,
 
-    highest_rx_frame_index: i32,
-    highest_rx_shard_index: i32,
+    dispersion_rate_bps: f32,
 }
 
 impl<H> ReceiverData<H> {
@@ -303,6 +877,12 @@ impl<H> ReceiverData<H> {
     pub fn get_filtered_ow_delay(&self) -> f32 {
         self.filtered_ow_delay
     }
+    /// Delay of the first received shard of the frame, computed with the send and receive times
+    /// relative to the reference time of each peer. Adding the offset between the peer clocks
+    /// gives the absolute one-way delay.
+    pub fn get_raw_ow_delay(&self) -> f64 {
+        self.raw_ow_delay
+    }
 
     pub fn get_rx_bytes(&self) -> u32 {
         self.rx_bytes
@@ -316,17 +896,44 @@ impl<H> ReceiverData<H> {
     pub fn get_frames_skipped(&self) -> u32 {
         self.frames_skipped
     }
+    pub fn get_fec_recovered_frames(&self) -> u32 {
+        self.fec_recovered_frames
+    }
+    pub fn get_nacks_sent(&self) -> u32 {
+        self.nacks_sent
+    }
+    pub fn get_late_recovered_frames(&self) -> u32 {
+        self.late_recovered_frames
+    }
+    pub fn get_wasted_retransmissions(&self) -> u32 {
+        self.wasted_retransmissions
+    }
     pub fn get_rx_shard_counter(&self) -> u32 {
         self.rx_shard_counter
     }
     pub fn get_duplicated_shard_counter(&self) -> u32 {
         self.duplicated_shard_counter
     }
-    pub fn get_highest_rx_frame_index(&self) -> i32 {
-        self.highest_rx_frame_index
+    /// Shards of any stream dropped because they failed authentication, with stream encryption
+    pub fn get_authentication_failures(&self) -> u32 {
+        self.authentication_failures
+    }
+    /// Received shards of the video frames completed or abandoned since the previous packet
+    pub fn get_shard_loss_reports(&self) -> &[ShardLossReport] {
+        &self.shard_loss_reports
     }
-    pub fn get_highest_rx_shard_index(&self) -> i32 {
-        self.highest_rx_shard_index
+    /// Incomplete packets of this stream dropped to stay within the receive buffer budget
+    pub fn get_evicted_packets(&self) -> u32 {
+        self.evicted_packets
+    }
+    /// Shards of this stream dropped because they didn't fit in the receive buffer budget
+    pub fn get_discarded_shards(&self) -> u32 {
+        self.discarded_shards
+    }
+    /// Receive rate of the packet shards, measured from the arrival of the first shard to the
+    /// arrival of the last shard. For back-to-back shards this estimates the bottleneck capacity.
+    pub fn get_dispersion_rate_bps(&self) -> f32 {
+        self.dispersion_rate_bps
     }
 }
 
@@ -364,6 +971,7 @@ struct ReconstructedPacket {
     interarrival_jitter: f32,
     ow_delay: f32,
     filtered_ow_delay: f32,
+    raw_ow_delay: f64,
 
     rx_bytes: u32,
     bytes_in_frame: u32,
@@ -371,9 +979,22 @@ struct ReconstructedPacket {
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
+    authentication_failures: u32,
+
+    shard_loss_reports: Vec<ShardLossReport>,
+
+    fec_recovered: bool,
+
+    nacks_sent: u32,
+    // completed by a retransmitted shard
+    late_recovered: bool,
+    // retransmitted shards received after their packet was completed or abandoned
+    wasted_retransmissions: u32,
 
-    highest_rx_frame_index: i32,
-    highest_rx_shard_index: i32,
+    evicted_packets: u32,
+    discarded_shards: u32,
+
+    dispersion_rate_bps: f32,
 }
 
 pub struct StreamReceiver<H> {
@@ -388,6 +1009,18 @@ pub struct StreamReceiver<H> {
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
+    authentication_failures: u32,
+
+    fec_recovered_frames: u32,
+
+    nacks_sent: u32,
+    late_recovered_frames: u32,
+    wasted_retransmissions: u32,
+
+    shard_loss_reports: Vec<ShardLossReport>,
+
+    evicted_packets: u32,
+    discarded_shards: u32,
 }
 
 fn wrapping_cmp(lhs: u32, rhs: u32) -> Ordering {
@@ -418,6 +1051,18 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
         self.rx_shard_counter += packet.rx_shard_counter;
 
         self.duplicated_shard_counter += packet.duplicated_shard_counter;
+        self.authentication_failures += packet.authentication_failures;
+
+        self.fec_recovered_frames += packet.fec_recovered as u32;
+
+        self.nacks_sent += packet.nacks_sent;
+        self.late_recovered_frames += packet.late_recovered as u32;
+        self.wasted_retransmissions += packet.wasted_retransmissions;
+
+        self.shard_loss_reports.extend(packet.shard_loss_reports);
+
+        self.evicted_packets += packet.evicted_packets;
+        self.discarded_shards += packet.discarded_shards;
 
         let mut had_packet_loss = false;
         let mut frames_skipped: u32 = 0;
@@ -443,11 +1088,25 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
         let rx_bytes_val = self.rx_bytes;
         let rx_counter = self.rx_shard_counter;
         let duplicated_counter = self.duplicated_shard_counter;
+        let authentication_failures = self.authentication_failures;
+        let fec_recovered_frames = self.fec_recovered_frames;
+        let nacks_sent = self.nacks_sent;
+        let late_recovered_frames = self.late_recovered_frames;
+        let wasted_retransmissions = self.wasted_retransmissions;
+        let evicted_packets = self.evicted_packets;
+        let discarded_shards = self.discarded_shards;
 
         self.frame_interarrival = 0.0;
         self.rx_bytes = 0;
         self.rx_shard_counter = 0;
         self.duplicated_shard_counter = 0;
+        self.authentication_failures = 0;
+        self.fec_recovered_frames = 0;
+        self.nacks_sent = 0;
+        self.late_recovered_frames = 0;
+        self.wasted_retransmissions = 0;
+        self.evicted_packets = 0;
+        self.discarded_shards = 0;
 
         self.last_packet_index = Some(packet.index);
 
@@ -466,18 +1125,29 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
             interarrival_jitter: packet.interarrival_jitter,
             ow_delay: packet.ow_delay,
             filtered_ow_delay: packet.filtered_ow_delay,
+            raw_ow_delay: packet.raw_ow_delay,
 
             rx_bytes: rx_bytes_val,
             bytes_in_frame: packet.bytes_in_frame,
             bytes_in_frame_app: packet.bytes_in_frame_app,
 
             frames_skipped: frames_skipped,
+            fec_recovered_frames,
+
+            nacks_sent,
+            late_recovered_frames,
+            wasted_retransmissions,
 
             rx_shard_counter: rx_counter,
             duplicated_shard_counter: duplicated_counter,
+            authentication_failures,
+
+            shard_loss_reports: mem::take(&mut self.shard_loss_reports),
+
+            evicted_packets,
+            discarded_shards,
 
-            highest_rx_frame_index: packet.highest_rx_frame_index,
-            highest_rx_shard_index: packet.highest_rx_shard_index,
+            dispersion_rate_bps: packet.dispersion_rate_bps,
         })
     }
 }
@@ -485,11 +1155,13 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
 pub enum StreamSocketBuilder {
     Tcp(TcpListener),
     Udp(UdpSocket),
+    Quic(quic::QuicListener),
 }
 
 impl StreamSocketBuilder {
     pub fn listen_for_server(
         timeout: Duration,
+        quic_certificate: quic::QuicCertificate,
         port: u16,
         stream_socket_config: SocketProtocol,
         stream_tos_config: Option<DscpTos>,
@@ -510,6 +1182,13 @@ impl StreamSocketBuilder {
                 send_buffer_bytes,
                 recv_buffer_bytes,
             )?),
+            SocketProtocol::Quic => StreamSocketBuilder::Quic(quic::bind(
+                quic_certificate,
+                port,
+                stream_tos_config,
+                send_buffer_bytes,
+                recv_buffer_bytes,
+            )?),
         })
     }
 
@@ -519,36 +1198,72 @@ impl StreamSocketBuilder {
         port: u16,
         max_packet_size: usize,
         timeout: Duration,
+        shard_header_version: ShardHeaderVersion,
+        encryption_keys: Option<StreamKeys>,
+        clock: SharedClock,
     ) -> ConResult<StreamSocket> {
+        let max_packet_size = shard_header_version.max_packet_size(max_packet_size);
+
         let protocol: SocketProtocol;
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
             match self {
                 StreamSocketBuilder::Udp(socket) => {
                     let (send_socket, receive_socket) =
-                        udp::connect(&socket, server_ip, port, timeout).to_con()?;
+                        udp::connect(&socket, server_ip, port, timeout, max_packet_size)
+                            .to_con()?;
                     protocol = SocketProtocol::Udp;
 
-                    (Box::new(send_socket), Box::new(receive_socket))
+                    (Box::new(send_socket), receive_socket)
                 }
                 StreamSocketBuilder::Tcp(listener) => {
                     let (send_socket, receive_socket) =
                         tcp::accept_from_server(&listener, Some(server_ip), timeout)?;
                     protocol = SocketProtocol::Tcp;
 
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                StreamSocketBuilder::Quic(listener) => {
+                    let (send_socket, receive_socket) =
+                        quic::accept_from_server(listener, server_ip, timeout, max_packet_size)?;
+                    protocol = SocketProtocol::Quic;
+
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
             };
 
+        let encrypted = encryption_keys.is_some();
+        let (send_socket, receive_socket, max_packet_size) = if let Some(keys) = encryption_keys {
+            let (send_socket, receive_socket) = encryption::encrypt(
+                send_socket,
+                receive_socket,
+                &protocol,
+                Arc::clone(&clock),
+                keys,
+            );
+
+            (
+                send_socket,
+                receive_socket,
+                max_packet_size - encryption::ENCRYPTION_OVERHEAD,
+            )
+        } else {
+            (send_socket, receive_socket, max_packet_size)
+        };
+
+        let now = clock.now();
+
         Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
+            clock,
+            reference_time: now,
+            shard_header_version,
+            max_packet_size,
             send_socket: Arc::new(Mutex::new(send_socket)),
             receive_socket,
             shard_recv_state: None,
             stream_recv_components: HashMap::new(),
 
             transport_protocol: protocol,
+            encrypted,
 
             map_rx: HashMap::new(),
             rx_bytes: 0,
@@ -559,14 +1274,16 @@ impl StreamSocketBuilder {
             interarrival_jitter: 0.,
 
             kalman: KalmanFilter::default(),
-            prev_frame_rx_instant: Instant::now(),
+            prev_frame_rx_instant: now,
             prev_frame_tx_r_instant: None,
 
             rx_shard_counter: 0,
             duplicated_shard_counter: 0,
+            authentication_failures: 0,
 
-            highest_rx_frame_index: -1,
-            highest_rx_shard_index: -1,
+            fec_coder: FecCoder::default(),
+
+            discarded_shard_buffer: vec![],
         })
     }
 
@@ -574,22 +1291,29 @@ impl StreamSocketBuilder {
     pub fn connect_to_client(
         timeout: Duration,
         client_ip: IpAddr,
+        client_quic_certificate_hash: [u8; 32],
         port: u16,
         protocol: SocketProtocol,
         dscp: Option<DscpTos>,
         send_buffer_bytes: SocketBufferSize,
         recv_buffer_bytes: SocketBufferSize,
         max_packet_size: usize,
+        shard_header_version: ShardHeaderVersion,
+        encryption_keys: Option<StreamKeys>,
+        clock: SharedClock,
     ) -> ConResult<StreamSocket> {
+        let max_packet_size = shard_header_version.max_packet_size(max_packet_size);
+
         let (send_socket, receive_socket): (Box<dyn SocketWriter>, Box<dyn SocketReader>) =
             match protocol {
                 SocketProtocol::Udp => {
                     let socket =
                         udp::bind(port, dscp, send_buffer_bytes, recv_buffer_bytes).to_con()?;
                     let (send_socket, receive_socket) =
-                        udp::connect(&socket, client_ip, port, timeout).to_con()?;
+                        udp::connect(&socket, client_ip, port, timeout, max_packet_size)
+                            .to_con()?;
 
-                    (Box::new(send_socket), Box::new(receive_socket))
+                    (Box::new(send_socket), receive_socket)
                 }
                 SocketProtocol::Tcp => {
                     let (send_socket, receive_socket) = tcp::connect_to_client(
@@ -600,20 +1324,57 @@ impl StreamSocketBuilder {
                         recv_buffer_bytes,
                     )?;
 
+                    (Box::new(send_socket), Box::new(receive_socket))
+                }
+                SocketProtocol::Quic => {
+                    let (send_socket, receive_socket) = quic::connect_to_client(
+                        timeout,
+                        client_ip,
+                        client_quic_certificate_hash,
+                        port,
+                        dscp,
+                        send_buffer_bytes,
+                        recv_buffer_bytes,
+                        max_packet_size,
+                    )?;
+
                     (Box::new(send_socket), Box::new(receive_socket))
                 }
             };
 
+        let encrypted = encryption_keys.is_some();
+        let (send_socket, receive_socket, max_packet_size) = if let Some(keys) = encryption_keys {
+            let (send_socket, receive_socket) = encryption::encrypt(
+                send_socket,
+                receive_socket,
+                &protocol,
+                Arc::clone(&clock),
+                keys,
+            );
+
+            (
+                send_socket,
+                receive_socket,
+                max_packet_size - encryption::ENCRYPTION_OVERHEAD,
+            )
+        } else {
+            (send_socket, receive_socket, max_packet_size)
+        };
+
+        let now = clock.now();
+
         Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
+            clock,
+            reference_time: now,
+            shard_header_version,
+            max_packet_size,
             send_socket: Arc::new(Mutex::new(send_socket)),
             receive_socket,
             shard_recv_state: None,
             stream_recv_components: HashMap::new(),
 
             transport_protocol: protocol,
+            encrypted,
 
             map_rx: HashMap::new(),
             rx_bytes: 0,
@@ -624,14 +1385,16 @@ impl StreamSocketBuilder {
             interarrival_jitter: 0.,
 
             kalman: KalmanFilter::default(),
-            prev_frame_rx_instant: Instant::now(),
+            prev_frame_rx_instant: now,
             prev_frame_tx_r_instant: None,
 
             rx_shard_counter: 0,
             duplicated_shard_counter: 0,
+            authentication_failures: 0,
+
+            fec_coder: FecCoder::default(),
 
-            highest_rx_frame_index: -1,
-            highest_rx_shard_index: -1,
+            discarded_shard_buffer: vec![],
         })
     }
 }
@@ -640,30 +1403,123 @@ struct RecvState {
     shard_length: usize, // contains prefix length itself
     stream_id: u16,
     packet_index: u32,
-    shards_count: usize,
+    shards_count: usize, // data shards only
+    parity_shards_count: usize,
     shard_index: usize,
+    flags: u8,
+    // without prefix. Always known with V2
+    data_size: Option<usize>,
     packet_cursor: usize, // counts also the prefix bytes
     overwritten_data_backup: Option<[u8; SHARD_PREFIX_SIZE]>,
     should_discard: bool,
+    rx_instant: Instant,
 }
 
 struct InProgressPacket {
     buffer: Vec<u8>,
     buffer_length: usize,
     received_shard_indices: HashSet<usize>,
+    data_shards_received: usize,
+    data_size: Option<usize>, // without prefix. Known after receiving the last or a parity shard
+
+    shards_count: usize, // data shards only
+    parity_shards_count: usize,
+    highest_shard_index: usize,
+    // includes parity shards
+    highest_received_shard_index: usize,
+    reordering_depth: usize,
+    first_rx_instant: Instant,
+    last_nack_instant: Option<Instant>,
+
+    last_rx_instant: Instant,
+    // bytes received after the first shard, including transport headers
+    dispersion_bytes: usize,
+}
+
+impl InProgressPacket {
+    fn total_shards_count(&self) -> usize {
+        self.shards_count + self.parity_shards_count
+    }
+
+    // Run-length encoding of the received state of the first `observed_shards_count` shards
+    fn shard_loss_report(&self, frame_index: u32, observed_shards_count: usize) -> ShardLossReport {
+        let mut runs = vec![];
+        let mut is_received_run = true;
+        let mut run_length = 0;
+        for idx in 0..observed_shards_count {
+            if self.received_shard_indices.contains(&idx) != is_received_run {
+                runs.push(run_length);
+                is_received_run = !is_received_run;
+                run_length = 0;
+            }
+            run_length += 1;
+        }
+        runs.push(run_length);
+
+        ShardLossReport {
+            frame_index,
+            runs,
+            reordering_depth: self.reordering_depth as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct NackTiming {
+    deadline: Duration,
+    retry_interval: Duration,
 }
 
 struct StreamRecvComponents {
-    used_buffer_sender: mpsc::Sender<Vec<u8>>,
-    used_buffer_receiver: mpsc::Receiver<Vec<u8>>,
+    buffer_pool: BufferPool,
     packet_queue: mpsc::Sender<ReconstructedPacket>,
     in_progress_packets: HashMap<u32, InProgressPacket>,
-    discarded_shards_sink: InProgressPacket,
+    // FEC parity shards keep arriving after the packet has been reconstructed
+    last_completed_packet_index: Option<u32>,
+
+    nack_timing: Option<NackTiming>,
+    newest_packet_index: Option<u32>,
+    nacks_sent: u32,
+    // Retransmitted shards received after their packet was completed or abandoned
+    wasted_retransmissions: u32,
+
+    // Only filled for the video stream
+    shard_loss_reports: Vec<ShardLossReport>,
+
+    // Incomplete packets dropped to stay within the buffer budget
+    evicted_packets: u32,
+    // Shards that could not fit in the buffer budget
+    discarded_shards: u32,
+}
+
+impl StreamRecvComponents {
+    // Drop the incomplete packet with the oldest index, returning its index. The missing shards
+    // are reported as lost
+    fn evict_oldest_packet(&mut self, stream_id: u16) -> Option<u32> {
+        let idx = self
+            .in_progress_packets
+            .keys()
+            .copied()
+            .min_by(|&lhs, &rhs| wrapping_cmp(lhs, rhs))?;
+        let packet = self.in_progress_packets.remove(&idx).unwrap();
+
+        if stream_id == VIDEO {
+            self.shard_loss_reports
+                .push(packet.shard_loss_report(idx, packet.total_shards_count()));
+        }
+        self.buffer_pool.put(packet.buffer);
+        self.evicted_packets += 1;
+
+        Some(idx)
+    }
 }
 
 // Note: used buffers don't *have* to be split by stream ID, but doing so improves memory usage
-// todo: impose cap on number of created buffers to avoid OOM crashes
 pub struct StreamSocket {
+    clock: SharedClock,
+    // Time base of the shard timestamps, shared by all streams
+    reference_time: Instant,
+    shard_header_version: ShardHeaderVersion,
     max_packet_size: usize,
     send_socket: Arc<Mutex<Box<dyn SocketWriter>>>,
     receive_socket: Box<dyn SocketReader>,
@@ -671,81 +1527,104 @@ pub struct StreamSocket {
     stream_recv_components: HashMap<u16, StreamRecvComponents>,
 
     transport_protocol: SocketProtocol,
+    // Shards are authenticated, replayed shards must be dropped
+    encrypted: bool,
 
     map_rx: HashMap<u32, HashMap<usize, ShardMapStats>>,
     rx_bytes: u32,
 
-    prev_shard_tx_r_instant: Option<f32>,
+    prev_shard_tx_r_instant: Option<f64>,
     prev_shard_rx_instant: Option<Instant>,
 
     interarrival_jitter: f32,
 
     kalman: KalmanFilter,
     prev_frame_rx_instant: Instant,
-    prev_frame_tx_r_instant: Option<f32>,
+    prev_frame_tx_r_instant: Option<f64>,
 
     rx_shard_counter: u32,
     duplicated_shard_counter: u32,
+    // Counted since the last video packet or the last take_authentication_failures() call
+    authentication_failures: u32,
+
+    fec_coder: FecCoder,
 
-    highest_rx_shard_index: i32,
-    highest_rx_frame_index: i32,
+    // Shards that don't fit in the buffer budget are read here and dropped
+    discarded_shard_buffer: Vec<u8>,
 }
 
 #[derive(Clone)]
 struct ShardMapStats {
-    tx_r_instant: f32,
+    tx_r_instant: f64,
     rx_instant: Instant,
     rx_bytes: u32,
     rx_bytes_app: u32,
 }
 impl StreamSocket {
+    /// Time base of the shard send timestamps. Used to synchronize with the peer clock.
+    pub fn reference_time(&self) -> Instant {
+        self.reference_time
+    }
+
+    // Video and probe shards are sent on the unreliable channel of the transport, if it has one
     pub fn request_stream<T>(&self, stream_id: u16) -> StreamSender<T> {
         StreamSender {
+            clock: Arc::clone(&self.clock),
             inner: Arc::clone(&self.send_socket),
             stream_id,
+            unreliable: matches!(stream_id, VIDEO | PROBE),
+            shard_header_version: self.shard_header_version,
             max_packet_size: self.max_packet_size,
             next_packet_index: 0,
             used_buffers: vec![],
             _phantom: PhantomData,
             shards_count: 0,
-            reference_time: Instant::now(),
-            frame_tracker: FrameTracker::new(),
+            reference_time: self.reference_time,
+            frame_tracker: Arc::new(FrameTracker::new(self.reference_time)),
+            fec_overhead_ratio: 0.0,
+            fec_coder: FecCoder::default(),
+            parity_buffers: vec![],
+            retransmission_cache: None,
+            prefix_backups: vec![],
+            batch_prefixes: vec![],
+            pacer: None,
+            last_send_duration: Duration::ZERO,
         }
     }
 
-    // max_concurrent_buffers: number of buffers allocated by this call which will be reused to
-    // receive packets for this stream ID. If packets are not read fast enough, the shards received
-    // for this particular stream will be discarded
+    // max_buffered_bytes: memory budget of the buffers used to receive packets for this stream ID,
+    // including the packets not yet read. If the budget is exceeded, the oldest incomplete packet
+    // is evicted. If packets are not read fast enough, the shards received for this particular
+    // stream will be discarded
     pub fn subscribe_to_stream<T>(
         &mut self,
         stream_id: u16,
-        max_concurrent_buffers: usize,
+        max_buffered_bytes: usize,
     ) -> StreamReceiver<T> {
         let (packet_sender, packet_receiver) = mpsc::channel();
-        let (used_buffer_sender, used_buffer_receiver) = mpsc::channel();
-
-        for _ in 0..max_concurrent_buffers {
-            used_buffer_sender.send(vec![]).ok();
-        }
+        let buffer_pool = BufferPool::new(max_buffered_bytes);
+        let used_buffer_queue = buffer_pool.release_queue();
 
         self.stream_recv_components.insert(
             stream_id,
             StreamRecvComponents {
-                used_buffer_sender: used_buffer_sender.clone(),
-                used_buffer_receiver,
+                buffer_pool,
                 packet_queue: packet_sender,
                 in_progress_packets: HashMap::new(),
-                discarded_shards_sink: InProgressPacket {
-                    buffer: vec![],
-                    buffer_length: 0,
-                    received_shard_indices: HashSet::new(),
-                },
+                last_completed_packet_index: None,
+                nack_timing: None,
+                newest_packet_index: None,
+                nacks_sent: 0,
+                wasted_retransmissions: 0,
+                shard_loss_reports: vec![],
+                evicted_packets: 0,
+                discarded_shards: 0,
             },
         );
 
         StreamReceiver {
             packet_receiver,
-            used_buffer_queue: used_buffer_sender,
+            used_buffer_queue,
             _phantom: PhantomData,
             last_packet_index: None,
 
@@ -755,50 +1634,192 @@ impl StreamSocket {
 
             rx_shard_counter: 0,
             duplicated_shard_counter: 0,
+            authentication_failures: 0,
+
+            fec_recovered_frames: 0,
+
+            nacks_sent: 0,
+            late_recovered_frames: 0,
+            wasted_retransmissions: 0,
+
+            shard_loss_reports: vec![],
+
+            evicted_packets: 0,
+            discarded_shards: 0,
+        }
+    }
+
+    /// Pass the outgoing packets through a network impairment emulator. Only UDP is supported,
+    /// since loss and reordering would corrupt a TCP or QUIC stream.
+    pub fn enable_network_impairment(&mut self, impairment: NetworkImpairment) {
+        if matches!(self.transport_protocol, SocketProtocol::Udp) {
+            impairment::impair(
+                &mut self.send_socket.lock(),
+                Arc::clone(&self.clock),
+                impairment,
+            );
+        }
+    }
+
+    /// Shards dropped because they failed authentication since the last call or the last video
+    /// packet, which also reports them
+    pub fn take_authentication_failures(&mut self) -> u32 {
+        self.authentication_failures += self.receive_socket.take_authentication_failures();
+
+        mem::take(&mut self.authentication_failures)
+    }
+
+    /// Request retransmission of the missing shards of incomplete packets of a stream. NACKs are
+    /// only generated for packets whose first shard was received less than `deadline` ago.
+    /// recv() returns at least once per `retry_interval`, so that poll_nacks() can be called
+    /// even when no shard is received.
+    pub fn enable_nack(
+        &mut self,
+        stream_id: u16,
+        deadline: Duration,
+        retry_interval: Duration,
+    ) -> Result<()> {
+        if let Some(components) = self.stream_recv_components.get_mut(&stream_id) {
+            components.nack_timing = Some(NackTiming {
+                deadline,
+                retry_interval,
+            });
+
+            // A zero timeout is not allowed by the sockets
+            self.receive_socket
+                .set_timeout(Duration::max(retry_interval, Duration::from_millis(1)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Collect the retransmission requests for all streams with NACK enabled. A shard is
+    /// considered missing if a shard with higher index or a shard of a newer packet has been
+    /// received. Each packet is requested at most once per retry interval.
+    pub fn poll_nacks(&mut self) -> Vec<NackPacket> {
+        let now = self.clock.now();
+
+        let mut nacks = vec![];
+        for (&stream_id, components) in &mut self.stream_recv_components {
+            let Some(timing) = components.nack_timing else {
+                continue;
+            };
+
+            for (&packet_index, packet) in &mut components.in_progress_packets {
+                if now.saturating_duration_since(packet.first_rx_instant) > timing.deadline
+                    || packet.last_nack_instant.map_or(false, |instant| {
+                        now.saturating_duration_since(instant) < timing.retry_interval
+                    })
+                {
+                    continue;
+                }
+
+                let is_superseded = components.newest_packet_index.map_or(false, |idx| {
+                    wrapping_cmp(idx, packet_index) == Ordering::Greater
+                });
+                let end_index = if is_superseded {
+                    packet.shards_count
+                } else {
+                    packet.highest_shard_index
+                };
+
+                let shard_indices = (0..end_index)
+                    .filter(|idx| !packet.received_shard_indices.contains(idx))
+                    .take(MAX_NACK_SHARDS)
+                    .map(|idx| idx as u32)
+                    .collect::<Vec<_>>();
+                if shard_indices.is_empty() {
+                    continue;
+                }
+
+                packet.last_nack_instant = Some(now);
+                components.nacks_sent += 1;
+
+                nacks.push(NackPacket {
+                    stream_id,
+                    packet_index,
+                    shard_indices,
+                });
+            }
+        }
+
+        nacks
+    }
+
+    // Size of the IP and transport headers of each shard
+    fn transport_header_size(&self) -> u32 {
+        match self.transport_protocol {
+            SocketProtocol::Udp => 42,
+            SocketProtocol::Tcp => 54,
+            SocketProtocol::Quic => 42 + quic::QUIC_HEADER_SIZE as u32,
         }
     }
 
     pub fn recv(&mut self) -> ConResult {
+        let transport_header_size = self.transport_header_size();
+        let prefix_size = self.shard_header_version.prefix_size();
+
         let shard_recv_state_mut = if let Some(state) = &mut self.shard_recv_state {
             state
         } else {
             let mut bytes = [0; SHARD_PREFIX_SIZE];
-            let count = self.receive_socket.peek(&mut bytes)?;
-            if count < SHARD_PREFIX_SIZE {
+            let count = self.receive_socket.peek(&mut bytes[..prefix_size])?;
+            if count < prefix_size {
                 return alvr_common::try_again();
             }
 
-            // todo: switch to little endian
-            // todo: do not remove sizeof<u32> for packet length
-            let shard_length = mem::size_of::<u32>()
-                + u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
-            let stream_id = u16::from_be_bytes(bytes[4..6].try_into().unwrap());
-            let packet_index = u32::from_be_bytes(bytes[6..10].try_into().unwrap());
-            let shards_count = u32::from_be_bytes(bytes[10..14].try_into().unwrap()) as usize;
-            let shard_index = u32::from_be_bytes(bytes[14..18].try_into().unwrap()) as usize;
-            let tx_r_instant = f32::from_be_bytes(bytes[18..22].try_into().unwrap());
-
-            if stream_id == VIDEO {
-                let rx_instant = Instant::now();
-
-                if self.highest_rx_frame_index == packet_index as i32 {
-                    if self.highest_rx_shard_index < shard_index as i32 {
-                        self.highest_rx_shard_index = shard_index as i32;
-                    }
-                } else if self.highest_rx_frame_index < packet_index as i32 {
-                    self.highest_rx_frame_index = packet_index as i32;
-                    self.highest_rx_shard_index = shard_index as i32;
+            // Batched readers receive the shards ahead of time
+            let now = self.clock.now();
+            let rx_instant = self
+                .receive_socket
+                .peeked_packet_age()
+                .and_then(|age| now.checked_sub(age))
+                .unwrap_or(now);
+
+            let ShardPrefix {
+                shard_length,
+                stream_id,
+                packet_index,
+                shards_count_field,
+                shard_index,
+                tx_r_instant,
+                flags,
+                data_size,
+            } = read_shard_prefix(
+                self.shard_header_version,
+                &bytes,
+                rx_instant
+                    .saturating_duration_since(self.reference_time)
+                    .as_secs_f64(),
+            )?;
+            let (shards_count, parity_shards_count) = fec::unpack_shards_count(shards_count_field);
+
+            self.authentication_failures += self.receive_socket.take_authentication_failures();
+
+            // Shards of packets already reconstructed (or older) can no longer be used. This
+            // includes the late FEC parity shards
+            let is_stale = self
+                .stream_recv_components
+                .get(&stream_id)
+                .and_then(|components| components.last_completed_packet_index)
+                .map_or(false, |idx| {
+                    wrapping_cmp(packet_index, idx) != Ordering::Greater
+                });
+            if is_stale && flags & SHARD_FLAG_RETRANSMISSION != 0 {
+                if let Some(components) = self.stream_recv_components.get_mut(&stream_id) {
+                    components.wasted_retransmissions += 1;
                 }
+            }
 
-                let header_bytes_transport: u32 = match self.transport_protocol {
-                    SocketProtocol::Udp => 42,
-                    SocketProtocol::Tcp => 54,
-                };
+            // Replay protection: stale shards are dropped before being accounted
+            let is_replayed = self.encrypted && is_stale;
+
+            if stream_id == VIDEO && !is_replayed {
                 let packet = ShardMapStats {
                     tx_r_instant,
                     rx_instant,
-                    rx_bytes: shard_length as u32 + header_bytes_transport,
-                    rx_bytes_app: (shard_length - SHARD_PREFIX_SIZE) as u32,
+                    rx_bytes: shard_length as u32 + transport_header_size,
+                    rx_bytes_app: (shard_length - prefix_size) as u32,
                 };
 
                 let shards_map = self.map_rx.entry(packet_index).or_insert(HashMap::new());
@@ -810,7 +1831,7 @@ impl StreamSocket {
                     self.rx_shard_counter += 1;
                 }
 
-                self.rx_bytes += shard_length as u32 + header_bytes_transport;
+                self.rx_bytes += shard_length as u32 + transport_header_size;
 
                 // Jitter
                 {
@@ -818,7 +1839,7 @@ impl StreamSocket {
                         (self.prev_shard_rx_instant, self.prev_shard_tx_r_instant)
                     {
                         let transit_diff = (rx_instant - prev_shard_rx_instant).as_secs_f32()
-                            - (tx_r_instant - prev_shard_tx_r_instant); // D(i-1,i), according to RFC 3550
+                            - (tx_r_instant - prev_shard_tx_r_instant) as f32; // D(i-1,i), according to RFC 3550
                         self.interarrival_jitter +=
                             (transit_diff.abs() - self.interarrival_jitter) / 16.0;
                     }
@@ -831,10 +1852,14 @@ impl StreamSocket {
                 stream_id,
                 packet_index,
                 shards_count,
+                parity_shards_count,
                 shard_index,
+                flags,
+                data_size,
                 packet_cursor: 0,
                 overwritten_data_backup: None,
-                should_discard: false,
+                should_discard: is_replayed,
+                rx_instant,
             })
         };
 
@@ -849,73 +1874,124 @@ impl StreamSocket {
             return alvr_common::try_again();
         };
 
-        let in_progress_packet = if shard_recv_state_mut.should_discard {
-            &mut components.discarded_shards_sink
-        } else if let Some(packet) = components
-            .in_progress_packets
-            .get_mut(&shard_recv_state_mut.packet_index)
-        {
-            packet
-        } else if let Some(buffer) = components.used_buffer_receiver.try_recv().ok().or_else(|| {
-            // By default, try to dequeue a used buffer. In case none were found, recycle one of the
-            // in progress packets, chances are these buffers are "dead" because one of their shards
-            // has been dropped by the network.
-            let idx = *components.in_progress_packets.iter().next()?.0;
-            Some(components.in_progress_packets.remove(&idx).unwrap().buffer)
-        }) {
-            // NB: Can't use entry pattern because we want to allow bailing out on the line above
-            components.in_progress_packets.insert(
-                shard_recv_state_mut.packet_index,
-                InProgressPacket {
-                    buffer,
-                    buffer_length: 0,
-                    // todo: find a way to skipping this allocation
-                    received_shard_indices: HashSet::with_capacity(
-                        shard_recv_state_mut.shards_count,
-                    ),
-                },
-            );
-            components
-                .in_progress_packets
-                .get_mut(&shard_recv_state_mut.packet_index)
-                .unwrap()
+        let max_shard_data_size = self.max_packet_size - prefix_size;
+        let fec_layout = (shard_recv_state_mut.parity_shards_count > 0).then(|| {
+            FecLayout::from_counts(
+                shard_recv_state_mut.shards_count,
+                shard_recv_state_mut.parity_shards_count,
+                max_shard_data_size - fec::PARITY_HEADER_SIZE,
+            )
+        });
+        let shard_position = if let Some(layout) = &fec_layout {
+            layout.shard_position(shard_recv_state_mut.shard_index)
         } else {
-            // This branch may be hit in case the thread related to the stream hangs for some reason
-            shard_recv_state_mut.should_discard = true;
-            shard_recv_state_mut.packet_cursor = 0; // reset cursor from old shards
-                                                    // always write at the start of the packet so the buffer doesn't grow much
-            shard_recv_state_mut.shard_index = 0;
-
-            &mut components.discarded_shards_sink
+            shard_recv_state_mut.shard_index * max_shard_data_size
         };
+        // Note: the prefix is written too, right before the shard data
+        let packet_start_index = shard_position + self.shard_header_version.prefix_offset();
 
-        let max_shard_data_size = self.max_packet_size - SHARD_PREFIX_SIZE;
-        // Note: there is no prefix offset, since we want to write the prefix too.
-        let packet_start_index = shard_recv_state_mut.shard_index * max_shard_data_size;
-
-        // Prepare buffer to accomodate receiving shard
+        if !shard_recv_state_mut.should_discard
+            && !components
+                .in_progress_packets
+                .contains_key(&shard_recv_state_mut.packet_index)
         {
-            // Note: this contains the prefix offset
-            in_progress_packet.buffer_length = usize::max(
-                in_progress_packet.buffer_length,
-                packet_start_index + shard_recv_state_mut.shard_length,
-            );
+            if components
+                .last_completed_packet_index
+                .map(|idx| {
+                    wrapping_cmp(shard_recv_state_mut.packet_index, idx) != Ordering::Greater
+                })
+                .unwrap_or(false)
+            {
+                // The packet has already been reconstructed (or is older), don't allocate a buffer
+                shard_recv_state_mut.should_discard = true;
+            } else {
+                components.in_progress_packets.insert(
+                    shard_recv_state_mut.packet_index,
+                    InProgressPacket {
+                        buffer: components.buffer_pool.take(),
+                        buffer_length: 0,
+                        // todo: find a way to skipping this allocation
+                        received_shard_indices: HashSet::with_capacity(
+                            shard_recv_state_mut.shards_count
+                                + shard_recv_state_mut.parity_shards_count,
+                        ),
+                        data_shards_received: 0,
+                        data_size: None,
+                        shards_count: shard_recv_state_mut.shards_count,
+                        parity_shards_count: shard_recv_state_mut.parity_shards_count,
+                        highest_shard_index: 0,
+                        highest_received_shard_index: 0,
+                        reordering_depth: 0,
+                        first_rx_instant: shard_recv_state_mut.rx_instant,
+                        last_nack_instant: None,
+                        last_rx_instant: shard_recv_state_mut.rx_instant,
+                        dispersion_bytes: 0,
+                    },
+                );
+            }
+        }
+
+        // Make room for the shard within the buffer budget. This may be hit if the packets are
+        // not read fast enough or if many packets are incomplete because of network loss
+        if !shard_recv_state_mut.should_discard {
+            let required_length = packet_start_index + shard_recv_state_mut.shard_length;
+            while !components.buffer_pool.try_grow(
+                &mut components
+                    .in_progress_packets
+                    .get_mut(&shard_recv_state_mut.packet_index)
+                    .unwrap()
+                    .buffer,
+                required_length,
+            ) {
+                // If this is the oldest packet, it's the one evicted
+                if components
+                    .evict_oldest_packet(shard_recv_state_mut.stream_id)
+                    .map_or(true, |idx| idx == shard_recv_state_mut.packet_index)
+                {
+                    shard_recv_state_mut.should_discard = true;
+                    components.discarded_shards += 1;
 
-            if in_progress_packet.buffer.len() < in_progress_packet.buffer_length {
-                in_progress_packet
-                    .buffer
-                    .resize(in_progress_packet.buffer_length, 0);
+                    break;
+                }
             }
         }
 
+        if shard_recv_state_mut.should_discard {
+            // The shard must still be consumed from the socket
+            self.discarded_shard_buffer
+                .resize(shard_recv_state_mut.shard_length, 0);
+            while shard_recv_state_mut.packet_cursor < shard_recv_state_mut.shard_length {
+                let size = self.receive_socket.recv(
+                    &mut self.discarded_shard_buffer
+                        [shard_recv_state_mut.packet_cursor..shard_recv_state_mut.shard_length],
+                )?;
+                shard_recv_state_mut.packet_cursor += size;
+            }
+
+            self.shard_recv_state = None;
+
+            return Ok(());
+        }
+
+        let in_progress_packet = components
+            .in_progress_packets
+            .get_mut(&shard_recv_state_mut.packet_index)
+            .unwrap();
+        // Note: this contains the prefix offset
+        in_progress_packet.buffer_length = usize::max(
+            in_progress_packet.buffer_length,
+            packet_start_index + shard_recv_state_mut.shard_length,
+        );
+
         let sub_buffer = &mut in_progress_packet.buffer[packet_start_index..];
 
         // Read shard into the single contiguous buffer
         {
             // Backup the small section of bytes that will be overwritten by reading from socket.
             if shard_recv_state_mut.overwritten_data_backup.is_none() {
-                shard_recv_state_mut.overwritten_data_backup =
-                    Some(sub_buffer[..SHARD_PREFIX_SIZE].try_into().unwrap())
+                let mut backup = [0; SHARD_PREFIX_SIZE];
+                backup[..prefix_size].copy_from_slice(&sub_buffer[..prefix_size]);
+                shard_recv_state_mut.overwritten_data_backup = Some(backup);
             }
 
             // This loop may bail out at any time if a timeout is reached. This is correctly handled by
@@ -930,29 +2006,116 @@ impl StreamSocket {
 
             // Restore backed up bytes
             // Safety: overwritten_data_backup is always set just before receiving the packet
-            sub_buffer[..SHARD_PREFIX_SIZE]
-                .copy_from_slice(&shard_recv_state_mut.overwritten_data_backup.take().unwrap());
+            sub_buffer[..prefix_size].copy_from_slice(
+                &shard_recv_state_mut.overwritten_data_backup.take().unwrap()[..prefix_size],
+            );
         }
 
-        if !shard_recv_state_mut.should_discard {
-            if !in_progress_packet
-                .received_shard_indices
-                .contains(&shard_recv_state_mut.shard_index)
+        if in_progress_packet
+            .received_shard_indices
+            .insert(shard_recv_state_mut.shard_index)
+        {
+            // The first shard marks the start of the dispersion interval
+            if in_progress_packet.received_shard_indices.len() > 1 {
+                in_progress_packet.dispersion_bytes +=
+                    shard_recv_state_mut.shard_length + transport_header_size as usize;
+            }
+            in_progress_packet.last_rx_instant = Instant::max(
+                in_progress_packet.last_rx_instant,
+                shard_recv_state_mut.rx_instant,
+            );
+
+            if let Some(data_size) = shard_recv_state_mut.data_size {
+                in_progress_packet.data_size = Some(data_size);
+            }
+
+            // Retransmitted shards are late by design, they don't indicate reordering
+            if shard_recv_state_mut.flags & SHARD_FLAG_RETRANSMISSION != 0 {
+                in_progress_packet.highest_received_shard_index = usize::max(
+                    in_progress_packet.highest_received_shard_index,
+                    shard_recv_state_mut.shard_index,
+                );
+            } else if shard_recv_state_mut.shard_index
+                < in_progress_packet.highest_received_shard_index
             {
-                in_progress_packet
-                    .received_shard_indices
-                    .insert(shard_recv_state_mut.shard_index);
+                in_progress_packet.reordering_depth = usize::max(
+                    in_progress_packet.reordering_depth,
+                    in_progress_packet.highest_received_shard_index
+                        - shard_recv_state_mut.shard_index,
+                );
+            } else {
+                in_progress_packet.highest_received_shard_index = shard_recv_state_mut.shard_index;
             }
+
+            if shard_recv_state_mut.shard_index < shard_recv_state_mut.shards_count {
+                in_progress_packet.data_shards_received += 1;
+                in_progress_packet.highest_shard_index = usize::max(
+                    in_progress_packet.highest_shard_index,
+                    shard_recv_state_mut.shard_index,
+                );
+
+                if shard_recv_state_mut.shard_index + 1 == shard_recv_state_mut.shards_count {
+                    in_progress_packet.data_size = Some(
+                        packet_start_index + shard_recv_state_mut.shard_length - SHARD_PREFIX_SIZE,
+                    );
+                }
+            } else {
+                // Parity shards carry the packet data size
+                in_progress_packet.data_size = Some(u32::from_be_bytes(
+                    sub_buffer[prefix_size..][..fec::PARITY_HEADER_SIZE]
+                        .try_into()
+                        .unwrap(),
+                ) as usize);
+            }
+        }
+
+        if components
+            .newest_packet_index
+            .map(|idx| wrapping_cmp(shard_recv_state_mut.packet_index, idx) == Ordering::Greater)
+            .unwrap_or(true)
+        {
+            components.newest_packet_index = Some(shard_recv_state_mut.packet_index);
         }
 
+        let mut fec_recovered = false;
+        let is_packet_complete = if in_progress_packet.data_shards_received
+            == shard_recv_state_mut.shards_count
+        {
+            true
+        } else if let (Some(layout), Some(data_size)) = (&fec_layout, in_progress_packet.data_size)
+        {
+            if layout.can_reconstruct(&in_progress_packet.received_shard_indices) {
+                match self.fec_coder.reconstruct(
+                    layout,
+                    &mut in_progress_packet.buffer,
+                    &in_progress_packet.received_shard_indices,
+                    data_size,
+                ) {
+                    Ok(()) => {
+                        fec_recovered = true;
+                        true
+                    }
+                    Err(e) => {
+                        debug!("FEC reconstruction failed: {e}");
+                        false
+                    }
+                }
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+
         let mut frame_span = 0.0;
         let mut frame_interarrival: f32 = 0.0;
+        let mut raw_ow_delay = 0.0;
 
         let mut all_bytes_in_frame: u32 = 0;
         let mut all_bytes_in_frame_app: u32 = 0;
 
         // Check if packet is complete and send
-        if in_progress_packet.received_shard_indices.len() == shard_recv_state_mut.shards_count {
+        if is_packet_complete {
             if shard_recv_state_mut.stream_id == VIDEO {
                 if let Some(inner_map) = self.map_rx.get(&shard_recv_state_mut.packet_index) {
                     let values: Vec<&ShardMapStats> = inner_map.values().collect();
@@ -969,11 +2132,19 @@ impl StreamSocket {
                     all_bytes_in_frame = values.iter().map(|shard| shard.rx_bytes).sum();
                     all_bytes_in_frame_app = values.iter().map(|shard| shard.rx_bytes_app).sum();
 
+                    if let Some(first_shard) = values.iter().min_by_key(|shard| shard.rx_instant) {
+                        let rx_r_instant = first_shard
+                            .rx_instant
+                            .saturating_duration_since(self.reference_time)
+                            .as_secs_f64();
+                        raw_ow_delay = (rx_r_instant - first_shard.tx_r_instant);
+                    }
+
                     // One way delay gradient
                     if let Some(first_shard_stats) = inner_map.get(&0) {
                         if let Some(prev_frame_tx_r_instant) = self.prev_frame_tx_r_instant {
                             self.kalman.ow_delay = frame_interarrival
-                                - (first_shard_stats.tx_r_instant - prev_frame_tx_r_instant);
+                                - (first_shard_stats.tx_r_instant - prev_frame_tx_r_instant) as f32;
                         }
                         self.prev_frame_tx_r_instant = Some(first_shard_stats.tx_r_instant);
 
@@ -1000,7 +2171,59 @@ impl StreamSocket {
                 }
             }
 
-            let size = in_progress_packet.buffer_length;
+            // Packets completed by reordered original shards are not counted
+            let late_recovered = shard_recv_state_mut.flags & SHARD_FLAG_RETRANSMISSION != 0;
+            let dispersion_s = in_progress_packet
+                .last_rx_instant
+                .saturating_duration_since(in_progress_packet.first_rx_instant)
+                .as_secs_f32();
+            let dispersion_rate_bps = if dispersion_s > 0.0 {
+                in_progress_packet.dispersion_bytes as f32 * 8.0 / dispersion_s
+            } else {
+                0.0
+            };
+            let size = in_progress_packet
+                .data_size
+                .map(|data_size| SHARD_PREFIX_SIZE + data_size)
+                .unwrap_or(in_progress_packet.buffer_length);
+
+            if shard_recv_state_mut.stream_id == VIDEO {
+                // The remaining parity shards were not needed, they are not reported as lost
+                let report = in_progress_packet.shard_loss_report(
+                    shard_recv_state_mut.packet_index,
+                    in_progress_packet.highest_received_shard_index + 1,
+                );
+
+                // Older packets won't be completed anymore, all their missing shards are lost
+                let mut abandoned_indices = components
+                    .in_progress_packets
+                    .keys()
+                    .copied()
+                    .filter(|&idx| {
+                        wrapping_cmp(idx, shard_recv_state_mut.packet_index) == Ordering::Less
+                    })
+                    .collect::<Vec<_>>();
+                abandoned_indices.sort_by(|&lhs, &rhs| wrapping_cmp(lhs, rhs));
+                for idx in abandoned_indices {
+                    let packet = &components.in_progress_packets[&idx];
+                    components
+                        .shard_loss_reports
+                        .push(packet.shard_loss_report(idx, packet.total_shards_count()));
+                }
+
+                components.shard_loss_reports.push(report);
+            }
+
+            // Keep only shards with later packet index (using wrapping logic)
+            while let Some((idx, _)) = components.in_progress_packets.iter().find(|(idx, _)| {
+                wrapping_cmp(**idx, shard_recv_state_mut.packet_index) == Ordering::Less
+            }) {
+                let idx = *idx; // fix borrow rule
+                let packet = components.in_progress_packets.remove(&idx).unwrap();
+
+                // Recycle buffer
+                components.buffer_pool.put(packet.buffer);
+            }
             components
                 .packet_queue
                 .send(ReconstructedPacket {
@@ -1020,6 +2243,7 @@ impl StreamSocket {
                     interarrival_jitter: self.interarrival_jitter,
                     ow_delay: self.kalman.ow_delay,
                     filtered_ow_delay: self.kalman.m_current,
+                    raw_ow_delay,
 
                     rx_bytes: self.rx_bytes,
                     bytes_in_frame: all_bytes_in_frame,
@@ -1027,16 +2251,33 @@ impl StreamSocket {
 
                     rx_shard_counter: self.rx_shard_counter,
                     duplicated_shard_counter: self.duplicated_shard_counter,
+                    authentication_failures: self.authentication_failures,
+
+                    shard_loss_reports: mem::take(&mut components.shard_loss_reports),
+
+                    fec_recovered,
+
+                    nacks_sent: components.nacks_sent,
+                    late_recovered,
+                    wasted_retransmissions: components.wasted_retransmissions,
 
-                    highest_rx_frame_index: self.highest_rx_frame_index,
-                    highest_rx_shard_index: self.highest_rx_shard_index,
+                    evicted_packets: components.evicted_packets,
+                    discarded_shards: components.discarded_shards,
+
+                    dispersion_rate_bps,
                 })
                 .ok();
+            components.nacks_sent = 0;
+            components.wasted_retransmissions = 0;
+            components.evicted_packets = 0;
+            components.discarded_shards = 0;
+            components.last_completed_packet_index = Some(shard_recv_state_mut.packet_index);
 
             if shard_recv_state_mut.stream_id == VIDEO {
                 self.rx_bytes = 0;
                 self.rx_shard_counter = 0;
                 self.duplicated_shard_counter = 0;
+                self.authentication_failures = 0;
 
                 // Keep only shards data from the latest packets (using wrapping logic)
                 let mut idxs_to_remove = Vec::new();
@@ -1051,17 +2292,6 @@ impl StreamSocket {
                     self.map_rx.remove(&idx);
                 }
             }
-
-            // Keep only shards with later packet index (using wrapping logic)
-            while let Some((idx, _)) = components.in_progress_packets.iter().find(|(idx, _)| {
-                wrapping_cmp(**idx, shard_recv_state_mut.packet_index) == Ordering::Less
-            }) {
-                let idx = *idx; // fix borrow rule
-                let packet = components.in_progress_packets.remove(&idx).unwrap();
-
-                // Recycle buffer
-                components.used_buffer_sender.send(packet.buffer).ok();
-            }
         }
 
         // Mark current shard as read and allow for a new shard to be read